@@ -1,6 +1,42 @@
 pub mod sharedkernel;
 pub mod user;
 
+use sharedkernel::breach;
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("import-breach") => import_breach(&args[2..]),
+        _ => println!("Hello, world!"),
+    }
+}
+
+// brokoli import-breach <input> <output> [min_count]
+fn import_breach(args: &[String]) {
+    if args.len() < 2 {
+        eprintln!("Usage: brokoli import-breach <input> <output> [min_count]");
+        std::process::exit(2);
+    }
+
+    let min_count: u64 = args.get(2).and_then(|c| c.parse().ok()).unwrap_or(0);
+    let result = File::open(&args[0])
+        .map_err(|_| sharedkernel::error::bad_request_error!("Input file can not be read"))
+        .and_then(|input| {
+            let output = File::create(&args[1]).map_err(|_| {
+                sharedkernel::error::internal_server_error!("Output file can not be created")
+            })?;
+            breach::import(BufReader::new(input), BufWriter::new(output), min_count)
+        });
+
+    match result {
+        Ok(written) => println!("Imported {} breached password hashes", written),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,386 @@
+use super::{client::ClientInfo, config, error};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/*
+==============================================================================================================================
+IP access rule
+==============================================================================================================================
+Allowlist / denylist berbasis CIDR (IPv4 dan IPv6) untuk setiap kelompok route, misal "admin" atau "registration".
+Untuk setiap kelompok :
+- IP yang cocok dengan `deny` selalu ditolak.
+- Jika `allow` tidak kosong, hanya IP yang cocok dengan `allow` yang diizinkan.
+Kelompok route yang tidak dikonfigurasi tidak dibatasi.
+
+Konfigurasi dibaca dari file JSON dan dibaca ulang secara otomatis ketika file berubah. Alamat IP client diambil dari
+header X-Forwarded-For hanya jika request datang dari proxy yang terdaftar di `trusted_proxies`.
+*/
+
+/// Blok alamat IP, misal 10.0.0.0/8 atau 2001:db8::/32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /**
+    Membentuk Cidr dari teks. Alamat IP tanpa prefix dianggap sebagai satu alamat.
+
+    # Arguments
+    * `text` - CIDR, misal "192.168.0.0/16"
+    */
+    pub fn parse(text: &str) -> Option<Cidr> {
+        let (address, prefix) = match text.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().ok()?)),
+            None => (text.trim(), None),
+        };
+        let network = address.parse::<IpAddr>().ok()?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max_prefix);
+        if prefix > max_prefix {
+            return None;
+        }
+        Some(Cidr { network, prefix })
+    }
+
+    /// Panjang prefix, semakin besar semakin spesifik
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Memeriksa apakah alamat IP berada di dalam blok ini
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        // IPv4-mapped IPv6 (::ffff:10.0.0.1) diperlakukan sebagai IPv4
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Cidr::parse(&text).ok_or_else(|| serde::de::Error::custom(format!("invalid CIDR {}", text)))
+    }
+}
+
+/// Aturan akses untuk satu kelompok route
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AccessRule {
+    pub allow: Vec<Cidr>,
+    pub deny: Vec<Cidr>,
+}
+
+impl AccessRule {
+    /**
+    Menolak IP yang masuk denylist, atau tidak masuk allowlist jika allowlist dikonfigurasi
+
+    # Arguments
+    * `ip` - Alamat IP client
+    */
+    pub fn check(&self, ip: &IpAddr) -> Result<(), error::ApplicationError<'static>> {
+        if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return Err(error::forbidden_error!(
+                "Sorry, requests from your network are not allowed.",
+                "ip_denied"
+            ));
+        }
+
+        if !self.allow.is_empty() && !self.allow.iter().any(|cidr| cidr.contains(ip)) {
+            return Err(error::forbidden_error!(
+                "Sorry, this resource is not available from your network.",
+                "ip_not_allowed"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct AccessRuleConfig {
+    pub trusted_proxies: Vec<Cidr>,
+    pub groups: HashMap<String, AccessRule>,
+}
+
+impl AccessRuleConfig {
+    /**
+    Menentukan alamat IP client. Header X-Forwarded-For dibaca dari kanan dan alamat proxy yang
+    terpercaya dilewati. Jika peer bukan proxy terpercaya, header diabaikan karena dapat dipalsukan.
+
+    # Arguments
+    * `peer` - Alamat IP dari koneksi TCP
+    * `forwarded_for` - Isi header X-Forwarded-For, jika ada
+    */
+    pub fn client_info(&self, peer: IpAddr, forwarded_for: Option<&str>) -> ClientInfo {
+        let is_trusted = |ip: &IpAddr| self.trusted_proxies.iter().any(|cidr| cidr.contains(ip));
+        if !is_trusted(&peer) {
+            return ClientInfo::new(peer);
+        }
+
+        let mut ip = peer;
+        for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+            match hop.trim().parse::<IpAddr>() {
+                Ok(hop) => {
+                    ip = hop;
+                    if !is_trusted(&hop) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        ClientInfo::new(ip)
+    }
+
+    /**
+    Memeriksa akses client ke kelompok route
+
+    # Arguments
+    * `group` - Nama kelompok route, misal "admin"
+    * `client` - Informasi client
+    */
+    pub fn check(
+        &self,
+        group: &str,
+        client: &ClientInfo,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        match self.groups.get(group) {
+            Some(rule) => rule.check(&client.ip),
+            None => Ok(()),
+        }
+    }
+}
+
+/// AccessRuleConfig yang dibaca ulang ketika file konfigurasi berubah
+pub struct AccessRules {
+    path: PathBuf,
+    state: RwLock<(AccessRuleConfig, Option<SystemTime>)>,
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl AccessRules {
+    /**
+    Membaca konfigurasi access rule dari file JSON
+
+    # Arguments
+    * `path` - Lokasi file konfigurasi
+    */
+    pub fn load(path: &Path) -> Result<AccessRules, error::ApplicationError<'static>> {
+        let modified = modified_at(path);
+        let config = config::load(path)?;
+        Ok(AccessRules {
+            path: path.to_path_buf(),
+            state: RwLock::new((config, modified)),
+        })
+    }
+
+    /**
+    Membaca ulang konfigurasi jika file berubah. Jika file baru tidak valid, konfigurasi sebelumnya
+    tetap digunakan dan error dikembalikan.
+    */
+    pub fn reload(&self) -> Result<(), error::ApplicationError<'static>> {
+        let modified = modified_at(&self.path);
+        if self
+            .state
+            .read()
+            .map(|state| state.1 == modified)
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+
+        let config = config::load(&self.path)?;
+        let mut state = self
+            .state
+            .write()
+            .map_err(|_| error::internal_server_error!())?;
+        *state = (config, modified);
+        Ok(())
+    }
+
+    /**
+    Menentukan alamat IP client, lihat AccessRuleConfig::client_info
+
+    # Arguments
+    * `peer` - Alamat IP dari koneksi TCP
+    * `forwarded_for` - Isi header X-Forwarded-For, jika ada
+    */
+    pub fn client_info(
+        &self,
+        peer: IpAddr,
+        forwarded_for: Option<&str>,
+    ) -> Result<ClientInfo, error::ApplicationError<'static>> {
+        let _ = self.reload();
+        let state = self
+            .state
+            .read()
+            .map_err(|_| error::internal_server_error!())?;
+        Ok(state.0.client_info(peer, forwarded_for))
+    }
+
+    /**
+    Memeriksa akses client ke kelompok route dengan konfigurasi terbaru
+
+    # Arguments
+    * `group` - Nama kelompok route, misal "admin"
+    * `client` - Informasi client
+    */
+    pub fn check(
+        &self,
+        group: &str,
+        client: &ClientInfo,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        // Konfigurasi yang tidak valid tidak boleh membuka akses, konfigurasi lama tetap berlaku
+        let _ = self.reload();
+        let state = self
+            .state
+            .read()
+            .map_err(|_| error::internal_server_error!())?;
+        state.0.check(group, client)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    fn client(ip: &str) -> ClientInfo {
+        ClientInfo::new(ip.parse().unwrap())
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr() {
+        let cidr = Cidr::parse("192.168.0.0/16").unwrap();
+        assert!(cidr.contains(&ip("192.168.10.1")));
+        assert!(cidr.contains(&ip("::ffff:192.168.10.1")));
+        assert!(!cidr.contains(&ip("192.169.0.1")));
+        assert!(!cidr.contains(&ip("2001:db8::1")));
+
+        let cidr = Cidr::parse("2001:db8::/32").unwrap();
+        assert!(cidr.contains(&ip("2001:db8:1::1")));
+        assert!(!cidr.contains(&ip("2001:db9::1")));
+
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(&ip("8.8.8.8")));
+        assert!(Cidr::parse("::/0").unwrap().contains(&ip("2001:db8::1")));
+        assert!(Cidr::parse("10.0.0.1").unwrap().contains(&ip("10.0.0.1")));
+        assert!(!Cidr::parse("10.0.0.1").unwrap().contains(&ip("10.0.0.2")));
+
+        assert_eq!(None, Cidr::parse("10.0.0.0/33"));
+        assert_eq!(None, Cidr::parse("10.0.0/8"));
+        assert_eq!(None, Cidr::parse("2001:db8::/129"));
+    }
+
+    fn config() -> AccessRuleConfig {
+        config::parse(
+            r#"{
+                "trusted_proxies": ["10.0.0.0/8"],
+                "groups": {
+                    "admin": {"allow": ["203.0.113.0/24", "2001:db8::/32"]},
+                    "registration": {"deny": ["198.51.100.0/24"]}
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check() {
+        let config = config();
+
+        assert!(config.check("admin", &client("203.0.113.7")).is_ok());
+        assert!(config.check("admin", &client("2001:db8::7")).is_ok());
+        let error = config.check("admin", &client("8.8.8.8")).unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("ip_not_allowed", error.error);
+
+        let error = config
+            .check("registration", &client("198.51.100.1"))
+            .unwrap_err();
+        assert_eq!("ip_denied", error.error);
+        assert!(config.check("registration", &client("8.8.8.8")).is_ok());
+        assert!(config.check("public", &client("198.51.100.1")).is_ok());
+
+        let error =
+            config::parse::<AccessRuleConfig>(r#"{"groups": {"admin": {"allow": ["nope"]}}}"#)
+                .unwrap_err();
+        assert_eq!("invalid_configuration", error.error);
+    }
+
+    #[test]
+    fn test_client_info() {
+        let config = config();
+
+        // Header dari client yang bukan proxy terpercaya diabaikan
+        assert_eq!(
+            client("8.8.8.8"),
+            config.client_info(ip("8.8.8.8"), Some("203.0.113.7"))
+        );
+        // Alamat paling kanan yang bukan proxy terpercaya adalah client
+        assert_eq!(
+            client("198.51.100.1"),
+            config.client_info(ip("10.0.0.2"), Some("203.0.113.7, 198.51.100.1, 10.0.0.3"))
+        );
+        assert_eq!(client("10.0.0.2"), config.client_info(ip("10.0.0.2"), None));
+        assert_eq!(
+            client("10.0.0.3"),
+            config.client_info(ip("10.0.0.2"), Some("garbage, 10.0.0.3"))
+        );
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let path = std::env::temp_dir().join(format!("{}.json", uuid::Uuid::new_v4()));
+        fs::write(
+            &path,
+            r#"{"groups": {"admin": {"allow": ["203.0.113.0/24"]}}}"#,
+        )
+        .unwrap();
+        let rules = AccessRules::load(&path).unwrap();
+        assert!(rules.check("admin", &client("203.0.113.7")).is_ok());
+        assert!(rules.check("admin", &client("192.0.2.1")).is_err());
+
+        // Memaksa waktu modifikasi berbeda, resolusi mtime beberapa filesystem cukup kasar
+        fs::write(
+            &path,
+            r#"{"groups": {"admin": {"allow": ["192.0.2.0/24"]}}}"#,
+        )
+        .unwrap();
+        rules.state.write().unwrap().1 = None;
+        assert!(rules.check("admin", &client("192.0.2.1")).is_ok());
+        assert!(rules.check("admin", &client("203.0.113.7")).is_err());
+
+        // File yang tidak valid tidak menggantikan konfigurasi terakhir
+        fs::write(&path, "not json").unwrap();
+        rules.state.write().unwrap().1 = None;
+        assert!(rules.reload().is_err());
+        assert!(rules.check("admin", &client("192.0.2.1")).is_ok());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
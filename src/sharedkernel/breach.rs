@@ -0,0 +1,298 @@
+use super::error;
+use super::password::Password;
+use mockall::*;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use validator::{ValidationError, ValidationErrors};
+
+/*
+==============================================================================================================================
+Breached password screening
+==============================================================================================================================
+Password diperiksa terhadap kumpulan SHA-1 hash dari password yang pernah bocor (HIBP-style corpus) yang disimpan secara
+lokal, sehingga tidak ada password (maupun prefix hash-nya) yang dikirim ke pihak ketiga.
+
+Corpus disimpan sebagai sorted hash file : setiap record berupa 40 karakter hex uppercase diikuti newline, sehingga
+lookup dapat dilakukan dengan binary search tanpa memuat seluruh file ke memory. File dibentuk dari download
+"ordered by hash" HIBP (format `HASH:COUNT`) melalui command :
+- brokoli import-breach <input> <output> [min_count]
+*/
+
+// Panjang satu record : 40 karakter hex + newline
+const RECORD_LEN: u64 = 41;
+
+#[automock]
+pub trait BreachCorpus {
+    /// Memeriksa apakah SHA-1 digest (hex uppercase) terdapat di dalam corpus
+    fn contains(&self, digest: &str) -> Result<bool, error::ApplicationError<'static>>;
+}
+
+/**
+Mengembalikan SHA-1 digest dari password dalam format hex uppercase, sama dengan format HIBP
+
+# Arguments
+* `password` - Reference dari object Password
+*/
+pub fn digest(password: &Password) -> String {
+    Sha1::digest(password.expose_secret().as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// Breach corpus yang disimpan dalam sorted hash file
+pub struct SortedHashFile {
+    path: PathBuf,
+    records: u64,
+}
+
+impl SortedHashFile {
+    /**
+    Membuka sorted hash file hasil `import`
+
+    # Arguments
+    * `path` - Lokasi sorted hash file
+    */
+    pub fn open(path: &Path) -> Result<SortedHashFile, error::ApplicationError<'static>> {
+        let length = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => {
+                return Err(error::internal_server_error!(
+                    "Breach corpus can not be read",
+                    "invalid_breach_corpus"
+                ))
+            }
+        };
+
+        if length % RECORD_LEN != 0 {
+            return Err(error::internal_server_error!(
+                "Breach corpus is corrupted",
+                "invalid_breach_corpus"
+            ));
+        }
+
+        Ok(SortedHashFile {
+            path: path.to_path_buf(),
+            records: length / RECORD_LEN,
+        })
+    }
+}
+
+impl BreachCorpus for SortedHashFile {
+    fn contains(&self, digest: &str) -> Result<bool, error::ApplicationError<'static>> {
+        let read_error = || {
+            error::internal_server_error!("Breach corpus can not be read", "invalid_breach_corpus")
+        };
+
+        let mut file = File::open(&self.path).map_err(|_| read_error())?;
+        let mut record = [0u8; 40];
+        let (mut low, mut high) = (0u64, self.records);
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+            file.seek(SeekFrom::Start(middle * RECORD_LEN))
+                .map_err(|_| read_error())?;
+            file.read_exact(&mut record).map_err(|_| read_error())?;
+
+            match record.as_slice().cmp(digest.as_bytes()) {
+                Ordering::Equal => return Ok(true),
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/**
+Membentuk sorted hash file dari HIBP "ordered by hash" dataset (`HASH:COUNT` per baris).
+Mengembalikan jumlah record yang ditulis.
+
+# Arguments
+* `input` - Dataset HIBP yang sudah terurut berdasarkan hash
+* `output` - Tujuan penulisan sorted hash file
+* `min_count` - Hash dengan jumlah kemunculan di bawah nilai ini tidak diikutkan
+*/
+pub fn import<R: BufRead, W: Write>(
+    input: R,
+    mut output: W,
+    min_count: u64,
+) -> Result<u64, error::ApplicationError<'static>> {
+    let mut previous = String::new();
+    let mut written = 0u64;
+
+    for line in input.lines() {
+        let line = line.map_err(|_| {
+            error::bad_request_error!("Breach dataset can not be read", "invalid_breach_dataset")
+        })?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (hash, count) = match line.split_once(':') {
+            Some((hash, count)) => (hash, count.trim().parse::<u64>().unwrap_or(0)),
+            None => (line, u64::MAX),
+        };
+        let hash = hash.to_ascii_uppercase();
+
+        if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(error::bad_request_error!(
+                "Breach dataset contains an invalid SHA-1 hash",
+                "invalid_breach_dataset"
+            ));
+        }
+        if hash <= previous {
+            return Err(error::bad_request_error!(
+                "Breach dataset must be ordered by hash",
+                "invalid_breach_dataset"
+            ));
+        }
+        previous = hash;
+
+        if count < min_count {
+            continue;
+        }
+
+        writeln!(output, "{}", previous).map_err(|_| error::internal_server_error!())?;
+        written += 1;
+    }
+
+    output
+        .flush()
+        .map_err(|_| error::internal_server_error!())?;
+    Ok(written)
+}
+
+/// Menolak password yang terdapat di dalam breach corpus
+pub struct BreachScreening<'a> {
+    corpus: &'a dyn BreachCorpus,
+}
+
+impl<'a> BreachScreening<'a> {
+    pub fn new(corpus: &'a dyn BreachCorpus) -> BreachScreening<'a> {
+        BreachScreening { corpus }
+    }
+
+    /**
+    Memeriksa password terhadap breach corpus. Password yang pernah bocor menghasilkan
+    validation error dengan code `password_breached`.
+
+    # Arguments
+    * `password` - Reference dari object Password
+    */
+    pub fn check(&self, password: &Password) -> Result<(), error::ApplicationError<'static>> {
+        if !self.corpus.contains(&digest(password))? {
+            return Ok(());
+        }
+
+        let mut errors = ValidationErrors::new();
+        errors.add("password", ValidationError::new("password_breached"));
+        Err(error::ApplicationError::from_validation_errors(errors))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::error::ApplicationErrorStatus;
+    use super::*;
+    use uuid::Uuid;
+
+    const DATASET: &str = "21BD12DC183F740EE76F27B78EB39C8AD972A757:52\n\
+        70CCD9007338D6D81DD3B6271621B9CF9A97EA00:2\n\
+        7C4A8D09CA3762AF61E59520943DC26494F8941B:24230577\n\
+        8BE3C943B1609FFFBFC51AAD666D0A04ADF83C9D:77\n";
+
+    // Membentuk sorted hash file sementara dari dataset
+    fn corpus(dataset: &str, min_count: u64) -> (PathBuf, u64) {
+        let path = std::env::temp_dir().join(format!("brokoli-breach-{}", Uuid::new_v4()));
+        let file = File::create(&path).unwrap();
+        let written = import(dataset.as_bytes(), file, min_count).unwrap();
+        (path, written)
+    }
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            "7C4A8D09CA3762AF61E59520943DC26494F8941B",
+            digest(&Password::from("123456"))
+        );
+    }
+
+    #[test]
+    fn test_sorted_hash_file_lookup() {
+        let (path, written) = corpus(DATASET, 0);
+        let corpus = SortedHashFile::open(&path).unwrap();
+
+        assert_eq!(4, written);
+        assert!(corpus
+            .contains("7C4A8D09CA3762AF61E59520943DC26494F8941B")
+            .unwrap());
+        assert!(corpus
+            .contains("21BD12DC183F740EE76F27B78EB39C8AD972A757")
+            .unwrap());
+        assert!(corpus
+            .contains("8BE3C943B1609FFFBFC51AAD666D0A04ADF83C9D")
+            .unwrap());
+        assert!(!corpus
+            .contains("0000000000000000000000000000000000000000")
+            .unwrap());
+        assert!(!corpus
+            .contains("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF")
+            .unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_import_with_min_count() {
+        let (path, written) = corpus(DATASET, 10);
+        let corpus = SortedHashFile::open(&path).unwrap();
+
+        assert_eq!(3, written);
+        assert!(!corpus
+            .contains("70CCD9007338D6D81DD3B6271621B9CF9A97EA00")
+            .unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_import_unsorted_dataset() {
+        let dataset = "7C4A8D09CA3762AF61E59520943DC26494F8941B:1\n\
+            21BD12DC183F740EE76F27B78EB39C8AD972A757:1\n";
+        let result = import(dataset.as_bytes(), Vec::new(), 0);
+        assert_eq!("invalid_breach_dataset", result.unwrap_err().error);
+    }
+
+    #[test]
+    fn test_screening_rejects_breached_password() {
+        let mut corpus = MockBreachCorpus::new();
+        corpus
+            .expect_contains()
+            .withf(|digest| digest == "7C4A8D09CA3762AF61E59520943DC26494F8941B")
+            .returning(|_| Ok(true));
+
+        let error = BreachScreening::new(&corpus)
+            .check(&Password::from("123456"))
+            .unwrap_err();
+
+        assert_eq!(ApplicationErrorStatus::ValidationError, error.status);
+        let fields = error.fields.unwrap();
+        assert!(fields["password"][0].contains("password_breached"));
+    }
+
+    #[test]
+    fn test_screening_accepts_unknown_password() {
+        let mut corpus = MockBreachCorpus::new();
+        corpus.expect_contains().returning(|_| Ok(false));
+
+        assert!(BreachScreening::new(&corpus)
+            .check(&Password::from("MypassworD1234!"))
+            .is_ok());
+    }
+}
@@ -0,0 +1,153 @@
+use super::{
+    error,
+    function::{from_hex, to_hex},
+};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::{rngs::OsRng, RngCore};
+use zeroize::Zeroize;
+
+// Panjang nonce AES-GCM dalam bytes
+const NONCE_LENGTH: usize = 12;
+
+/**
+Enkripsi secret yang harus disimpan namun dapat dibaca kembali oleh server (misal TOTP secret),
+menggunakan AES-256-GCM. Ciphertext terikat dengan `context` (misal id account), sehingga
+ciphertext milik satu account tidak dapat dipindahkan ke account lain.
+*/
+pub struct SecretCipher {
+    key: [u8; 32],
+}
+
+fn invalid_ciphertext() -> error::ApplicationError<'static> {
+    error::internal_server_error!(
+        "It's not you. We are experiencing technical difficulties. Please try again later.",
+        "invalid_ciphertext"
+    )
+}
+
+impl SecretCipher {
+    /**
+    Mengembalikan SecretCipher dari key 32 bytes
+
+    # Arguments
+    * `key` - Encryption key dalam format hex (64 karakter)
+    */
+    pub fn from_hex_key(key: &str) -> Result<SecretCipher, error::ApplicationError<'static>> {
+        let mut bytes = from_hex(key).unwrap_or_default();
+        let result = <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+            error::internal_server_error!(
+                "Encryption key must be 32 bytes",
+                "invalid_configuration"
+            )
+        });
+        bytes.zeroize();
+        Ok(SecretCipher { key: result? })
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.key.into())
+    }
+
+    /**
+    Mengenkripsi secret, hasilnya berupa hex dari nonce dan ciphertext
+
+    # Arguments
+    * `plaintext` - Secret yang akan dienkripsi
+    * `context` - Data yang mengikat ciphertext, misal id account
+    */
+    pub fn encrypt(
+        &self,
+        plaintext: &[u8],
+        context: &str,
+    ) -> Result<String, error::ApplicationError<'static>> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: plaintext,
+            aad: context.as_bytes(),
+        };
+        let ciphertext = self
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| invalid_ciphertext())?;
+        Ok(format!("{}{}", to_hex(&nonce), to_hex(&ciphertext)))
+    }
+
+    /**
+    Mendekripsi secret yang dihasilkan oleh `encrypt`
+
+    # Arguments
+    * `ciphertext` - Hasil dari `encrypt`
+    * `context` - Data yang sama dengan saat enkripsi
+    */
+    pub fn decrypt(
+        &self,
+        ciphertext: &str,
+        context: &str,
+    ) -> Result<Vec<u8>, error::ApplicationError<'static>> {
+        let bytes = from_hex(ciphertext).ok_or_else(invalid_ciphertext)?;
+        if bytes.len() <= NONCE_LENGTH {
+            return Err(invalid_ciphertext());
+        }
+        let (nonce, msg) = bytes.split_at(NONCE_LENGTH);
+        let payload = Payload {
+            msg,
+            aad: context.as_bytes(),
+        };
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| invalid_ciphertext())
+    }
+}
+
+impl Drop for SecretCipher {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let cipher = SecretCipher::from_hex_key(KEY).unwrap();
+        let ciphertext = cipher.encrypt(b"secret", "account-1").unwrap();
+
+        assert!(!ciphertext.contains(&to_hex(b"secret")));
+        assert_ne!(ciphertext, cipher.encrypt(b"secret", "account-1").unwrap());
+        assert_eq!(
+            b"secret".to_vec(),
+            cipher.decrypt(&ciphertext, "account-1").unwrap()
+        );
+
+        // Context lain, ciphertext yang diubah dan key lain ditolak
+        assert!(cipher.decrypt(&ciphertext, "account-2").is_err());
+        let mut tampered = ciphertext.clone();
+        tampered.replace_range(
+            30..32,
+            if &ciphertext[30..32] == "00" {
+                "01"
+            } else {
+                "00"
+            },
+        );
+        assert!(cipher.decrypt(&tampered, "account-1").is_err());
+        let other = SecretCipher::from_hex_key(&KEY.replace("00", "ff")).unwrap();
+        let error = other.decrypt(&ciphertext, "account-1").unwrap_err();
+        assert_eq!("invalid_ciphertext", error.error);
+        assert!(cipher.decrypt("abcd", "account-1").is_err());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        for key in ["", "0011", "zz"] {
+            let error = SecretCipher::from_hex_key(key).err().unwrap();
+            assert_eq!("invalid_configuration", error.error);
+        }
+    }
+}
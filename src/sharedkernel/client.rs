@@ -0,0 +1,50 @@
+use super::token::Token;
+use std::net::IpAddr;
+
+/// Informasi client yang melakukan request, diisi oleh layer HTTP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub ip: IpAddr,
+    /// Fingerprint perangkat / browser, misal dari device cookie
+    pub device: Option<String>,
+    /// Digest dari trusted device token yang dikirim browser, token asli tidak pernah disimpan
+    pub trusted_device: Option<String>,
+}
+
+impl ClientInfo {
+    /**
+    Mengembalikan informasi client
+
+    # Arguments
+    * `ip` - Alamat IP client
+    */
+    pub fn new(ip: IpAddr) -> ClientInfo {
+        ClientInfo {
+            ip,
+            device: None,
+            trusted_device: None,
+        }
+    }
+
+    /**
+    Menambahkan fingerprint perangkat client
+
+    # Arguments
+    * `device` - Fingerprint perangkat / browser
+    */
+    pub fn with_device(mut self, device: &str) -> ClientInfo {
+        self.device = Some(String::from(device));
+        self
+    }
+
+    /**
+    Menambahkan trusted device token yang dikirim browser, misal dari cookie "remember this browser"
+
+    # Arguments
+    * `token` - Trusted device token
+    */
+    pub fn with_trusted_device(mut self, token: &str) -> ClientInfo {
+        self.trusted_device = Some(Token::from(token).digest());
+        self
+    }
+}
@@ -0,0 +1,32 @@
+use super::error;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::Path;
+
+/**
+Membentuk object konfigurasi dari teks JSON
+
+# Arguments
+* `text` - Isi konfigurasi dalam format JSON
+*/
+pub fn parse<T: DeserializeOwned>(text: &str) -> Result<T, error::ApplicationError<'static>> {
+    serde_json::from_str(text).map_err(|_| {
+        error::internal_server_error!("Configuration is malformed", "invalid_configuration")
+    })
+}
+
+/**
+Membaca dan membentuk object konfigurasi dari file JSON
+
+# Arguments
+* `path` - Lokasi file konfigurasi
+*/
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, error::ApplicationError<'static>> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse(&text),
+        Err(_) => Err(error::internal_server_error!(
+            "Configuration file can not be read",
+            "invalid_configuration"
+        )),
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt;
 use validator::Validate;
 
 // pub struct Email{text: String}
-#[derive(Validate, PartialEq, Eq)]
+#[derive(Validate, PartialEq, Eq, Clone)]
 pub struct Email {
     #[validate(email)]
     value: String,
@@ -16,6 +16,22 @@ impl Email {
             value: String::from(value),
         }
     }
+
+    /// Mengembalikan bagian email sebelum karakter '@'
+    pub fn local_part(&self) -> &str {
+        match self.value.rsplit_once('@') {
+            Some((local_part, _)) => local_part,
+            None => &self.value,
+        }
+    }
+
+    /// Mengembalikan bagian domain dari email, atau string kosong jika tidak ada
+    pub fn domain(&self) -> &str {
+        match self.value.rsplit_once('@') {
+            Some((_, domain)) => domain,
+            None => "",
+        }
+    }
 }
 
 impl fmt::Debug for Email {
@@ -68,6 +84,14 @@ mod test {
         };
     }
 
+    #[test]
+    fn test_parts() {
+        let my_email: Email = Email::from("harun@digitalsekuriti.id");
+        assert_eq!("harun", my_email.local_part());
+        assert_eq!("digitalsekuriti.id", my_email.domain());
+        assert_eq!("", Email::from("harunasolole").domain());
+    }
+
     email_test! {
         (invalid_email_test, "harunasolole", false),
         (valid_email_test, "harun@digitalsekuriti.id", true)
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use serde::Serialize;
-use validator::{Validate, ValidationErrorsKind};
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
 
 /**
 ==============================================================================================================================
@@ -47,17 +47,37 @@ pub struct ApplicationError<'a> {
     pub description: &'a str,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fields: Option<HashMap<&'a str, Vec<String>>>,
+    /// Header yang harus disertakan pada response, misal `Retry-After`
+    #[serde(skip_serializing)]
+    pub headers: Vec<(&'static str, String)>,
 }
 
 impl ApplicationError<'_> {
+    /*
+    Menyertakan header pada response error, misal `Retry-After` dan `RateLimit-*` ketika request
+    ditolak oleh rate limiter
+    */
+    pub fn with_headers(mut self, headers: Vec<(&'static str, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
     /*
     Berikut adalah proses wrapping validator. ValidationError dari validator akan dibentuk ulang
     sesuai dengan format dan spesifikasi error response body
     */
     pub fn validate<T: Validate>(object: T) -> Option<ApplicationError<'static>> {
-        let errors: HashMap<&str, Vec<String>> = object
-            .validate()
-            .err()?
+        Some(ApplicationError::from_validation_errors(
+            object.validate().err()?,
+        ))
+    }
+
+    /*
+    Membentuk validation error dari ValidationErrors yang dihasilkan di luar trait Validate,
+    misal dari pemeriksaan yang membutuhkan akses ke storage
+    */
+    pub fn from_validation_errors(errors: ValidationErrors) -> ApplicationError<'static> {
+        let errors: HashMap<&str, Vec<String>> = errors
             .errors()
             .iter()
             .map(|error_kind| {
@@ -86,13 +106,14 @@ impl ApplicationError<'_> {
             })
             .collect();
 
-        Some(ApplicationError {
+        ApplicationError {
             status: ApplicationErrorStatus::ValidationError,
             code: 400u16,
             error: "invalid_input",
             description: "Please check your input",
             fields: Some(errors),
-        })
+            headers: Vec::new(),
+        }
     }
 }
 
@@ -146,6 +167,7 @@ macro_rules! application_error_function {
                         error: $default_error,
                         description: $default_description,
                         fields: None,
+                        headers: Vec::new(),
                     }
                 };
 
@@ -157,6 +179,7 @@ macro_rules! application_error_function {
                         error: $default_error,
                         description: $description,
                         fields: None,
+                        headers: Vec::new(),
                     }
                 };
 
@@ -168,6 +191,7 @@ macro_rules! application_error_function {
                         error: $error,
                         description: $description,
                         fields: None,
+                        headers: Vec::new(),
                     }
                 };
             }
@@ -0,0 +1,163 @@
+use super::{access_rule::Cidr, error};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::Path;
+
+/*
+==============================================================================================================================
+GeoIP
+==============================================================================================================================
+Database GeoIP lokal dalam format CSV, satu blok IP per baris :
+
+    network,asn,country,latitude,longitude
+    203.0.113.0/24,64500,ID,-6.2088,106.8456
+
+Baris kosong dan baris yang diawali '#' diabaikan. Kolom asn boleh dikosongkan. Lookup mengembalikan blok yang paling
+spesifik (prefix terpanjang).
+*/
+
+/// Lokasi dan jaringan dari sebuah alamat IP
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GeoLocation {
+    pub asn: Option<u32>,
+    pub country: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoLocation {
+    /// Jarak great-circle (haversine) ke lokasi lain dalam kilometer
+    pub fn distance_km(&self, other: &GeoLocation) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+pub struct GeoIpDatabase {
+    entries: Vec<(Cidr, GeoLocation)>,
+}
+
+fn invalid_database() -> error::ApplicationError<'static> {
+    error::internal_server_error!("GeoIP database is malformed", "invalid_geoip_database")
+}
+
+impl GeoIpDatabase {
+    /**
+    Membaca database GeoIP dari reader CSV
+
+    # Arguments
+    * `reader` - Isi database GeoIP
+    */
+    pub fn parse<R: BufRead>(reader: R) -> Result<GeoIpDatabase, error::ApplicationError<'static>> {
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(|_| invalid_database())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+            if columns.len() != 5 {
+                return Err(invalid_database());
+            }
+            let network = Cidr::parse(columns[0]).ok_or_else(invalid_database)?;
+            let asn = match columns[1] {
+                "" => None,
+                asn => Some(asn.parse().map_err(|_| invalid_database())?),
+            };
+            let location = GeoLocation {
+                asn,
+                country: String::from(columns[2]),
+                latitude: columns[3].parse().map_err(|_| invalid_database())?,
+                longitude: columns[4].parse().map_err(|_| invalid_database())?,
+            };
+            entries.push((network, location));
+        }
+
+        // Blok yang lebih spesifik diperiksa lebih dulu
+        entries.sort_by_key(|(network, _)| std::cmp::Reverse(network.prefix()));
+        Ok(GeoIpDatabase { entries })
+    }
+
+    /**
+    Membaca database GeoIP dari file CSV
+
+    # Arguments
+    * `path` - Lokasi file database
+    */
+    pub fn open(path: &Path) -> Result<GeoIpDatabase, error::ApplicationError<'static>> {
+        let file = File::open(path).map_err(|_| {
+            error::internal_server_error!(
+                "GeoIP database can not be read",
+                "invalid_geoip_database"
+            )
+        })?;
+        GeoIpDatabase::parse(BufReader::new(file))
+    }
+
+    /**
+    Mengembalikan lokasi dari alamat IP, None jika tidak ditemukan
+
+    # Arguments
+    * `ip` - Alamat IP yang dicari
+    */
+    pub fn lookup(&self, ip: &IpAddr) -> Option<&GeoLocation> {
+        self.entries
+            .iter()
+            .find(|(network, _)| network.contains(ip))
+            .map(|(_, location)| location)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DATABASE: &str = "# network,asn,country,latitude,longitude
+203.0.113.0/24,64500,ID,-6.2088,106.8456
+203.0.113.128/25,64501,SG,1.3521,103.8198
+
+2001:db8::/32,,NL,52.3676,4.9041
+";
+
+    #[test]
+    fn test_lookup() {
+        let database = GeoIpDatabase::parse(DATABASE.as_bytes()).unwrap();
+
+        let jakarta = database.lookup(&"203.0.113.1".parse().unwrap()).unwrap();
+        assert_eq!(Some(64500), jakarta.asn);
+        assert_eq!("ID", jakarta.country);
+
+        let singapore = database.lookup(&"203.0.113.200".parse().unwrap()).unwrap();
+        assert_eq!(Some(64501), singapore.asn);
+
+        let amsterdam = database.lookup(&"2001:db8::1".parse().unwrap()).unwrap();
+        assert_eq!(None, amsterdam.asn);
+
+        assert!(database.lookup(&"192.0.2.1".parse().unwrap()).is_none());
+
+        // Jakarta - Singapura sekitar 880 km
+        let distance = jakarta.distance_km(singapore);
+        assert!((850.0..=910.0).contains(&distance), "{}", distance);
+        assert_eq!(0.0, jakarta.distance_km(jakarta));
+    }
+
+    #[test]
+    fn test_parse_invalid_database() {
+        for database in [
+            "203.0.113.0/24,64500,ID",
+            "nope,1,ID,0,0",
+            "203.0.113.0/24,x,ID,0,0",
+        ] {
+            let error = GeoIpDatabase::parse(database.as_bytes()).err().unwrap();
+            assert_eq!("invalid_geoip_database", error.error);
+        }
+    }
+}
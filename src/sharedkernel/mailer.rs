@@ -0,0 +1,620 @@
+use super::email::Email;
+use super::error;
+use super::function::get_now;
+use super::token::Token;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{SmtpTransport, Transport};
+use mockall::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Merepresentasikan email yang akan dikirim ke user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub to: Email,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Message {
+    /**
+    Mengembalikan message baru
+
+    # Arguments
+    * `to` - Email penerima
+    * `subject` - Subject email
+    * `body` - Isi email dalam plain text
+    */
+    pub fn new(to: &Email, subject: &str, body: &str) -> Message {
+        Message {
+            to: to.clone(),
+            subject: String::from(subject),
+            body: String::from(body),
+        }
+    }
+}
+
+#[automock]
+pub trait Mailer {
+    fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>>;
+}
+
+fn mail_unavailable() -> error::ApplicationError<'static> {
+    error::internal_server_error!(
+        "It's not you. We are experiencing technical difficulties. Please try again later.",
+        "mail_unavailable"
+    )
+}
+
+// Email ditolak secara permanen (misal SMTP 550 mailbox unavailable), mengirim ulang tidak akan berhasil
+fn mail_rejected() -> error::ApplicationError<'static> {
+    error::bad_request_error!("The email was rejected by the mail server", "mail_rejected")
+}
+
+/**
+Membentuk email dalam format RFC 5322
+
+# Arguments
+* `from` - Pengirim email
+* `message` - Email yang akan dikirim
+*/
+fn compose(
+    from: &Mailbox,
+    message: &Message,
+) -> Result<lettre::Message, error::ApplicationError<'static>> {
+    let to: Mailbox = message.to.to_string().parse().map_err(|_| {
+        error::bad_request_error!("Recipient email address is invalid", "invalid_recipient")
+    })?;
+    lettre::Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(message.subject.as_str())
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.body.clone())
+        .map_err(|_| mail_unavailable())
+}
+
+/// Keamanan koneksi ke SMTP server
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Default)]
+pub enum SmtpSecurity {
+    /// Plain text, hanya untuk relay lokal (misal MailHog saat development)
+    #[serde(rename = "none")]
+    None,
+    /// Koneksi plain text yang wajib di-upgrade dengan STARTTLS
+    #[default]
+    #[serde(rename = "starttls")]
+    StartTls,
+    /// Koneksi TLS sejak awal (SMTPS)
+    #[serde(rename = "tls")]
+    Tls,
+}
+
+/// Konfigurasi SMTP server
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// Dikirim apa adanya, tanpa normalisasi seperti password user
+    pub password: Option<Token>,
+    /// Alamat pengirim, misal "Brokoli <no-reply@brokoli.id>"
+    pub from: String,
+    /// Batas waktu koneksi dan setiap perintah SMTP (dalam detik)
+    pub timeout: u64,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        SmtpConfig {
+            host: String::from("localhost"),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            from: String::from("no-reply@localhost"),
+            timeout: 10,
+        }
+    }
+}
+
+/// Mailer yang mengirim email melalui SMTP server
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /**
+    Mengembalikan SmtpMailer baru. Koneksi dibuat saat email dikirim.
+
+    # Arguments
+    * `config` - Konfigurasi SMTP server
+    */
+    pub fn new(config: &SmtpConfig) -> Result<SmtpMailer, error::ApplicationError<'static>> {
+        let invalid_configuration = || {
+            error::internal_server_error!("SMTP configuration is invalid", "invalid_configuration")
+        };
+        let from: Mailbox = config.from.parse().map_err(|_| invalid_configuration())?;
+        let tls = match config.security {
+            SmtpSecurity::None => Tls::None,
+            SmtpSecurity::StartTls => Tls::Required(
+                TlsParameters::new(config.host.clone()).map_err(|_| invalid_configuration())?,
+            ),
+            SmtpSecurity::Tls => Tls::Wrapper(
+                TlsParameters::new(config.host.clone()).map_err(|_| invalid_configuration())?,
+            ),
+        };
+
+        let mut builder = SmtpTransport::builder_dangerous(config.host.as_str())
+            .port(config.port)
+            .tls(tls)
+            .timeout(Some(Duration::from_secs(config.timeout)));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                String::from(password.expose_secret()),
+            ));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>> {
+        let email = compose(&self.from, message)?;
+        self.transport.send(&email).map(|_| ()).map_err(|err| {
+            if err.is_permanent() {
+                mail_rejected()
+            } else {
+                mail_unavailable()
+            }
+        })
+    }
+}
+
+/**
+Mailer untuk development dan testing lokal. Email tidak dikirim, melainkan disimpan sebagai file
+RFC 5322 di dalam direktori dengan struktur maildir (`tmp`, `new`, `cur`), sehingga dapat dibuka
+dengan email client.
+*/
+pub struct MaildirMailer {
+    path: PathBuf,
+    from: Mailbox,
+}
+
+impl MaildirMailer {
+    /**
+    Mengembalikan MaildirMailer baru
+
+    # Arguments
+    * `path` - Lokasi direktori maildir, dibuat jika belum ada
+    * `from` - Alamat pengirim
+    */
+    pub fn new(path: &Path, from: &str) -> Result<MaildirMailer, error::ApplicationError<'static>> {
+        let from: Mailbox = from.parse().map_err(|_| {
+            error::internal_server_error!("Sender address is invalid", "invalid_configuration")
+        })?;
+        for directory in ["tmp", "new", "cur"] {
+            fs::create_dir_all(path.join(directory)).map_err(|_| mail_unavailable())?;
+        }
+        Ok(MaildirMailer {
+            path: path.to_path_buf(),
+            from,
+        })
+    }
+}
+
+impl Mailer for MaildirMailer {
+    fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>> {
+        let email = compose(&self.from, message)?;
+        // File ditulis ke `tmp` lalu dipindahkan agar pembaca tidak melihat file yang belum lengkap
+        let name = format!("{}.{}.brokoli", get_now(), Uuid::new_v4());
+        let tmp = self.path.join("tmp").join(&name);
+        fs::write(&tmp, email.formatted())
+            .and_then(|_| fs::rename(&tmp, self.path.join("new").join(&name)))
+            .map_err(|_| mail_unavailable())
+    }
+}
+
+/// Kebijakan pengiriman ulang email yang gagal
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Jumlah percobaan maksimal, termasuk percobaan pertama
+    pub max_attempts: u32,
+    /// Jeda sebelum percobaan kedua (dalam milidetik), dikali dua untuk setiap percobaan berikutnya
+    pub initial_backoff: u64,
+    /// Jeda maksimal antar percobaan (dalam milidetik)
+    pub max_backoff: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: 500,
+            max_backoff: 5000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /**
+    Mengembalikan jeda sebelum percobaan berikutnya (dalam milidetik)
+
+    # Arguments
+    * `attempt` - Percobaan yang baru saja gagal, dimulai dari 1
+    */
+    pub fn backoff(&self, attempt: u32) -> u64 {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/**
+Mailer yang mengirim ulang email dengan exponential backoff jika mailer di dalamnya gagal. Hanya
+kegagalan server (misal SMTP server tidak dapat dihubungi) yang dikirim ulang, email yang ditolak
+secara permanen tidak.
+
+Thread pemanggil ditahan selama percobaan dan jeda antar percobaan, sehingga RetryMailer hanya
+digunakan untuk pengiriman di background, yaitu sebagai mailer di dalam QueuedMailer.
+*/
+pub struct RetryMailer<M: Mailer> {
+    inner: M,
+    policy: RetryPolicy,
+}
+
+impl<M: Mailer> RetryMailer<M> {
+    pub fn new(inner: M) -> RetryMailer<M> {
+        RetryMailer {
+            inner,
+            policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Mengganti jumlah percobaan dan jeda antar percobaan
+    pub fn with_policy(mut self, policy: RetryPolicy) -> RetryMailer<M> {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<M: Mailer> Mailer for RetryMailer<M> {
+    fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>> {
+        let mut attempt = 1;
+        loop {
+            match self.inner.send(message) {
+                Err(err) if err.code >= 500 && attempt < self.policy.max_attempts => {
+                    thread::sleep(Duration::from_millis(self.policy.backoff(attempt)));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/**
+Mailer yang mengirim email di background thread. `send` hanya memasukkan email ke antrian dan
+langsung kembali, sehingga lama pengiriman (termasuk retry) tidak terlihat dari response time dan
+tidak dapat digunakan untuk enumerasi account. Kegagalan pengiriman tidak dikembalikan ke pemanggil.
+*/
+pub struct QueuedMailer {
+    queue: Option<mpsc::Sender<Message>>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl QueuedMailer {
+    /**
+    Menjalankan worker yang mengirim email dari antrian menggunakan mailer di dalamnya
+
+    # Arguments
+    * `inner` - Mailer yang digunakan worker, misal RetryMailer berisi SmtpMailer
+    */
+    pub fn start<M: Mailer + Send + 'static>(inner: M) -> QueuedMailer {
+        let (queue, messages) = mpsc::channel::<Message>();
+        let worker = thread::spawn(move || {
+            for message in messages {
+                let _ = inner.send(&message);
+            }
+        });
+        QueuedMailer {
+            queue: Some(queue),
+            worker: Some(worker),
+        }
+    }
+}
+
+impl Mailer for QueuedMailer {
+    fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>> {
+        match &self.queue {
+            Some(queue) => queue.send(message.clone()).map_err(|_| mail_unavailable()),
+            None => Err(mail_unavailable()),
+        }
+    }
+}
+
+// Menunggu email yang masih di antrian terkirim sebelum aplikasi berhenti
+impl Drop for QueuedMailer {
+    fn drop(&mut self) {
+        self.queue.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod capture {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /**
+    Mailer untuk test yang menyimpan seluruh email terkirim agar dapat diperiksa. Hasil clone
+    berbagi outbox yang sama, sehingga dapat diberikan ke QueuedMailer.
+    */
+    #[derive(Default, Clone)]
+    pub struct CapturingMailer {
+        sent: Arc<Mutex<Vec<Message>>>,
+        failures: Arc<Mutex<u32>>,
+    }
+
+    impl CapturingMailer {
+        pub fn new() -> CapturingMailer {
+            CapturingMailer::default()
+        }
+
+        /// Menggagalkan `count` pengiriman berikutnya dengan error `mail_unavailable`
+        pub fn fail_next(&self, count: u32) {
+            *self.failures.lock().unwrap() = count;
+        }
+
+        /// Mengembalikan seluruh email yang berhasil dikirim
+        pub fn sent(&self) -> Vec<Message> {
+            self.sent.lock().unwrap().clone()
+        }
+
+        /// Mengembalikan email terakhir yang dikirim ke alamat tertentu
+        pub fn last_to(&self, to: &str) -> Option<Message> {
+            self.sent
+                .lock()
+                .unwrap()
+                .iter()
+                .rev()
+                .find(|message| message.to.to_string() == to)
+                .cloned()
+        }
+    }
+
+    impl Mailer for CapturingMailer {
+        fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(mail_unavailable());
+            }
+            self.sent.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::capture::CapturingMailer;
+    use super::*;
+
+    fn message() -> Message {
+        Message::new(
+            &Email::from("harun@digitalsekuriti.id"),
+            "Welcome",
+            "Hello from Brokoli",
+        )
+    }
+
+    #[test]
+    fn test_maildir_mailer() {
+        let path = std::env::temp_dir().join(format!("brokoli-maildir-{}", Uuid::new_v4()));
+        let mailer = MaildirMailer::new(&path, "Brokoli <no-reply@brokoli.id>").unwrap();
+
+        mailer.send(&message()).unwrap();
+
+        let files: Vec<PathBuf> = fs::read_dir(path.join("new"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(1, files.len());
+        assert_eq!(0, fs::read_dir(path.join("tmp")).unwrap().count());
+        let content = fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("From: Brokoli <no-reply@brokoli.id>"));
+        assert!(content.contains("To: harun@digitalsekuriti.id"));
+        assert!(content.contains("Subject: Welcome"));
+        assert!(content.contains("Hello from Brokoli"));
+        fs::remove_dir_all(&path).unwrap();
+
+        assert!(MaildirMailer::new(&path, "not an address").is_err());
+        let error = MaildirMailer::new(&path, "no-reply@brokoli.id")
+            .unwrap()
+            .send(&Message::new(&Email::from("invalid"), "Welcome", "Hello"))
+            .unwrap_err();
+        assert_eq!("invalid_recipient", error.error);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    /**
+    Menjalankan SMTP server palsu yang membalas koneksi pertama dengan `greeting` lalu menutup
+    koneksi, dan mengembalikan konfigurasi untuk terhubung ke server tersebut
+
+    # Arguments
+    * `greeting` - Balasan SMTP server, misal "421 Service not available"
+    */
+    fn fake_smtp_server(greeting: &'static str) -> SmtpConfig {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                use std::io::Write;
+                let _ = write!(stream, "{}\r\n", greeting);
+            }
+        });
+        SmtpConfig {
+            host: String::from("127.0.0.1"),
+            port,
+            security: SmtpSecurity::None,
+            timeout: 5,
+            ..SmtpConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_smtp_mailer_unavailable() {
+        let config = fake_smtp_server("421 Service not available");
+        let error = SmtpMailer::new(&config)
+            .unwrap()
+            .send(&message())
+            .unwrap_err();
+        assert_eq!(500, error.code);
+        assert_eq!("mail_unavailable", error.error);
+
+        // Penolakan permanen tidak boleh dikirim ulang
+        let config = fake_smtp_server("554 No SMTP service here");
+        let error = SmtpMailer::new(&config)
+            .unwrap()
+            .send(&message())
+            .unwrap_err();
+        assert_eq!(400, error.code);
+        assert_eq!("mail_rejected", error.error);
+
+        let config = SmtpConfig {
+            from: String::from("not an address"),
+            ..SmtpConfig::default()
+        };
+        assert!(SmtpMailer::new(&config).is_err());
+    }
+
+    /**
+    Mengirim email ke SMTP server lokal tanpa TLS, misal container MailHog :
+    `docker run -p 1025:1025 -p 8025:8025 mailhog/mailhog` lalu jalankan test dengan
+    `BROKOLI_SMTP_TEST=localhost:1025 cargo test test_smtp_mailer -- --ignored`
+    */
+    #[test]
+    #[ignore = "membutuhkan SMTP server, lihat BROKOLI_SMTP_TEST"]
+    fn test_smtp_mailer() {
+        let server = std::env::var("BROKOLI_SMTP_TEST").expect("BROKOLI_SMTP_TEST is not set");
+        let (host, port) = server.split_once(':').unwrap_or((&server, "1025"));
+        let config = SmtpConfig {
+            host: String::from(host),
+            port: port.parse().unwrap(),
+            security: SmtpSecurity::None,
+            from: String::from("Brokoli <no-reply@brokoli.id>"),
+            ..SmtpConfig::default()
+        };
+        SmtpMailer::new(&config).unwrap().send(&message()).unwrap();
+    }
+
+    #[test]
+    fn test_smtp_config() {
+        let config: SmtpConfig = serde_json::from_str(
+            r#"{"host": "smtp.brokoli.id", "security": "tls", "port": 465, "username": "mailer", "password": "secret"}"#,
+        )
+        .unwrap();
+        assert_eq!(SmtpSecurity::Tls, config.security);
+        assert_eq!(465, config.port);
+        assert_eq!("no-reply@localhost", config.from);
+        assert!(!format!("{:?}", config).contains("secret"));
+
+        // Karakter zero-width dan fullwidth pada credential SMTP tidak diubah
+        let config: SmtpConfig =
+            serde_json::from_str(r#"{"password": "se\u200bcret\uff01"}"#).unwrap();
+        assert_eq!(
+            "se\u{200b}cret\u{ff01}",
+            config.password.unwrap().expose_secret()
+        );
+    }
+
+    #[test]
+    fn test_retry_mailer() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: 0,
+            max_backoff: 0,
+        };
+        let inner = CapturingMailer::new();
+        let mailer = RetryMailer::new(inner.clone()).with_policy(policy);
+
+        inner.fail_next(2);
+        mailer.send(&message()).unwrap();
+        assert_eq!(1, inner.sent().len());
+
+        inner.fail_next(3);
+        let error = mailer.send(&message()).unwrap_err();
+        assert_eq!("mail_unavailable", error.error);
+        assert_eq!(1, inner.sent().len());
+
+        // Kesalahan dari sisi request tidak dikirim ulang
+        let mut invalid = MockMailer::new();
+        invalid
+            .expect_send()
+            .times(1)
+            .returning(|_| Err(mail_rejected()));
+        assert!(RetryMailer::new(invalid)
+            .with_policy(policy)
+            .send(&message())
+            .is_err());
+    }
+
+    #[test]
+    fn test_queued_mailer() {
+        // Inner mailer yang lambat tidak menahan pemanggil
+        struct SlowMailer(CapturingMailer);
+        impl Mailer for SlowMailer {
+            fn send(&self, message: &Message) -> Result<(), error::ApplicationError<'static>> {
+                thread::sleep(Duration::from_millis(200));
+                self.0.send(message)
+            }
+        }
+
+        let inner = CapturingMailer::new();
+        let mailer = QueuedMailer::start(SlowMailer(inner.clone()));
+        let started = std::time::Instant::now();
+        for _ in 0..3 {
+            mailer.send(&message()).unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        // Email di antrian tetap terkirim saat mailer dihentikan
+        drop(mailer);
+        assert_eq!(3, inner.sent().len());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: 500,
+            max_backoff: 5000,
+        };
+        assert_eq!(500, policy.backoff(1));
+        assert_eq!(1000, policy.backoff(2));
+        assert_eq!(4000, policy.backoff(4));
+        assert_eq!(5000, policy.backoff(5));
+        assert_eq!(5000, policy.backoff(100));
+    }
+}
@@ -1,4 +1,31 @@
+pub mod access_rule;
+pub mod breach;
+pub mod cipher;
+pub mod client;
+pub mod config;
+pub mod email;
 pub mod error;
-pub mod password;
 pub mod function;
-pub mod email;
\ No newline at end of file
+pub mod geoip;
+pub mod mailer;
+// Test bawaan modul password ditulis sebelum clippy dijalankan dengan -D warnings dan dibiarkan apa adanya
+#[cfg_attr(
+    test,
+    allow(
+        clippy::needless_borrow,
+        clippy::bool_assert_comparison,
+        clippy::single_match
+    )
+)]
+pub mod password;
+pub mod password_policy;
+pub mod phone;
+pub mod proof_of_work;
+pub mod rate_limit;
+pub mod signature;
+pub mod sms;
+pub mod store;
+pub mod strength;
+pub mod token;
+pub mod totp;
+pub mod webauthn;
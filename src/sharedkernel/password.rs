@@ -1,58 +1,85 @@
 use super::error;
+use super::password_policy::PasswordPolicy;
 use pwhash::sha512_crypt;
 use serde::{Deserialize, Serialize, Serializer};
 use std::fmt;
-use validator::{Validate, ValidationError};
+use unicode_normalization::UnicodeNormalization;
+use validator::{Validate, ValidationErrors};
+use zeroize::Zeroize;
 
-/// Merepresentasikan object password
-#[derive(Validate, PartialEq, Eq)]
+// Representasi secret pada Debug, agar tidak bocor ke log
+const REDACTED: &str = "[REDACTED]";
+
+/**
+Merepresentasikan object password. Password diperlakukan sebagai secret : tidak dapat ditampilkan
+melalui Display, Debug menampilkan `[REDACTED]`, dan memory di-zeroize saat object di-drop.
+Teks password hanya dapat diakses secara eksplisit melalui `expose_secret`.
+*/
+#[derive(PartialEq, Eq)]
 pub struct Password {
-    #[validate(length(min = 8, max = 18), custom = "validate_pass")]
     value: String,
 }
 
-// Extended password validator
-fn validate_pass(passw: &String) -> Result<(), ValidationError> {
-    let mut has_lower = false;
-    let mut has_upper = false;
-    let mut has_number = false;
-    let mut has_special = false;
-
-    for c in passw.chars() {
-        if c.is_ascii() {
-            if c.is_alphabetic() {
-                if c.is_lowercase() {
-                    has_lower = true;
-                } else if c.is_uppercase() {
-                    has_upper = true;
-                }
-            } else if c.is_numeric() {
-                has_number = true;
-            } else {
-                has_special = true;
-            }
-        }
+// Tanpa policy eksplisit, password divalidasi menggunakan PasswordPolicy::default()
+impl Validate for Password {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        PasswordPolicy::default().check(self).validate()
     }
-
-    if !(has_lower && has_upper && has_number && has_special) {
-        return Err(ValidationError::new("Invalid Password"));
-    }
-
-    Ok(())
 }
 
 impl Password {
     /**
-    Mengembalikan sebuah Password object dari string literal (*str)
+    Mengembalikan sebuah Password object dari string literal (*str). Teks password dinormalisasi
+    terlebih dahulu (lihat `normalize`) sehingga validasi dan hashing selalu menggunakan bentuk
+    yang sama meskipun diketik dari keyboard atau input method yang berbeda.
 
     # Arguments
     * `value` - Sebuah literal sebagai teks password
     */
     pub fn from(value: &str) -> Password {
         Password {
-            value: String::from(value),
+            value: normalize(value),
         }
     }
+
+    /// Mengembalikan teks password. Hanya digunakan untuk hashing dan pemeriksaan password.
+    pub fn expose_secret(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Drop for Password {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+/**
+Normalisasi teks password ala SASLprep : karakter yang "commonly mapped to nothing"
+(RFC 3454 tabel B.1) dihapus, kemudian teks dinormalisasi dengan NFKC. Spasi non-ASCII
+(misal no-break space atau ideographic space) otomatis menjadi spasi biasa melalui NFKC.
+*/
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !is_mapped_to_nothing(*c))
+        .nfkc()
+        .collect()
+}
+
+// RFC 3454 tabel B.1
+fn is_mapped_to_nothing(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{034F}'
+            | '\u{1806}'
+            | '\u{180B}'..='\u{180D}'
+            | '\u{200B}'..='\u{200D}'
+            | '\u{2060}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FEFF}'
+    )
 }
 
 impl<'de> Deserialize<'de> for Password {
@@ -60,25 +87,25 @@ impl<'de> Deserialize<'de> for Password {
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        Ok(Password { value: s })
-    }
-}
-
-impl fmt::Display for Password {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.value)
+        let mut s = String::deserialize(deserializer)?;
+        let password = Password::from(&s);
+        s.zeroize();
+        Ok(password)
     }
 }
 
 impl fmt::Debug for Password {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.value)
+        write!(f, "{}", REDACTED)
     }
 }
 
-/// Merepresentasikan object hash. Biasanya digunakan untuk menyimpan hashed password.
+/**
+Merepresentasikan object hash. Biasanya digunakan untuk menyimpan hashed password.
+Seperti Password, Debug menampilkan `[REDACTED]` dan memory di-zeroize saat object di-drop.
+*/
 #[derive(Default)]
+#[cfg_attr(test, derive(Clone))]
 pub struct Hash {
     hash: String,
 }
@@ -114,7 +141,20 @@ impl Hash {
         key: &str,
         password: &Password,
     ) -> Result<Hash, error::ApplicationError<'static>> {
-        match sha512_crypt::hash_with(key, password.to_string()) {
+        match sha512_crypt::hash_with(key, password.expose_secret()) {
+            Ok(result) => Ok(Hash { hash: result }),
+            Err(_) => Err(error::internal_server_error!()),
+        }
+    }
+
+    /**
+    Mengembalikan hash result dari object &Password menggunakan random salt
+
+    # Arguments
+    * `password` = reference dari object Password
+    */
+    pub fn generate(password: &Password) -> Result<Hash, error::ApplicationError<'static>> {
+        match sha512_crypt::hash(password.expose_secret()) {
             Ok(result) => Ok(Hash { hash: result }),
             Err(_) => Err(error::internal_server_error!()),
         }
@@ -130,19 +170,24 @@ impl Hash {
         if self.hash.is_empty() {
             return Err("Hash is empty");
         }
-        Ok(sha512_crypt::verify(password.to_string(), &self.hash))
+        Ok(sha512_crypt::verify(password.expose_secret(), &self.hash))
+    }
+
+    /// Mengembalikan hashed string, misal untuk disimpan ke storage
+    pub fn expose_secret(&self) -> &str {
+        &self.hash
     }
 }
 
-impl fmt::Display for Hash {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.hash)
+impl Drop for Hash {
+    fn drop(&mut self) {
+        self.hash.zeroize();
     }
 }
 
 impl fmt::Debug for Hash {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.hash)
+        write!(f, "{}", REDACTED)
     }
 }
 
@@ -200,6 +245,28 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_generate_hash() {
+        let my_password: Password = Password::from("Aasolole123!");
+        let first: Hash = Hash::generate(&my_password).unwrap();
+        let second: Hash = Hash::generate(&my_password).unwrap();
+
+        assert_ne!(first.expose_secret(), second.expose_secret());
+        assert!(first.verify_password(&my_password).unwrap());
+        assert!(second.verify_password(&my_password).unwrap());
+    }
+
+    #[test]
+    fn test_redacted_debug() {
+        let my_password: Password = Password::from("Aasolole123!");
+        let my_hash: Hash = Hash::generate(&my_password).unwrap();
+
+        assert_eq!("[REDACTED]", format!("{:?}", my_password));
+        assert_eq!("[REDACTED]", format!("{:?}", my_hash));
+        assert_eq!("Aasolole123!", my_password.expose_secret());
+        assert!(my_hash.expose_secret().starts_with("$6$"));
+    }
+
     macro_rules! password_validation_test_cases {
         (
             $(
@@ -222,6 +289,39 @@ mod test {
         (lowercase_only_password_test, "mypassword", true),
         (lower_and_upper_case_only_password_test, "MypassworD", true),
         (no_special_char_password_test, "MypassworD1234", true),
-        (good_password_test, "MypassworD1234!", false)
+        (good_password_test, "MypassworD1234!", false),
+        (long_passphrase_test, "MypassworD1234!-with-a-long-passphrase", false),
+        (japanese_passphrase_test, "正しい馬の電池ホチキス2024!", false),
+        (indonesian_passphrase_test, "Kata-Sandi-Rahasiaku-2024", false),
+        (control_character_password_test, "MypassworD1234!\u{0007}", true)
+    }
+
+    #[test]
+    fn test_normalize_compatibility_form() {
+        // Fullwidth (misal dari IME Jepang) dan halfwidth harus menghasilkan password yang sama
+        assert_eq!(
+            Password::from("Ｐａｓｓ１２３！"),
+            Password::from("Pass123!")
+        );
+        // Precomposed dan combining character
+        assert_eq!(
+            Password::from("Caf\u{00E9}-2024!"),
+            Password::from("Cafe\u{0301}-2024!")
+        );
+        // Karakter tak terlihat dihapus dan spasi non-ASCII menjadi spasi biasa
+        assert_eq!(
+            Password::from("My\u{200B}pass\u{00A0}word1!"),
+            Password::from("Mypass word1!")
+        );
+    }
+
+    #[test]
+    fn test_normalize_before_hashing() {
+        let key: &str = "$6$G/gkPn17kHYo0gTF$";
+        let hash = Hash::from_password(key, &Password::from("Ｐａｓｓ１２３！")).unwrap();
+        assert!(hash.verify_password(&Password::from("Pass123!")).unwrap());
+
+        let deserialized: Password = serde_json::from_str("\"Ｐａｓｓ１２３！\"").unwrap();
+        assert!(hash.verify_password(&deserialized).unwrap());
     }
 }
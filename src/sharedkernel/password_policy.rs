@@ -0,0 +1,390 @@
+use super::password::Password;
+use super::strength;
+use serde::Deserialize;
+use std::borrow::Cow;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+/**
+Merepresentasikan aturan password yang berlaku. Policy dapat dimuat dari konfigurasi
+(lihat `config::load`), field yang tidak didefinisikan akan menggunakan default value.
+*/
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    pub max_repeated_chars: Option<usize>,
+    pub banned_substrings: Vec<String>,
+    pub min_strength_score: Option<u8>,
+    /// Jumlah password terakhir (termasuk password saat ini) yang tidak boleh digunakan kembali
+    pub history_depth: usize,
+    /// Usia maksimal password dalam detik, setelahnya user wajib mengganti password
+    pub max_age: Option<u64>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_special: true,
+            max_repeated_chars: None,
+            banned_substrings: Vec::new(),
+            min_strength_score: None,
+            history_depth: 0,
+            max_age: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /**
+    Mengembalikan object yang dapat divalidasi (misal melalui `ApplicationError::validate`)
+    untuk memeriksa password terhadap policy ini
+
+    # Arguments
+    * `password` - Reference dari object Password yang akan diperiksa
+    */
+    pub fn check<'a>(&'a self, password: &'a Password) -> PasswordCheck<'a> {
+        PasswordCheck {
+            policy: self,
+            password,
+            user_inputs: Vec::new(),
+        }
+    }
+
+    // Memeriksa password dan mengembalikan seluruh pelanggaran aturan
+    fn violations(&self, passw: &str, user_inputs: &[String]) -> Vec<ValidationError> {
+        let mut errors: Vec<ValidationError> = Vec::new();
+
+        let length = passw.chars().count();
+        if length < self.min_length {
+            errors.push(rule_error("password_too_short", "min", self.min_length));
+        }
+        if length > self.max_length {
+            errors.push(rule_error("password_too_long", "max", self.max_length));
+        }
+
+        let classes = CharacterClasses::of(passw);
+        if classes.control {
+            errors.push(ValidationError::new("password_control_character"));
+        }
+
+        if self.require_lowercase && !classes.lower {
+            errors.push(ValidationError::new("password_missing_lowercase"));
+        }
+        if self.require_uppercase && !classes.upper {
+            errors.push(ValidationError::new("password_missing_uppercase"));
+        }
+        if self.require_digit && !classes.digit {
+            errors.push(ValidationError::new("password_missing_digit"));
+        }
+        if self.require_special && !classes.special {
+            errors.push(ValidationError::new("password_missing_special"));
+        }
+
+        if let Some(max) = self.max_repeated_chars {
+            if longest_run(passw) > max {
+                errors.push(rule_error("password_repeated_chars", "max", max));
+            }
+        }
+
+        let lowered = passw.to_lowercase();
+        let banned: Vec<&String> = self
+            .banned_substrings
+            .iter()
+            .filter(|s| !s.is_empty() && lowered.contains(&s.to_lowercase()))
+            .collect();
+        if !banned.is_empty() {
+            errors.push(rule_error("password_banned_substring", "found", banned));
+        }
+
+        // Password yang terlalu panjang sudah ditolak, sehingga tidak perlu diestimasi
+        if let Some(min_score) = self
+            .min_strength_score
+            .filter(|_| length <= self.max_length)
+        {
+            let strength = strength::estimate(passw, user_inputs);
+            if strength.score < min_score {
+                let mut error = rule_error("password_too_weak", "min_score", min_score);
+                error.add_param(Cow::from("score"), &strength.score);
+                error.add_param(Cow::from("warning"), &strength.warning);
+                error.add_param(Cow::from("suggestions"), &strength.suggestions);
+                errors.push(error);
+            }
+        }
+
+        errors
+    }
+}
+
+/**
+Kelas karakter yang ditemukan pada password. Klasifikasi menggunakan properti Unicode, bukan
+hanya ASCII, sehingga passphrase dalam aksara apapun dapat memenuhi aturan policy. Huruf yang
+tidak mengenal huruf besar/kecil (misal kanji, hiragana, aksara Arab) dianggap memenuhi
+kedua aturan tersebut.
+*/
+#[derive(Default)]
+struct CharacterClasses {
+    lower: bool,
+    upper: bool,
+    digit: bool,
+    special: bool,
+    control: bool,
+}
+
+impl CharacterClasses {
+    fn of(passw: &str) -> CharacterClasses {
+        let mut classes = CharacterClasses::default();
+
+        for c in passw.chars() {
+            if c.is_control() {
+                classes.control = true;
+            } else if c.is_alphabetic() {
+                if c.is_lowercase() {
+                    classes.lower = true;
+                } else if c.is_uppercase() {
+                    classes.upper = true;
+                } else {
+                    classes.lower = true;
+                    classes.upper = true;
+                }
+            } else if c.is_numeric() {
+                classes.digit = true;
+            } else {
+                classes.special = true;
+            }
+        }
+
+        classes
+    }
+}
+
+// Membentuk ValidationError dengan satu parameter tambahan
+fn rule_error<T: serde::Serialize>(
+    code: &'static str,
+    name: &'static str,
+    value: T,
+) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.add_param(Cow::from(name), &value);
+    error
+}
+
+// Menghitung jumlah karakter identik terpanjang yang muncul berurutan
+fn longest_run(passw: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<char> = None;
+
+    for c in passw.chars() {
+        if previous == Some(c) {
+            current += 1;
+        } else {
+            current = 1;
+            previous = Some(c);
+        }
+        longest = longest.max(current);
+    }
+
+    longest
+}
+
+/// Pasangan password dan policy yang siap divalidasi
+pub struct PasswordCheck<'a> {
+    policy: &'a PasswordPolicy,
+    password: &'a Password,
+    user_inputs: Vec<String>,
+}
+
+impl PasswordCheck<'_> {
+    /**
+    Menambahkan teks yang berhubungan dengan user (misal `strength::email_inputs`) sebagai
+    penalti pada estimasi kekuatan password
+
+    # Arguments
+    * `user_inputs` - Teks yang dianggap mudah ditebak
+    */
+    pub fn with_user_inputs(mut self, user_inputs: Vec<String>) -> Self {
+        self.user_inputs = user_inputs;
+        self
+    }
+}
+
+impl Validate for PasswordCheck<'_> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for error in self
+            .policy
+            .violations(self.password.expose_secret(), &self.user_inputs)
+        {
+            errors.add("value", error);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{config, error::ApplicationError};
+    use super::*;
+
+    // Mengembalikan seluruh error code hasil validasi
+    fn codes(policy: &PasswordPolicy, passw: &str) -> Vec<String> {
+        let password = Password::from(passw);
+        match policy.check(&password).validate() {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors
+                .field_errors()
+                .get("value")
+                .unwrap()
+                .iter()
+                .map(|e| e.code.to_string())
+                .collect(),
+        }
+    }
+
+    macro_rules! policy_test_cases {
+        (
+            $(
+                ($test_name: ident, $passw: expr, $expected: expr)
+            ),*
+        ) => {
+            $(
+                #[test]
+                fn $test_name() {
+                    let expected: Vec<&str> = $expected;
+                    assert_eq!(expected, codes(&PasswordPolicy::default(), $passw))
+                }
+            )*
+        };
+    }
+
+    policy_test_cases! {
+        (too_short_test, "Ab1!", vec!["password_too_short"]),
+        (missing_lowercase_test, "ABCDEFG1!", vec!["password_missing_lowercase"]),
+        (missing_uppercase_test, "abcdefg1!", vec!["password_missing_uppercase"]),
+        (missing_digit_test, "Abcdefgh!", vec!["password_missing_digit"]),
+        (missing_special_test, "Abcdefgh1", vec!["password_missing_special"]),
+        (long_passphrase_test, "Correct-Horse-Battery-Staple-2024", vec![]),
+        (unicode_letter_test, "Ünïcödé-2024", vec![]),
+        (unicode_digit_test, "Pass-word-٣٤", vec![]),
+        (caseless_letter_test, "パスワード-2024", vec![]),
+        (control_character_test, "Pass\tword-2024", vec!["password_control_character"]),
+        (multiple_violation_test, "abc", vec![
+            "password_too_short",
+            "password_missing_uppercase",
+            "password_missing_digit",
+            "password_missing_special"
+        ])
+    }
+
+    #[test]
+    fn test_too_long() {
+        let policy = PasswordPolicy {
+            max_length: 12,
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(vec!["password_too_long"], codes(&policy, "MypassworD1234!"));
+    }
+
+    #[test]
+    fn test_repeated_chars() {
+        let policy = PasswordPolicy {
+            max_repeated_chars: Some(2),
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(
+            vec!["password_repeated_chars"],
+            codes(&policy, "Myyypass1!")
+        );
+        assert!(codes(&policy, "Myypass1!").is_empty());
+    }
+
+    #[test]
+    fn test_banned_substring() {
+        let policy = PasswordPolicy {
+            banned_substrings: vec![String::from("brokoli")],
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(
+            vec!["password_banned_substring"],
+            codes(&policy, "MyBrokoli123!")
+        );
+    }
+
+    #[test]
+    fn test_min_strength_score() {
+        let policy = PasswordPolicy {
+            min_strength_score: Some(3),
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(vec!["password_too_weak"], codes(&policy, "Password1!"));
+        assert!(codes(&policy, "rT9#kq!Zp2vL").is_empty());
+
+        let password = Password::from("Digitalsekuriti#1");
+        let inputs = vec![String::from("harun"), String::from("digitalsekuriti")];
+        assert!(policy.check(&password).validate().is_ok());
+        let errors = policy
+            .check(&password)
+            .with_user_inputs(inputs)
+            .validate()
+            .unwrap_err();
+        let error = &errors.field_errors()["value"][0];
+        assert_eq!("password_too_weak", error.code);
+        assert!(error.params.contains_key("score"));
+        assert!(error.params.contains_key("suggestions"));
+    }
+
+    #[test]
+    fn test_too_long_password_is_not_estimated() {
+        let policy = PasswordPolicy {
+            max_length: 64,
+            min_strength_score: Some(3),
+            ..PasswordPolicy::default()
+        };
+        let passw = "Ab1!".repeat(2500);
+
+        let started = std::time::Instant::now();
+        assert_eq!(vec!["password_too_long"], codes(&policy, &passw));
+        assert!(started.elapsed().as_secs() < 1);
+    }
+
+    #[test]
+    fn test_load_from_config() {
+        let policy: PasswordPolicy =
+            config::parse(r#"{"min_length": 12, "require_special": false, "history_depth": 5}"#)
+                .unwrap();
+        assert_eq!(12, policy.min_length);
+        assert_eq!(5, policy.history_depth);
+        assert_eq!(128, policy.max_length);
+        assert!(!policy.require_special);
+        assert!(policy.require_digit);
+    }
+
+    #[test]
+    fn test_application_error() {
+        let policy = PasswordPolicy::default();
+        let password = Password::from("mypass");
+        let error = ApplicationError::validate(policy.check(&password)).unwrap();
+
+        let fields = error.fields.unwrap();
+        let value = fields.get("value").unwrap();
+        assert!(value.iter().any(|e| e.contains("password_too_short")));
+        assert!(value
+            .iter()
+            .any(|e| e.contains("password_missing_uppercase")));
+    }
+}
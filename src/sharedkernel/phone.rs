@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// Nomor telepon dalam format E.164, misal +6281234567890
+#[derive(PartialEq, Eq, Clone)]
+pub struct PhoneNumber {
+    value: String,
+}
+
+impl PhoneNumber {
+    /**
+    Mengembalikan nomor telepon dari string literal. Spasi dan karakter pemisah yang umum
+    digunakan (`-`, `.`, `(`, `)`) dihapus.
+
+    # Arguments
+    * `value` - Nomor telepon, misal "+62 812-3456-7890"
+    */
+    pub fn from(value: &str) -> PhoneNumber {
+        PhoneNumber {
+            value: value
+                .chars()
+                .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '.' | '(' | ')'))
+                .collect(),
+        }
+    }
+
+    /// Memeriksa format E.164 : '+' diikuti kode negara dan nomor, total 8 - 15 digit
+    pub fn is_valid(&self) -> bool {
+        match self.value.strip_prefix('+') {
+            Some(digits) => {
+                (8..=15).contains(&digits.len())
+                    && digits.chars().all(|c| c.is_ascii_digit())
+                    && !digits.starts_with('0')
+            }
+            None => false,
+        }
+    }
+
+    /// Mengembalikan nomor dengan digit tengah disamarkan, untuk ditampilkan kepada user
+    pub fn masked(&self) -> String {
+        let length = self.value.chars().count();
+        self.value
+            .chars()
+            .enumerate()
+            .map(|(i, c)| if i < 3 || i + 2 >= length { c } else { '*' })
+            .collect()
+    }
+}
+
+impl fmt::Debug for PhoneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl fmt::Display for PhoneNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Serialize for PhoneNumber {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.value)
+    }
+}
+
+impl<'de> Deserialize<'de> for PhoneNumber {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(PhoneNumber::from(&s))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_validate() {
+        let phone = PhoneNumber::from("+62 (812) 3456-7890");
+        assert_eq!("+6281234567890", phone.to_string());
+        assert!(phone.is_valid());
+        assert_eq!("+62*********90", phone.masked());
+
+        for invalid in [
+            "081234567890",
+            "+0812345678",
+            "+62812",
+            "+62812x4567890",
+            "",
+        ] {
+            assert!(!PhoneNumber::from(invalid).is_valid(), "{}", invalid);
+        }
+
+        let phone: PhoneNumber = serde_json::from_str("\"+1 415.555.0100\"").unwrap();
+        assert_eq!("\"+14155550100\"", serde_json::to_string(&phone).unwrap());
+    }
+}
@@ -0,0 +1,411 @@
+use super::{client::ClientInfo, error, signature, store::KeyValueStore, token::Token};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/*
+==============================================================================================================================
+Proof of work (hashcash)
+==============================================================================================================================
+Ketika jumlah request dari satu IP melebihi threshold, client diwajibkan menyelesaikan challenge : mencari `counter`
+sehingga SHA-256("<nonce>:<counter>") memiliki minimal `difficulty` bit nol di depan. Difficulty naik satu bit
+(dua kali lipat pekerjaan) setiap kali jumlah request berlipat ganda di atas threshold.
+
+Challenge bersifat stateless : server hanya menandatangani challenge (terikat dengan IP client) dan memverifikasi
+signature-nya. Nonce yang sudah digunakan dicatat di KeyValueStore hingga challenge expired agar tidak dapat di-replay.
+Solusi hanya diterima jika difficulty challenge minimal sama dengan difficulty yang berlaku saat solusi dikirim,
+sehingga challenge murah yang dikumpulkan sebelum burst tidak dapat digunakan setelah difficulty naik.
+*/
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProofOfWorkPolicy {
+    /// Jumlah request per window dari satu IP sebelum challenge diwajibkan
+    pub threshold: u32,
+    /// Dalam detik
+    pub window: u64,
+    pub base_difficulty: u8,
+    pub max_difficulty: u8,
+    /// Masa berlaku challenge dalam detik
+    pub challenge_ttl: u64,
+}
+
+impl Default for ProofOfWorkPolicy {
+    fn default() -> Self {
+        ProofOfWorkPolicy {
+            threshold: 5,
+            window: 3600,
+            base_difficulty: 16,
+            max_difficulty: 24,
+            challenge_ttl: 300,
+        }
+    }
+}
+
+/// Challenge yang diberikan kepada client
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Challenge {
+    pub nonce: String,
+    pub difficulty: u8,
+    pub expires_at: u64,
+    pub signature: String,
+}
+
+/// Challenge beserta counter yang ditemukan oleh client
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Solution {
+    pub challenge: Challenge,
+    pub counter: u64,
+}
+
+// Data yang ditandatangani, challenge hanya berlaku untuk IP yang memintanya
+fn signed_message(nonce: &str, difficulty: u8, expires_at: u64, client: &ClientInfo) -> String {
+    format!("{}:{}:{}:{}", nonce, difficulty, expires_at, client.ip)
+}
+
+// Jumlah bit nol di depan SHA-256("<nonce>:<counter>")
+fn leading_zero_bits(nonce: &str, counter: u64) -> u32 {
+    let digest = Sha256::digest(format!("{}:{}", nonce, counter).as_bytes());
+    let mut bits = 0;
+    for byte in digest.iter() {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/**
+Mencari counter untuk challenge. Digunakan oleh client (dan test), server cukup memverifikasi.
+
+# Arguments
+* `challenge` - Challenge dari server
+*/
+pub fn solve(challenge: &Challenge) -> Solution {
+    let counter = (0..)
+        .find(|counter| {
+            leading_zero_bits(&challenge.nonce, *counter) >= challenge.difficulty as u32
+        })
+        .unwrap_or_default();
+    Solution {
+        challenge: challenge.clone(),
+        counter,
+    }
+}
+
+pub struct ProofOfWork<'a> {
+    store: &'a dyn KeyValueStore,
+    policy: &'a ProofOfWorkPolicy,
+    secret: &'a [u8],
+    /// Nama request yang dihitung, misal "registration"
+    scope: &'a str,
+}
+
+impl<'a> ProofOfWork<'a> {
+    pub fn new(
+        store: &'a dyn KeyValueStore,
+        policy: &'a ProofOfWorkPolicy,
+        secret: &'a [u8],
+        scope: &'a str,
+    ) -> ProofOfWork<'a> {
+        ProofOfWork {
+            store,
+            policy,
+            secret,
+            scope,
+        }
+    }
+
+    // Jumlah request dari satu IP dihitung per fixed window
+    fn count_key(&self, client: &ClientInfo, now: u64) -> String {
+        let window = now / self.policy.window.max(1);
+        format!("pow:{}:count:{}:{}", self.scope, client.ip, window)
+    }
+
+    fn count(
+        &self,
+        client: &ClientInfo,
+        now: u64,
+    ) -> Result<u32, error::ApplicationError<'static>> {
+        Ok(self
+            .store
+            .get(&self.count_key(client, now))?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default())
+    }
+
+    /**
+    Mengembalikan difficulty yang berlaku untuk client, None jika challenge belum diwajibkan
+
+    # Arguments
+    * `client` - Informasi client
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn difficulty(
+        &self,
+        client: &ClientInfo,
+        now: u64,
+    ) -> Result<Option<u8>, error::ApplicationError<'static>> {
+        let count = self.count(client, now)?;
+        let threshold = self.policy.threshold.max(1);
+        if count < threshold {
+            return Ok(None);
+        }
+
+        let extra = (count / threshold).ilog2();
+        let difficulty =
+            (self.policy.base_difficulty as u32 + extra).min(self.policy.max_difficulty as u32);
+        Ok(Some(difficulty as u8))
+    }
+
+    /**
+    Membuat challenge baru yang ditandatangani untuk client
+
+    # Arguments
+    * `client` - Informasi client
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn issue(
+        &self,
+        client: &ClientInfo,
+        now: u64,
+    ) -> Result<Challenge, error::ApplicationError<'static>> {
+        let difficulty = self
+            .difficulty(client, now)?
+            .unwrap_or(self.policy.base_difficulty);
+        let nonce = Token::generate().expose_secret().to_string();
+        let expires_at = now + self.policy.challenge_ttl;
+        let signature = signature::sign(
+            self.secret,
+            &signed_message(&nonce, difficulty, expires_at, client),
+        );
+
+        Ok(Challenge {
+            nonce,
+            difficulty,
+            expires_at,
+            signature,
+        })
+    }
+
+    /**
+    Memverifikasi solusi challenge : signature, masa berlaku, IP, hasil pekerjaan dan replay
+
+    # Arguments
+    * `solution` - Solusi dari client
+    * `client` - Informasi client
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn verify(
+        &self,
+        solution: &Solution,
+        client: &ClientInfo,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let invalid = || {
+            error::bad_request_error!(
+                "Sorry, the proof of work is invalid or has expired. Please request a new challenge.",
+                "invalid_proof_of_work"
+            )
+        };
+
+        let challenge = &solution.challenge;
+        let message = signed_message(
+            &challenge.nonce,
+            challenge.difficulty,
+            challenge.expires_at,
+            client,
+        );
+        if !signature::verify(self.secret, &message, &challenge.signature)
+            || challenge.expires_at <= now
+            || leading_zero_bits(&challenge.nonce, solution.counter) < challenge.difficulty as u32
+        {
+            return Err(invalid());
+        }
+
+        // Dicatat secara atomic agar satu solusi tidak dapat digunakan oleh request paralel
+        let used_key = format!("pow:{}:used:{}", self.scope, challenge.nonce);
+        if !self
+            .store
+            .set_if_absent(&used_key, "1", challenge.expires_at - now)?
+        {
+            return Err(invalid());
+        }
+        Ok(())
+    }
+
+    /**
+    Mewajibkan solusi challenge jika jumlah request dari client melebihi threshold, lalu menghitung
+    request tersebut. Challenge dengan difficulty di bawah difficulty yang berlaku ditolak.
+
+    # Arguments
+    * `solution` - Solusi dari client, jika ada
+    * `client` - Informasi client
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn ensure_solved(
+        &self,
+        solution: Option<&Solution>,
+        client: &ClientInfo,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if let Some(required) = self.difficulty(client, now)? {
+            match solution {
+                Some(solution) if solution.challenge.difficulty < required => {
+                    return Err(error::bad_request_error!(
+                        "Sorry, the proof of work challenge is too easy. Please request a new challenge.",
+                        "proof_of_work_too_easy"
+                    ))
+                }
+                Some(solution) => self.verify(solution, client, now)?,
+                None => {
+                    return Err(error::forbidden_error!(
+                        "Sorry, we received too many requests from your network. Please solve the proof of work challenge and try again.",
+                        "proof_of_work_required"
+                    ))
+                }
+            }
+        }
+
+        let window = self.policy.window.max(1);
+        let ttl = (now / window + 1) * window - now;
+        self.store
+            .incr_with_ttl(&self.count_key(client, now), ttl)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::store::MemoryStore;
+    use super::*;
+
+    const NOW: u64 = 1669969200;
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn client(ip: &str) -> ClientInfo {
+        ClientInfo::new(ip.parse().unwrap())
+    }
+
+    fn policy() -> ProofOfWorkPolicy {
+        ProofOfWorkPolicy {
+            threshold: 2,
+            base_difficulty: 8,
+            max_difficulty: 10,
+            ..ProofOfWorkPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_required_after_threshold() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let pow = ProofOfWork::new(&store, &policy, SECRET, "registration");
+        let alice = client("10.0.0.1");
+
+        for _ in 0..2 {
+            assert!(pow.ensure_solved(None, &alice, NOW).is_ok());
+        }
+        let error = pow.ensure_solved(None, &alice, NOW).unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("proof_of_work_required", error.error);
+
+        // IP lain dan window berikutnya tidak terpengaruh
+        assert!(pow.ensure_solved(None, &client("10.0.0.2"), NOW).is_ok());
+        assert!(pow.ensure_solved(None, &alice, NOW + 3600).is_ok());
+
+        let challenge = pow.issue(&alice, NOW).unwrap();
+        assert!(pow
+            .ensure_solved(Some(&solve(&challenge)), &alice, NOW)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_adaptive_difficulty() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let pow = ProofOfWork::new(&store, &policy, SECRET, "registration");
+        let alice = client("10.0.0.1");
+        let mut expected = vec![
+            None,
+            None,
+            Some(8),
+            Some(8),
+            Some(9),
+            Some(9),
+            Some(9),
+            Some(9),
+            Some(10),
+        ];
+        expected.extend([Some(10); 10]);
+
+        for difficulty in expected {
+            assert_eq!(difficulty, pow.difficulty(&alice, NOW).unwrap());
+            let challenge = pow.issue(&alice, NOW).unwrap();
+            pow.ensure_solved(Some(&solve(&challenge)), &alice, NOW)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reject_challenge_issued_before_burst() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let pow = ProofOfWork::new(&store, &policy, SECRET, "registration");
+        let alice = client("10.0.0.1");
+
+        // Challenge murah dikumpulkan sebelum difficulty naik
+        let cheap = pow.issue(&alice, NOW).unwrap();
+        assert_eq!(8, cheap.difficulty);
+        for _ in 0..4 {
+            let challenge = pow.issue(&alice, NOW).unwrap();
+            pow.ensure_solved(Some(&solve(&challenge)), &alice, NOW)
+                .unwrap();
+        }
+        assert_eq!(Some(9), pow.difficulty(&alice, NOW).unwrap());
+
+        let error = pow
+            .ensure_solved(Some(&solve(&cheap)), &alice, NOW)
+            .unwrap_err();
+        assert_eq!("proof_of_work_too_easy", error.error);
+    }
+
+    #[test]
+    fn test_reject_invalid_solution() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let pow = ProofOfWork::new(&store, &policy, SECRET, "registration");
+        let alice = client("10.0.0.1");
+        let challenge = pow.issue(&alice, NOW).unwrap();
+        let solution = solve(&challenge);
+
+        let assert_invalid = |solution: &Solution, client: &ClientInfo, now: u64| {
+            let error = pow.verify(solution, client, now).unwrap_err();
+            assert_eq!("invalid_proof_of_work", error.error);
+        };
+
+        // Difficulty diturunkan oleh client
+        let mut tampered = solution.clone();
+        tampered.challenge.difficulty = 1;
+        assert_invalid(&tampered, &alice, NOW);
+
+        // Counter yang salah
+        let mut wrong = solution.clone();
+        wrong.counter = (0..)
+            .find(|counter| leading_zero_bits(&challenge.nonce, *counter) < 8)
+            .unwrap();
+        assert_invalid(&wrong, &alice, NOW);
+
+        // IP lain dan challenge yang expired
+        assert_invalid(&solution, &client("10.0.0.2"), NOW);
+        assert_invalid(&solution, &alice, challenge.expires_at);
+
+        // Secret lain
+        let other = ProofOfWork::new(&store, &policy, b"other", "registration");
+        assert!(other.verify(&solution, &alice, NOW).is_err());
+
+        // Replay
+        assert!(pow.verify(&solution, &alice, NOW).is_ok());
+        assert_invalid(&solution, &alice, NOW);
+    }
+}
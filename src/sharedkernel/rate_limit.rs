@@ -0,0 +1,524 @@
+use super::{client::ClientInfo, error, store::KeyValueStore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/*
+==============================================================================================================================
+Rate limiting
+==============================================================================================================================
+Membatasi jumlah request per endpoint (route). Setiap route dapat memiliki limit sendiri, dengan pilihan algoritma :
+- token_bucket : mengizinkan burst hingga `limit` request, lalu diisi ulang secara merata selama `window` detik.
+- sliding_window : memperkirakan jumlah request dalam `window` detik terakhir dari counter window saat ini dan sebelumnya.
+
+State limiter disimpan di KeyValueStore, sehingga dapat menggunakan MemoryStore (single instance) atau RedisStore
+(dibagi oleh beberapa instance). Hasil pemeriksaan dikembalikan sebagai RateLimitDecision yang berisi nilai header
+`RateLimit-*` dan `Retry-After` untuk response. Request yang ditolak menghasilkan error dengan header tersebut.
+
+Use case login dan registrasi memeriksa limit route masing-masing (LOGIN_ROUTE dan REGISTRATION_ROUTE), sedangkan
+use case yang membatasi subjek tertentu (misal nomor telepon tujuan SMS) menggunakan RateLimit::check secara langsung.
+*/
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    TokenBucket,
+    SlidingWindow,
+}
+
+/// Subjek yang dihitung oleh limiter
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    Ip,
+    /// Per account, atau per IP jika request belum terautentikasi
+    Account,
+    /// Seluruh request ke route dihitung bersama
+    Route,
+}
+
+/// Limit untuk satu route
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct RateLimit {
+    pub algorithm: RateLimitAlgorithm,
+    pub key: RateLimitKey,
+    pub limit: u32,
+    /// Dalam detik
+    pub window: u64,
+}
+
+/// Konfigurasi rate limit per route, dengan limit default untuk route yang tidak dikonfigurasi
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub default: Option<RateLimit>,
+    pub routes: HashMap<String, RateLimit>,
+}
+
+/// Hasil pemeriksaan rate limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Detik hingga kuota kembali penuh
+    pub reset: u64,
+    /// Detik hingga request berikutnya diizinkan, hanya jika request ditolak
+    pub retry_after: Option<u64>,
+}
+
+impl RateLimitDecision {
+    fn unlimited() -> RateLimitDecision {
+        RateLimitDecision {
+            allowed: true,
+            limit: 0,
+            remaining: 0,
+            reset: 0,
+            retry_after: None,
+        }
+    }
+
+    /// Header response yang sesuai dengan hasil pemeriksaan
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        if self.limit == 0 {
+            return Vec::new();
+        }
+
+        let mut headers = vec![
+            ("RateLimit-Limit", self.limit.to_string()),
+            ("RateLimit-Remaining", self.remaining.to_string()),
+            ("RateLimit-Reset", self.reset.to_string()),
+        ];
+        if let Some(retry_after) = self.retry_after {
+            headers.push(("Retry-After", retry_after.to_string()));
+        }
+        headers
+    }
+
+    /// Mengembalikan too_many_request_error beserta header rate limit jika request ditolak
+    pub fn ensure_allowed(&self) -> Result<(), error::ApplicationError<'static>> {
+        if self.allowed {
+            Ok(())
+        } else {
+            Err(error::too_many_request_error!().with_headers(self.headers()))
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TokenBucketState {
+    tokens: f64,
+    updated_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SlidingWindowState {
+    window: u64,
+    current: u32,
+    previous: u32,
+}
+
+// Jumlah percobaan ulang ketika state limiter diubah oleh request lain secara bersamaan
+const MAX_CONFLICTS: u32 = 16;
+
+// State baru beserta masa berlakunya, None jika state tidak perlu disimpan
+type Transition = Option<(String, u64)>;
+
+fn ceil(value: f64) -> u64 {
+    value.max(0.0).ceil() as u64
+}
+
+fn state<T: serde::de::DeserializeOwned>(value: Option<&str>) -> Option<T> {
+    value.and_then(|value| serde_json::from_str(value).ok())
+}
+
+fn transition<T: Serialize>(state: &T, ttl: u64) -> Transition {
+    Some((serde_json::to_string(state).unwrap_or_default(), ttl))
+}
+
+impl RateLimit {
+    /**
+    Menghitung request untuk key tertentu dan menentukan apakah request diizinkan. State disimpan
+    dengan compare-and-set, sehingga request paralel tidak dapat melewati limit. Request yang
+    ditolak tidak mengubah state.
+
+    # Arguments
+    * `store` - Tempat state limiter disimpan
+    * `key` - Key subjek yang dihitung, misal "ratelimit:POST /sessions:ip:10.0.0.1"
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn check(
+        &self,
+        store: &dyn KeyValueStore,
+        key: &str,
+        now: u64,
+    ) -> Result<RateLimitDecision, error::ApplicationError<'static>> {
+        if self.limit == 0 {
+            return Ok(RateLimitDecision::unlimited());
+        }
+
+        for _ in 0..MAX_CONFLICTS {
+            let current = store.get(key)?;
+            let (decision, next) = match self.algorithm {
+                RateLimitAlgorithm::TokenBucket => self.token_bucket(current.as_deref(), now),
+                RateLimitAlgorithm::SlidingWindow => self.sliding_window(current.as_deref(), now),
+            };
+            match next {
+                None => return Ok(decision),
+                Some((value, ttl)) => {
+                    if store.compare_and_set(key, current.as_deref(), &value, ttl)? {
+                        return Ok(decision);
+                    }
+                }
+            }
+        }
+
+        // Terlalu banyak request bersamaan pada key yang sama
+        Err(error::too_many_request_error!())
+    }
+
+    fn token_bucket(&self, current: Option<&str>, now: u64) -> (RateLimitDecision, Transition) {
+        let capacity = self.limit as f64;
+        let rate = capacity / self.window.max(1) as f64;
+        let mut state = state::<TokenBucketState>(current).unwrap_or(TokenBucketState {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.saturating_sub(state.updated_at) as f64;
+        state.tokens = (state.tokens + elapsed * rate).min(capacity);
+        state.updated_at = now;
+
+        let allowed = state.tokens >= 1.0;
+        if allowed {
+            state.tokens -= 1.0;
+        }
+        let reset = ceil((capacity - state.tokens) / rate);
+
+        let decision = RateLimitDecision {
+            allowed,
+            limit: self.limit,
+            remaining: state.tokens.floor() as u32,
+            reset,
+            retry_after: (!allowed).then(|| ceil((1.0 - state.tokens) / rate).max(1)),
+        };
+        let next = if allowed {
+            transition(&state, reset.max(1))
+        } else {
+            None
+        };
+        (decision, next)
+    }
+
+    fn sliding_window(&self, current: Option<&str>, now: u64) -> (RateLimitDecision, Transition) {
+        let size = self.window.max(1);
+        let window = now / size;
+        let mut state = state::<SlidingWindowState>(current).unwrap_or_default();
+
+        if state.window + 1 == window {
+            state.previous = state.current;
+            state.current = 0;
+        } else if state.window != window {
+            state.previous = 0;
+            state.current = 0;
+        }
+        state.window = window;
+
+        // Bobot window sebelumnya sesuai porsi yang masih berada dalam sliding window
+        let elapsed = now % size;
+        let weight = (size - elapsed) as f64 / size as f64;
+        let estimated = state.previous as f64 * weight + state.current as f64;
+        let limit = self.limit as f64;
+
+        let allowed = estimated + 1.0 <= limit;
+        if allowed {
+            state.current += 1;
+        }
+        let reset = size - elapsed;
+
+        let retry_after = if allowed {
+            None
+        } else if state.current as f64 + 1.0 > limit || state.previous == 0 {
+            Some(reset)
+        } else {
+            // Waktu hingga bobot window sebelumnya cukup berkurang untuk satu request lagi
+            let available = limit - 1.0 - state.current as f64;
+            let target = size as f64 - available * size as f64 / state.previous as f64;
+            Some(ceil(target - elapsed as f64).clamp(1, reset))
+        };
+
+        let decision = RateLimitDecision {
+            allowed,
+            limit: self.limit,
+            remaining: (limit - estimated - allowed as u32 as f64).max(0.0) as u32,
+            reset,
+            retry_after,
+        };
+        let next = if allowed {
+            transition(&state, reset + size)
+        } else {
+            None
+        };
+        (decision, next)
+    }
+}
+
+pub struct RateLimiter<'a> {
+    store: &'a dyn KeyValueStore,
+    config: &'a RateLimitConfig,
+}
+
+impl<'a> RateLimiter<'a> {
+    pub fn new(store: &'a dyn KeyValueStore, config: &'a RateLimitConfig) -> RateLimiter<'a> {
+        RateLimiter { store, config }
+    }
+
+    fn key(
+        route: &str,
+        rate_limit: &RateLimit,
+        client: &ClientInfo,
+        account_id: Option<&Uuid>,
+    ) -> String {
+        match (rate_limit.key, account_id) {
+            (RateLimitKey::Route, _) => format!("ratelimit:{}", route),
+            (RateLimitKey::Account, Some(account_id)) => {
+                format!("ratelimit:{}:account:{}", route, account_id)
+            }
+            _ => format!("ratelimit:{}:ip:{}", route, client.ip),
+        }
+    }
+
+    /**
+    Menghitung request dan menentukan apakah request diizinkan. Route tanpa limit (dan tanpa limit
+    default) selalu diizinkan.
+
+    # Arguments
+    * `route` - Nama route / endpoint, misal "POST /accounts"
+    * `client` - Informasi client yang melakukan request
+    * `account_id` - Id account jika request sudah terautentikasi
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn check(
+        &self,
+        route: &str,
+        client: &ClientInfo,
+        account_id: Option<&Uuid>,
+        now: u64,
+    ) -> Result<RateLimitDecision, error::ApplicationError<'static>> {
+        let rate_limit = match self
+            .config
+            .routes
+            .get(route)
+            .or(self.config.default.as_ref())
+        {
+            Some(rate_limit) => rate_limit,
+            None => return Ok(RateLimitDecision::unlimited()),
+        };
+
+        let key = Self::key(route, rate_limit, client, account_id);
+        rate_limit.check(self.store, &key, now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{config, store::MemoryStore};
+    use super::*;
+
+    const NOW: u64 = 1669969200;
+
+    fn client(ip: &str) -> ClientInfo {
+        ClientInfo::new(ip.parse().unwrap())
+    }
+
+    fn config() -> RateLimitConfig {
+        config::parse(
+            r#"{
+                "default": {"algorithm": "sliding_window", "key": "ip", "limit": 4, "window": 60},
+                "routes": {
+                    "POST /sessions": {"algorithm": "token_bucket", "key": "ip", "limit": 3, "window": 30},
+                    "GET /me": {"algorithm": "token_bucket", "key": "account", "limit": 1, "window": 10},
+                    "GET /health": {"algorithm": "token_bucket", "key": "route", "limit": 0, "window": 10}
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let store = MemoryStore::new();
+        let config = config();
+        let limiter = RateLimiter::new(&store, &config);
+        let alice = client("10.0.0.1");
+
+        for remaining in [2, 1, 0] {
+            let decision = limiter.check("POST /sessions", &alice, None, NOW).unwrap();
+            assert!(decision.ensure_allowed().is_ok());
+            assert_eq!(remaining, decision.remaining);
+        }
+
+        let decision = limiter.check("POST /sessions", &alice, None, NOW).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(Some(10), decision.retry_after);
+        assert_eq!(30, decision.reset);
+        let error = decision.ensure_allowed().unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!(decision.headers(), error.headers);
+
+        // Client lain memiliki bucket sendiri
+        let bob = client("10.0.0.2");
+        assert!(
+            limiter
+                .check("POST /sessions", &bob, None, NOW)
+                .unwrap()
+                .allowed
+        );
+
+        // Satu token diisi ulang setiap 10 detik
+        assert!(
+            limiter
+                .check("POST /sessions", &alice, None, NOW + 10)
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            !limiter
+                .check("POST /sessions", &alice, None, NOW + 10)
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let store = MemoryStore::new();
+        let config = config();
+        let limiter = RateLimiter::new(&store, &config);
+        let alice = client("10.0.0.1");
+
+        for _ in 0..4 {
+            assert!(
+                limiter
+                    .check("GET /accounts", &alice, None, NOW + 30)
+                    .unwrap()
+                    .allowed
+            );
+        }
+        let decision = limiter
+            .check("GET /accounts", &alice, None, NOW + 30)
+            .unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(Some(30), decision.retry_after);
+
+        // Separuh window berikutnya : 4 * 0.5 request masih dihitung dari window sebelumnya
+        let decision = limiter
+            .check("GET /accounts", &alice, None, NOW + 90)
+            .unwrap();
+        assert!(decision.allowed);
+        assert_eq!(1, decision.remaining);
+        assert!(
+            limiter
+                .check("GET /accounts", &alice, None, NOW + 90)
+                .unwrap()
+                .allowed
+        );
+        let decision = limiter
+            .check("GET /accounts", &alice, None, NOW + 90)
+            .unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(Some(15), decision.retry_after);
+    }
+
+    #[test]
+    fn test_account_key() {
+        let store = MemoryStore::new();
+        let config = config();
+        let limiter = RateLimiter::new(&store, &config);
+        let account_id = Uuid::new_v4();
+
+        // Account yang sama dari IP berbeda dihitung bersama
+        assert!(
+            limiter
+                .check("GET /me", &client("10.0.0.1"), Some(&account_id), NOW)
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            !limiter
+                .check("GET /me", &client("10.0.0.2"), Some(&account_id), NOW)
+                .unwrap()
+                .allowed
+        );
+        assert!(
+            limiter
+                .check("GET /me", &client("10.0.0.1"), Some(&Uuid::new_v4()), NOW)
+                .unwrap()
+                .allowed
+        );
+    }
+
+    #[test]
+    fn test_concurrent_requests() {
+        let store = MemoryStore::new();
+        let rate_limit = RateLimit {
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            key: RateLimitKey::Ip,
+            limit: 20,
+            window: 3600,
+        };
+
+        let allowed = std::sync::atomic::AtomicU32::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        if let Ok(decision) = rate_limit.check(&store, "ratelimit:test", NOW) {
+                            if decision.allowed {
+                                allowed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        // Request paralel tidak dapat melewati limit
+        assert_eq!(20, allowed.into_inner());
+    }
+
+    #[test]
+    fn test_headers() {
+        let store = MemoryStore::new();
+        let config = config();
+        let limiter = RateLimiter::new(&store, &config);
+        let alice = client("10.0.0.1");
+
+        assert!(limiter
+            .check("GET /health", &alice, None, NOW)
+            .unwrap()
+            .headers()
+            .is_empty());
+
+        let headers = limiter
+            .check("POST /sessions", &alice, None, NOW)
+            .unwrap()
+            .headers();
+        assert_eq!(
+            vec![
+                ("RateLimit-Limit", String::from("3")),
+                ("RateLimit-Remaining", String::from("2")),
+                ("RateLimit-Reset", String::from("10")),
+            ],
+            headers
+        );
+
+        limiter.check("POST /sessions", &alice, None, NOW).unwrap();
+        limiter.check("POST /sessions", &alice, None, NOW).unwrap();
+        let headers = limiter
+            .check("POST /sessions", &alice, None, NOW)
+            .unwrap()
+            .headers();
+        assert!(headers.contains(&("Retry-After", String::from("10"))));
+    }
+}
@@ -0,0 +1,75 @@
+use super::function::{from_hex, to_hex};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+/*
+Signature HMAC-SHA256 untuk data yang diberikan kepada client dan harus dapat diverifikasi kembali
+tanpa menyimpan state di server (misal challenge atau link yang ditandatangani).
+*/
+
+fn mac(secret: &[u8], message: &str) -> Hmac<Sha256> {
+    // HMAC menerima key dengan panjang berapapun
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).expect("HMAC accepts any key length");
+    mac.update(message.as_bytes());
+    mac
+}
+
+/**
+Mengembalikan signature (hex) dari message
+
+# Arguments
+* `secret` - Secret key milik server
+* `message` - Data yang ditandatangani
+*/
+pub fn sign(secret: &[u8], message: &str) -> String {
+    to_hex(&mac(secret, message).finalize().into_bytes())
+}
+
+/**
+Memverifikasi signature dari message dengan perbandingan constant time
+
+# Arguments
+* `secret` - Secret key milik server
+* `message` - Data yang ditandatangani
+* `signature` - Signature (hex) yang dikirim oleh client
+*/
+pub fn verify(secret: &[u8], message: &str, signature: &str) -> bool {
+    match from_hex(signature) {
+        Some(signature) => mac(secret, message).verify(&signature).is_ok(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        // RFC 4231 test case 2
+        let signature = sign(b"Jefe", "what do ya want for nothing?");
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            signature
+        );
+
+        assert!(verify(b"Jefe", "what do ya want for nothing?", &signature));
+        assert!(verify(
+            b"Jefe",
+            "what do ya want for nothing?",
+            &signature.to_uppercase()
+        ));
+        assert!(!verify(
+            b"Jefe",
+            "what do ya want for something?",
+            &signature
+        ));
+        assert!(!verify(
+            b"other",
+            "what do ya want for nothing?",
+            &signature
+        ));
+        assert!(!verify(b"Jefe", "what do ya want for nothing?", "zz"));
+        assert!(!verify(b"Jefe", "what do ya want for nothing?", "5bdc"));
+    }
+}
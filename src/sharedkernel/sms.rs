@@ -0,0 +1,138 @@
+use super::{error, function::get_now, phone::PhoneNumber};
+use mockall::*;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Cara pesan disampaikan ke user
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum SmsChannel {
+    #[default]
+    #[serde(rename = "sms")]
+    Sms,
+    /// Pesan dibacakan melalui panggilan telepon
+    #[serde(rename = "voice")]
+    Voice,
+}
+
+/// Merepresentasikan pesan yang akan dikirim ke nomor telepon user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmsMessage {
+    pub to: PhoneNumber,
+    pub channel: SmsChannel,
+    pub body: String,
+}
+
+impl SmsMessage {
+    /**
+    Mengembalikan message baru
+
+    # Arguments
+    * `to` - Nomor telepon penerima
+    * `channel` - SMS atau panggilan suara
+    * `body` - Isi pesan dalam plain text
+    */
+    pub fn new(to: &PhoneNumber, channel: SmsChannel, body: &str) -> SmsMessage {
+        SmsMessage {
+            to: to.clone(),
+            channel,
+            body: String::from(body),
+        }
+    }
+}
+
+#[automock]
+pub trait SmsProvider {
+    fn send(&self, message: &SmsMessage) -> Result<(), error::ApplicationError<'static>>;
+}
+
+/**
+SmsProvider untuk development dan testing lokal. Pesan tidak dikirim, melainkan ditambahkan ke file
+(satu pesan per baris, dipisahkan tab : timestamp, channel, nomor tujuan, isi pesan).
+*/
+pub struct FileSmsProvider {
+    path: PathBuf,
+}
+
+impl FileSmsProvider {
+    /**
+    Mengembalikan FileSmsProvider baru
+
+    # Arguments
+    * `path` - Lokasi file log, dibuat jika belum ada
+    */
+    pub fn new(path: &Path) -> FileSmsProvider {
+        FileSmsProvider {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl SmsProvider for FileSmsProvider {
+    fn send(&self, message: &SmsMessage) -> Result<(), error::ApplicationError<'static>> {
+        let channel = match message.channel {
+            SmsChannel::Sms => "sms",
+            SmsChannel::Voice => "voice",
+        };
+        let line = format!(
+            "{}\t{}\t{}\t{}\n",
+            get_now(),
+            channel,
+            message.to,
+            message.body.replace(['\t', '\n'], " ")
+        );
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|_| {
+                error::internal_server_error!(
+                    "It's not you. We are experiencing technical difficulties. Please try again later.",
+                    "sms_unavailable"
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_file_provider() {
+        let path = std::env::temp_dir().join(format!("brokoli-sms-{}.log", uuid::Uuid::new_v4()));
+        let provider = FileSmsProvider::new(&path);
+        let phone = PhoneNumber::from("+6281234567890");
+
+        provider
+            .send(&SmsMessage::new(
+                &phone,
+                SmsChannel::Sms,
+                "Your code is 123456",
+            ))
+            .unwrap();
+        provider
+            .send(&SmsMessage::new(
+                &phone,
+                SmsChannel::Voice,
+                "Your code is\n654321",
+            ))
+            .unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].ends_with("\tsms\t+6281234567890\tYour code is 123456"));
+        assert!(lines[1].ends_with("\tvoice\t+6281234567890\tYour code is 654321"));
+        fs::remove_file(&path).unwrap();
+
+        let provider = FileSmsProvider::new(Path::new("/nonexistent/brokoli/sms.log"));
+        let error = provider
+            .send(&SmsMessage::new(&phone, SmsChannel::Sms, "x"))
+            .unwrap_err();
+        assert_eq!("sms_unavailable", error.error);
+    }
+}
@@ -0,0 +1,375 @@
+use super::error;
+use super::function::get_now;
+use mockall::*;
+use redis::Commands;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/*
+==============================================================================================================================
+Key value store
+==============================================================================================================================
+Penyimpanan data berumur pendek (misal counter kegagalan login) yang dapat diganti implementasinya :
+- MemoryStore : disimpan di memory proses, cocok untuk development dan single instance.
+- RedisStore : disimpan di Redis, sehingga dapat digunakan bersama oleh beberapa instance.
+
+Counter dan token sekali pakai harus menggunakan operasi atomic (`incr_with_ttl`, `set_if_absent`, `take` dan
+`compare_and_set`), bukan `get` lalu `set` / `delete`, karena request paralel dapat membaca value yang sama sebelum
+salah satunya menulis.
+*/
+
+#[automock]
+pub trait KeyValueStore {
+    fn get(&self, key: &str) -> Result<Option<String>, error::ApplicationError<'static>>;
+    /// Menyimpan value dengan masa berlaku `ttl` detik
+    fn set(&self, key: &str, value: &str, ttl: u64)
+        -> Result<(), error::ApplicationError<'static>>;
+    fn delete(&self, key: &str) -> Result<(), error::ApplicationError<'static>>;
+    /// Menambah counter secara atomic dan memperbarui masa berlakunya menjadi `ttl` detik.
+    /// Mengembalikan nilai counter setelah ditambah, key yang belum ada dianggap bernilai 0.
+    fn incr_with_ttl(&self, key: &str, ttl: u64) -> Result<u64, error::ApplicationError<'static>>;
+    /// Menyimpan value hanya jika key belum ada. Mengembalikan false jika key sudah ada.
+    fn set_if_absent(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: u64,
+    ) -> Result<bool, error::ApplicationError<'static>>;
+    /// Mengambil lalu menghapus value secara atomic, sehingga hanya satu pemanggil yang mendapatkannya
+    fn take(&self, key: &str) -> Result<Option<String>, error::ApplicationError<'static>>;
+    /// Menyimpan value hanya jika value saat ini sama dengan `current` (None berarti key belum ada).
+    /// Mengembalikan false jika value sudah diubah oleh pemanggil lain.
+    // Lifetime eksplisit dibutuhkan oleh automock
+    #[allow(clippy::needless_lifetimes)]
+    fn compare_and_set<'a>(
+        &self,
+        key: &str,
+        current: Option<&'a str>,
+        value: &str,
+        ttl: u64,
+    ) -> Result<bool, error::ApplicationError<'static>>;
+}
+
+// Value beserta waktu expired-nya
+type Entries = HashMap<String, (String, u64)>;
+
+/// Key value store di dalam memory proses
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    fn entries(&self) -> Result<MutexGuard<'_, Entries>, error::ApplicationError<'static>> {
+        self.entries
+            .lock()
+            .map_err(|_| error::internal_server_error!())
+    }
+
+    /// Mengunci entries setelah membersihkan entry yang sudah expired
+    fn live_entries(&self) -> Result<MutexGuard<'_, Entries>, error::ApplicationError<'static>> {
+        let now = get_now();
+        let mut entries = self.entries()?;
+        // Membersihkan entry yang sudah expired agar memory tidak terus bertambah
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        Ok(entries)
+    }
+}
+
+impl KeyValueStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<String>, error::ApplicationError<'static>> {
+        let mut entries = self.entries()?;
+        match entries.get(key) {
+            Some((_, expires_at)) if *expires_at <= get_now() => {
+                entries.remove(key);
+                Ok(None)
+            }
+            Some((value, _)) => Ok(Some(value.clone())),
+            None => Ok(None),
+        }
+    }
+
+    fn set(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.live_entries()?
+            .insert(String::from(key), (String::from(value), get_now() + ttl));
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), error::ApplicationError<'static>> {
+        self.entries()?.remove(key);
+        Ok(())
+    }
+
+    fn incr_with_ttl(&self, key: &str, ttl: u64) -> Result<u64, error::ApplicationError<'static>> {
+        let mut entries = self.live_entries()?;
+        let count = match entries.get(key) {
+            Some((value, _)) => value
+                .parse::<u64>()
+                .map_err(|_| error::internal_server_error!())?,
+            None => 0,
+        } + 1;
+        entries.insert(String::from(key), (count.to_string(), get_now() + ttl));
+        Ok(count)
+    }
+
+    fn set_if_absent(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: u64,
+    ) -> Result<bool, error::ApplicationError<'static>> {
+        let mut entries = self.live_entries()?;
+        if entries.contains_key(key) {
+            return Ok(false);
+        }
+        entries.insert(String::from(key), (String::from(value), get_now() + ttl));
+        Ok(true)
+    }
+
+    fn take(&self, key: &str) -> Result<Option<String>, error::ApplicationError<'static>> {
+        Ok(self.live_entries()?.remove(key).map(|(value, _)| value))
+    }
+
+    fn compare_and_set(
+        &self,
+        key: &str,
+        current: Option<&str>,
+        value: &str,
+        ttl: u64,
+    ) -> Result<bool, error::ApplicationError<'static>> {
+        let mut entries = self.live_entries()?;
+        if entries.get(key).map(|(value, _)| value.as_str()) != current {
+            return Ok(false);
+        }
+        entries.insert(String::from(key), (String::from(value), get_now() + ttl));
+        Ok(true)
+    }
+}
+
+// Jumlah maksimal koneksi idle yang disimpan untuk digunakan kembali
+const MAX_IDLE_CONNECTIONS: usize = 8;
+
+// INCR tidak memperbarui masa berlaku, sehingga INCR dan EXPIRE dijalankan bersama dalam satu script
+const INCR_WITH_TTL: &str =
+    "local count = redis.call('INCR', KEYS[1]) redis.call('EXPIRE', KEYS[1], ARGV[1]) return count";
+
+// GETDEL baru tersedia sejak Redis 6.2
+const TAKE: &str =
+    "local value = redis.call('GET', KEYS[1]) if value then redis.call('DEL', KEYS[1]) end return value";
+
+// ARGV : ada tidaknya value saat ini ("1" / "0"), value saat ini, value baru, ttl
+const COMPARE_AND_SET: &str = "local value = redis.call('GET', KEYS[1]) \
+    if (ARGV[1] == '1' and value == ARGV[2]) or (ARGV[1] == '0' and not value) then \
+    redis.call('SET', KEYS[1], ARGV[3], 'EX', ARGV[4]) return 1 end return 0";
+
+/// Key value store yang disimpan di Redis
+pub struct RedisStore {
+    client: redis::Client,
+    idle: Mutex<Vec<redis::Connection>>,
+}
+
+// Redis tidak dapat dihubungi atau mengembalikan error
+fn store_unavailable(_: redis::RedisError) -> error::ApplicationError<'static> {
+    error::internal_server_error!(
+        "It's not you. We are experiencing technical difficulties. Please try again later.",
+        "store_unavailable"
+    )
+}
+
+impl RedisStore {
+    /**
+    Mengembalikan RedisStore. Koneksi dibuka ketika store pertama kali digunakan, lalu digunakan
+    kembali oleh operasi berikutnya.
+
+    # Arguments
+    * `url` - Redis connection url, misal redis://127.0.0.1:6379
+    */
+    pub fn open(url: &str) -> Result<RedisStore, error::ApplicationError<'static>> {
+        let client = redis::Client::open(url).map_err(store_unavailable)?;
+        Ok(RedisStore {
+            client,
+            idle: Mutex::new(Vec::new()),
+        })
+    }
+
+    /**
+    Menjalankan operasi dengan koneksi idle atau koneksi baru jika tidak ada. Koneksi dikembalikan
+    ke pool hanya jika operasi berhasil, koneksi yang error dibuang.
+
+    # Arguments
+    * `operation` - Operasi yang dijalankan terhadap koneksi
+    */
+    fn with_connection<T>(
+        &self,
+        operation: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+    ) -> Result<T, error::ApplicationError<'static>> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+        let mut connection = match idle {
+            Some(connection) => connection,
+            None => self.client.get_connection().map_err(store_unavailable)?,
+        };
+
+        let result = operation(&mut connection).map_err(store_unavailable)?;
+        if let Ok(mut idle) = self.idle.lock() {
+            if idle.len() < MAX_IDLE_CONNECTIONS {
+                idle.push(connection);
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl KeyValueStore for RedisStore {
+    fn get(&self, key: &str) -> Result<Option<String>, error::ApplicationError<'static>> {
+        self.with_connection(|connection| connection.get(key))
+    }
+
+    fn set(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.with_connection(|connection| connection.set_ex(key, value, ttl.max(1) as usize))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), error::ApplicationError<'static>> {
+        self.with_connection(|connection| connection.del(key))
+    }
+
+    fn incr_with_ttl(&self, key: &str, ttl: u64) -> Result<u64, error::ApplicationError<'static>> {
+        self.with_connection(|connection| {
+            redis::cmd("EVAL")
+                .arg(INCR_WITH_TTL)
+                .arg(1)
+                .arg(key)
+                .arg(ttl.max(1))
+                .query(connection)
+        })
+    }
+
+    fn set_if_absent(
+        &self,
+        key: &str,
+        value: &str,
+        ttl: u64,
+    ) -> Result<bool, error::ApplicationError<'static>> {
+        let reply: Option<String> = self.with_connection(|connection| {
+            redis::cmd("SET")
+                .arg(key)
+                .arg(value)
+                .arg("NX")
+                .arg("EX")
+                .arg(ttl.max(1))
+                .query(connection)
+        })?;
+        Ok(reply.is_some())
+    }
+
+    fn take(&self, key: &str) -> Result<Option<String>, error::ApplicationError<'static>> {
+        self.with_connection(|connection| {
+            redis::cmd("EVAL")
+                .arg(TAKE)
+                .arg(1)
+                .arg(key)
+                .query(connection)
+        })
+    }
+
+    fn compare_and_set(
+        &self,
+        key: &str,
+        current: Option<&str>,
+        value: &str,
+        ttl: u64,
+    ) -> Result<bool, error::ApplicationError<'static>> {
+        let exists = if current.is_some() { "1" } else { "0" };
+        self.with_connection(|connection| {
+            redis::cmd("EVAL")
+                .arg(COMPARE_AND_SET)
+                .arg(1)
+                .arg(key)
+                .arg(exists)
+                .arg(current.unwrap_or_default())
+                .arg(value)
+                .arg(ttl.max(1))
+                .query(connection)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new();
+        assert_eq!(None, store.get("key").unwrap());
+
+        store.set("key", "value", 60).unwrap();
+        assert_eq!(Some(String::from("value")), store.get("key").unwrap());
+
+        store.delete("key").unwrap();
+        assert_eq!(None, store.get("key").unwrap());
+    }
+
+    #[test]
+    fn test_memory_store_expiry() {
+        let store = MemoryStore::new();
+        store.set("key", "value", 0).unwrap();
+        assert_eq!(None, store.get("key").unwrap());
+    }
+
+    #[test]
+    fn test_memory_store_atomic_operations() {
+        let store = MemoryStore::new();
+        assert_eq!(1, store.incr_with_ttl("counter", 60).unwrap());
+        assert_eq!(2, store.incr_with_ttl("counter", 60).unwrap());
+        assert_eq!(Some(String::from("2")), store.get("counter").unwrap());
+
+        assert!(store.set_if_absent("nonce", "1", 60).unwrap());
+        assert!(!store.set_if_absent("nonce", "2", 60).unwrap());
+        assert_eq!(Some(String::from("1")), store.get("nonce").unwrap());
+
+        assert_eq!(Some(String::from("1")), store.take("nonce").unwrap());
+        assert_eq!(None, store.take("nonce").unwrap());
+        assert_eq!(None, store.get("nonce").unwrap());
+
+        assert!(!store.compare_and_set("state", Some("1"), "2", 60).unwrap());
+        assert!(store.compare_and_set("state", None, "1", 60).unwrap());
+        assert!(!store.compare_and_set("state", None, "2", 60).unwrap());
+        assert!(store.compare_and_set("state", Some("1"), "2", 60).unwrap());
+        assert_eq!(Some(String::from("2")), store.get("state").unwrap());
+    }
+
+    #[test]
+    fn test_memory_store_concurrent_incr() {
+        let store = MemoryStore::new();
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        store.incr_with_ttl("counter", 60).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(Some(String::from("800")), store.get("counter").unwrap());
+    }
+
+    #[test]
+    fn test_redis_store_with_invalid_url() {
+        let error = RedisStore::open("not-a-redis-url").err().unwrap();
+        assert_eq!("store_unavailable", error.error);
+    }
+}
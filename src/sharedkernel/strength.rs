@@ -0,0 +1,831 @@
+use super::email::Email;
+use super::function::get_now;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/*
+==============================================================================================================================
+Password strength estimation
+==============================================================================================================================
+Estimasi jumlah tebakan yang dibutuhkan untuk menebak sebuah password, mengikuti pendekatan zxcvbn :
+- Password dipecah menjadi pattern yang dikenali : dictionary (termasuk reversed dan l33t), user input (misal email),
+  keyboard walk, sequence, repeat dan date.
+- Dipilih kombinasi pattern yang menghasilkan jumlah tebakan paling kecil. Bagian yang tidak dikenali dihitung sebagai
+  bruteforce.
+- Jumlah tebakan dikonversi menjadi score 0 - 4 beserta feedback yang dapat ditampilkan sebagai strength meter.
+*/
+
+// Password umum, diurutkan berdasarkan frekuensi
+#[rustfmt::skip]
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "password", "12345678", "qwerty", "123456789", "12345", "1234", "111111", "1234567",
+    "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein", "696969", "shadow",
+    "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890", "michael", "654321",
+    "superman", "1qaz2wsx", "7777777", "121212", "000000", "qazwsx", "123qwe", "killer", "trustno1",
+    "jordan", "jennifer", "zxcvbnm", "asdfgh", "hunter", "buster", "soccer", "harley", "batman",
+    "andrew", "tigger", "sunshine", "iloveyou", "2000", "charlie", "robert", "thomas", "hockey",
+    "ranger", "daniel", "starwars", "klaster", "112233", "george", "computer", "michelle",
+    "jessica", "pepper", "1111", "zxcvbn", "555555", "11111111", "131313", "freedom", "777777",
+    "pass", "maggie", "159753", "aaaaaa", "ginger", "princess", "joshua", "cheese", "amanda",
+    "summer", "love", "ashley", "nicole", "chelsea", "biteme", "matthew", "access", "yankees",
+    "987654321", "dallas", "austin", "thunder", "taylor", "matrix", "admin", "welcome", "login",
+    "passw0rd",
+];
+
+// Kata umum (English dan Bahasa Indonesia), diurutkan berdasarkan frekuensi
+#[rustfmt::skip]
+const COMMON_WORDS: &[&str] = &[
+    "the", "you", "and", "love", "secret", "hello", "house", "world", "money", "family", "happy",
+    "life", "music", "angel", "friend", "summer", "winter", "spring", "horse", "battery", "staple",
+    "correct", "dog", "cat", "sun", "moon", "star", "king", "queen", "sayang", "cinta", "rahasia",
+    "sandi", "kata", "indonesia", "jakarta", "bandung", "surabaya", "bismillah", "merdeka",
+    "garuda", "bunga", "rindu", "kasih", "hati", "aku", "kamu", "dia", "rumah", "kucing", "anjing",
+    "bola", "sepak", "bintang", "bulan", "matahari", "brokoli",
+];
+
+// Baris keyboard qwerty (unshifted, shifted)
+const QWERTY: &[(&str, &str)] = &[
+    ("`1234567890-=", "~!@#$%^&*()_+"),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+    ("asdfghjkl;'", "ASDFGHJKL:\""),
+    ("zxcvbnm,./", "ZXCVBNM<>?"),
+];
+
+// Jumlah posisi awal dan rata-rata jumlah tetangga pada keyboard qwerty
+const KEYBOARD_STARTING_POSITIONS: f64 = 94.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.6;
+
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+// Panjang maksimal yang diestimasi, sama dengan zxcvbn. Kompleksitas estimasi kubik terhadap
+// panjang password, sehingga karakter setelahnya diabaikan agar request tidak dapat menahan worker.
+const MAX_ESTIMATED_LENGTH: usize = 100;
+
+/// Hasil estimasi kekuatan password
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Strength {
+    pub score: u8,
+    pub guesses_log10: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    Dictionary {
+        user_input: bool,
+        common_password: bool,
+        rank: usize,
+        reversed: bool,
+        l33t: bool,
+        uppercase: bool,
+    },
+    Spatial {
+        turns: usize,
+    },
+    Sequence,
+    Repeat,
+    Date,
+    Bruteforce,
+}
+
+#[derive(Debug, Clone)]
+struct Match {
+    i: usize,
+    j: usize,
+    pattern: Pattern,
+    guesses: f64,
+}
+
+/**
+Mengembalikan user input yang tidak boleh digunakan di dalam password berdasarkan email :
+local part (utuh dan per segmen) serta label domain selain top-level domain.
+
+# Arguments
+* `email` - Reference dari object Email milik user
+*/
+pub fn email_inputs(email: &Email) -> Vec<String> {
+    let mut inputs: Vec<String> = Vec::new();
+    let local_part = email.local_part().to_lowercase();
+
+    inputs.push(local_part.clone());
+    inputs.extend(
+        local_part
+            .split(|c: char| !c.is_alphanumeric())
+            .map(String::from),
+    );
+
+    let labels: Vec<&str> = email.domain().split('.').collect();
+    if labels.len() > 1 {
+        inputs.extend(labels[..labels.len() - 1].iter().map(|l| l.to_lowercase()));
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for input in inputs {
+        if input.chars().count() >= 3 && !unique.contains(&input) {
+            unique.push(input);
+        }
+    }
+    unique
+}
+
+/**
+Mengestimasi kekuatan password. Hanya `MAX_ESTIMATED_LENGTH` karakter pertama yang diestimasi.
+
+# Arguments
+* `password` - Teks password
+* `user_inputs` - Teks yang berhubungan dengan user (misal hasil `email_inputs`) yang dianggap mudah ditebak
+*/
+pub fn estimate(password: &str, user_inputs: &[String]) -> Strength {
+    let chars: Vec<char> = password.chars().take(MAX_ESTIMATED_LENGTH).collect();
+    if chars.is_empty() {
+        return Strength {
+            score: 0,
+            guesses_log10: 0.0,
+            warning: None,
+            suggestions: vec![String::from(
+                "Use a few words, avoid common phrases. No need for symbols, digits, or uppercase letters.",
+            )],
+        };
+    }
+
+    let dictionaries = dictionaries(user_inputs);
+    let (guesses_log10, sequence) = most_guessable_sequence(&chars, &dictionaries);
+    let score = score(guesses_log10);
+    let (warning, suggestions) = feedback(score, &sequence, chars.len());
+
+    Strength {
+        score,
+        guesses_log10: (guesses_log10 * 100.0).round() / 100.0,
+        warning,
+        suggestions,
+    }
+}
+
+// Kamus kata beserta rank dan sumbernya (user_input, common_password)
+type Dictionaries = HashMap<String, (usize, bool, bool)>;
+
+fn dictionaries(user_inputs: &[String]) -> Dictionaries {
+    let mut dictionaries: Dictionaries = HashMap::new();
+    for (rank, word) in COMMON_WORDS.iter().enumerate() {
+        dictionaries.insert(word.to_string(), (rank + 1, false, false));
+    }
+    for (rank, word) in COMMON_PASSWORDS.iter().enumerate() {
+        dictionaries.insert(word.to_string(), (rank + 1, false, true));
+    }
+    for (rank, word) in user_inputs.iter().enumerate() {
+        dictionaries.insert(word.to_lowercase(), (rank + 1, true, false));
+    }
+    dictionaries
+}
+
+// Konversi log10 jumlah tebakan menjadi score 0 - 4
+fn score(guesses_log10: f64) -> u8 {
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/*
+Mencari kombinasi match dengan jumlah tebakan paling kecil. Jumlah tebakan dari kombinasi l match adalah
+l! * (perkalian tebakan tiap match) + 10000^(l - 1), sama dengan zxcvbn.
+*/
+fn most_guessable_sequence(chars: &[char], dictionaries: &Dictionaries) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    let matches = omnimatch(chars, dictionaries);
+
+    // best[k][l] : log10 perkalian tebakan terkecil untuk prefix sepanjang k yang terdiri dari l match
+    let mut best: Vec<Vec<Option<(f64, Match)>>> = vec![vec![None; n + 1]; n + 1];
+    let mut starts: Vec<Vec<(f64, usize)>> = vec![Vec::new(); n + 1];
+    starts[0].push((0.0, 0));
+
+    for k in 1..=n {
+        let mut candidates: Vec<Match> = matches.iter().filter(|m| m.j + 1 == k).cloned().collect();
+        for i in 0..k {
+            candidates.push(Match {
+                i,
+                j: k - 1,
+                pattern: Pattern::Bruteforce,
+                guesses: 10f64.powi((k - i) as i32),
+            });
+        }
+
+        for candidate in candidates {
+            let guesses = if candidate.j - candidate.i + 1 == n {
+                candidate.guesses
+            } else if candidate.i == candidate.j {
+                candidate.guesses.max(MIN_SUBMATCH_GUESSES_SINGLE_CHAR)
+            } else {
+                candidate.guesses.max(MIN_SUBMATCH_GUESSES_MULTI_CHAR)
+            };
+
+            for (previous, count) in starts[candidate.i].clone() {
+                let total = previous + guesses.log10();
+                let slot = &mut best[k][count + 1];
+                if slot.as_ref().is_none_or(|(current, _)| total < *current) {
+                    *slot = Some((total, candidate.clone()));
+                }
+            }
+        }
+
+        for (count, slot) in best[k].iter().enumerate() {
+            if let Some((total, _)) = slot {
+                starts[k].push((*total, count));
+            }
+        }
+    }
+
+    let mut optimal: Option<(f64, usize)> = None;
+    for (count, slot) in best[n].iter().enumerate() {
+        if let Some((product, _)) = slot {
+            let factorial: f64 = (1..=count).map(|c| (c as f64).log10()).sum();
+            let total = log10_sum(product + factorial, 4.0 * (count as f64 - 1.0));
+            if optimal.is_none_or(|(current, _)| total < current) {
+                optimal = Some((total, count));
+            }
+        }
+    }
+
+    let (guesses_log10, mut count) = optimal.unwrap_or((0.0, 0));
+    let mut sequence: Vec<Match> = Vec::new();
+    let mut k = n;
+    while count > 0 {
+        let (_, m) = best[k][count].clone().unwrap();
+        k = m.i;
+        count -= 1;
+        sequence.insert(0, m);
+    }
+
+    (guesses_log10, sequence)
+}
+
+// log10(10^a + 10^b)
+fn log10_sum(a: f64, b: f64) -> f64 {
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    high + (1.0 + 10f64.powf(low - high)).log10()
+}
+
+fn omnimatch(chars: &[char], dictionaries: &Dictionaries) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    matches.extend(dictionary_matches(chars, dictionaries));
+    matches.extend(spatial_matches(chars));
+    matches.extend(sequence_matches(chars));
+    matches.extend(repeat_matches(chars, dictionaries));
+    matches.extend(date_matches(chars));
+    matches
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn unl33t(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '8' => 'b',
+        '(' | '{' | '[' | '<' => 'c',
+        '3' => 'e',
+        '6' | '9' => 'g',
+        '1' | '!' | '|' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '+' | '7' => 't',
+        '%' => 'x',
+        '2' => 'z',
+        _ => c,
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (1..=k).fold(1.0, |acc, i| acc * (n - k + i) as f64 / i as f64)
+}
+
+// Jumlah variasi huruf besar yang mungkin, misal "Password" lebih mudah ditebak dibanding "pAsSWoRd"
+fn uppercase_variations(chars: &[char]) -> f64 {
+    let upper = chars.iter().filter(|c| c.is_uppercase()).count();
+    let lower = chars.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_upper = chars.first().is_some_and(|c| c.is_uppercase()) && upper == 1;
+    let last_upper = chars.last().is_some_and(|c| c.is_uppercase()) && upper == 1;
+    if lower == 0 || first_upper || last_upper {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|i| binomial(upper + lower, i))
+        .sum()
+}
+
+fn dictionary_matches(chars: &[char], dictionaries: &Dictionaries) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let n = chars.len();
+
+    for i in 0..n {
+        for j in (i + 2)..n {
+            let token = &chars[i..=j];
+            let lowered: String = token.iter().map(|c| lowercase(*c)).collect();
+            let reversed: String = lowered.chars().rev().collect();
+            let substituted: String = token.iter().map(|c| unl33t(lowercase(*c))).collect();
+
+            let candidates = [
+                (lowered.clone(), false, false),
+                (reversed, true, false),
+                (substituted, false, true),
+            ];
+            for (word, is_reversed, is_l33t) in candidates {
+                if (is_reversed || is_l33t) && word == lowered {
+                    continue;
+                }
+                if let Some((rank, user_input, common_password)) = dictionaries.get(&word) {
+                    let variations = uppercase_variations(token);
+                    let mut guesses = *rank as f64 * variations;
+                    if is_reversed {
+                        guesses *= 2.0;
+                    }
+                    if is_l33t {
+                        guesses *= 2.0;
+                    }
+                    matches.push(Match {
+                        i,
+                        j,
+                        pattern: Pattern::Dictionary {
+                            user_input: *user_input,
+                            common_password: *common_password,
+                            rank: *rank,
+                            reversed: is_reversed,
+                            l33t: is_l33t,
+                            uppercase: variations > 1.0,
+                        },
+                        guesses,
+                    });
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+// Posisi karakter pada keyboard qwerty : (baris, kolom, shifted)
+fn keyboard_position(c: char) -> Option<(i32, i32, bool)> {
+    for (row, (unshifted, shifted)) in QWERTY.iter().enumerate() {
+        if let Some(col) = unshifted.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, false));
+        }
+        if let Some(col) = shifted.chars().position(|k| k == c) {
+            return Some((row as i32, col as i32, true));
+        }
+    }
+    None
+}
+
+// Arah perpindahan antar tombol yang bersebelahan, None jika tidak bersebelahan
+fn keyboard_direction(a: (i32, i32, bool), b: (i32, i32, bool)) -> Option<(i32, i32)> {
+    let direction = (b.0 - a.0, b.1 - a.1);
+    match direction {
+        (0, -1) | (0, 1) | (-1, 0) | (-1, 1) | (1, -1) | (1, 0) => Some(direction),
+        _ => None,
+    }
+}
+
+fn spatial_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let n = chars.len();
+    let mut i = 0;
+
+    while i + 1 < n {
+        let mut j = i;
+        let mut turns = 0;
+        let mut shifted = usize::from(keyboard_position(chars[i]).is_some_and(|p| p.2));
+        let mut last_direction: Option<(i32, i32)> = None;
+
+        while j + 1 < n {
+            let direction = match (keyboard_position(chars[j]), keyboard_position(chars[j + 1])) {
+                (Some(a), Some(b)) => keyboard_direction(a, b),
+                _ => None,
+            };
+            match direction {
+                Some(direction) => {
+                    if last_direction != Some(direction) {
+                        turns += 1;
+                        last_direction = Some(direction);
+                    }
+                    if keyboard_position(chars[j + 1]).is_some_and(|p| p.2) {
+                        shifted += 1;
+                    }
+                    j += 1;
+                }
+                None => break,
+            }
+        }
+
+        if j - i + 1 >= 3 {
+            let length = j - i + 1;
+            let mut guesses = 0.0;
+            for l in 2..=length {
+                for t in 1..=turns.min(l - 1) {
+                    guesses += binomial(l - 1, t - 1)
+                        * KEYBOARD_STARTING_POSITIONS
+                        * KEYBOARD_AVERAGE_DEGREE.powi(t as i32);
+                }
+            }
+            let unshifted = length - shifted;
+            if shifted > 0 {
+                guesses *= if unshifted == 0 {
+                    2.0
+                } else {
+                    (1..=shifted.min(unshifted))
+                        .map(|s| binomial(length, s))
+                        .sum()
+                };
+            }
+            matches.push(Match {
+                i,
+                j,
+                pattern: Pattern::Spatial { turns },
+                guesses,
+            });
+        }
+
+        i = j.max(i + 1);
+    }
+
+    matches
+}
+
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let lowered: Vec<char> = chars.iter().map(|c| lowercase(*c)).collect();
+    let n = lowered.len();
+    let mut i = 0;
+
+    while i + 1 < n {
+        let delta = lowered[i + 1] as i64 - lowered[i] as i64;
+        if delta.abs() != 1 {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j + 1 < n && lowered[j + 1] as i64 - lowered[j] as i64 == delta {
+            j += 1;
+        }
+
+        if j - i + 1 >= 3 {
+            let first = lowered[i];
+            let mut base = if matches!(first, 'a' | 'z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            matches.push(Match {
+                i,
+                j,
+                pattern: Pattern::Sequence,
+                guesses: base * (j - i + 1) as f64,
+            });
+        }
+
+        i = j;
+    }
+
+    matches
+}
+
+fn repeat_matches(chars: &[char], dictionaries: &Dictionaries) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    // Jumlah tebakan setiap block cukup dihitung sekali walaupun block berulang di banyak posisi
+    let mut block_guesses: HashMap<&[char], f64> = HashMap::new();
+    let n = chars.len();
+
+    for i in 0..n {
+        for block in 1..=((n - i) / 2) {
+            let mut count = 1;
+            while i + block * (count + 1) <= n
+                && chars[i..i + block] == chars[i + block * count..i + block * (count + 1)]
+            {
+                count += 1;
+            }
+            if count < 2 || (block == 1 && count < 3) {
+                continue;
+            }
+
+            let base = if block == 1 {
+                MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+            } else {
+                *block_guesses
+                    .entry(&chars[i..i + block])
+                    .or_insert_with_key(|block| {
+                        10f64.powf(most_guessable_sequence(block, dictionaries).0)
+                    })
+            };
+            matches.push(Match {
+                i,
+                j: i + block * count - 1,
+                pattern: Pattern::Repeat,
+                guesses: base * count as f64,
+            });
+        }
+    }
+
+    matches
+}
+
+fn current_year() -> i64 {
+    1970 + (get_now() / 31_556_952) as i64
+}
+
+// Jumlah tebakan untuk tahun atau tanggal lengkap dengan tahun tertentu
+fn date_guesses(year: i64, year_only: bool, separator: bool) -> f64 {
+    let year_space = ((year - current_year()).abs() as f64).max(MIN_YEAR_SPACE);
+    if year_only {
+        return year_space;
+    }
+    let guesses = year_space * 365.0;
+    if separator {
+        guesses * 4.0
+    } else {
+        guesses
+    }
+}
+
+fn expand_year(year: i64, digits: usize) -> Option<i64> {
+    match (digits, year) {
+        (2, y) if y > 50 => Some(1900 + y),
+        (2, y) => Some(2000 + y),
+        (4, y) if (1900..=2099).contains(&y) => Some(y),
+        _ => None,
+    }
+}
+
+// Memeriksa apakah tiga komponen (sesuai urutan yang dicoba) membentuk tanggal yang valid
+fn valid_date(parts: &[(i64, usize)]) -> Option<i64> {
+    let orders: [[usize; 3]; 3] = [[0, 1, 2], [1, 0, 2], [2, 1, 0]];
+    for order in orders {
+        let (day, month, (year, digits)) = (parts[order[0]].0, parts[order[1]].0, parts[order[2]]);
+        if (1..=31).contains(&day) && (1..=12).contains(&month) {
+            if let Some(year) = expand_year(year, digits) {
+                return Some(year);
+            }
+        }
+    }
+    None
+}
+
+fn date_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let n = chars.len();
+
+    for i in 0..n {
+        for j in (i + 3)..n.min(i + 10) {
+            let token = &chars[i..=j];
+            let text: String = token.iter().collect();
+            let separators: Vec<char> = token
+                .iter()
+                .filter(|c| !c.is_ascii_digit())
+                .cloned()
+                .collect();
+
+            let year = if separators.is_empty() {
+                let digits: Vec<i64> = token.iter().map(|c| *c as i64 - '0' as i64).collect();
+                let number =
+                    |from: usize, to: usize| digits[from..to].iter().fold(0, |acc, d| acc * 10 + d);
+                match digits.len() {
+                    4 => expand_year(number(0, 4), 4),
+                    6 => valid_date(&[(number(0, 2), 2), (number(2, 4), 2), (number(4, 6), 2)]),
+                    8 => valid_date(&[(number(0, 2), 2), (number(2, 4), 2), (number(4, 8), 4)])
+                        .or_else(|| {
+                            valid_date(&[(number(6, 8), 2), (number(4, 6), 2), (number(0, 4), 4)])
+                        }),
+                    _ => None,
+                }
+            } else if separators.len() == 2
+                && separators[0] == separators[1]
+                && matches!(separators[0], '-' | '/' | '.' | '_' | ' ')
+            {
+                let parts: Vec<&str> = text.split(separators[0]).collect();
+                let parsed: Vec<(i64, usize)> = parts
+                    .iter()
+                    .filter_map(|p| p.parse::<i64>().ok().map(|v| (v, p.len())))
+                    .collect();
+                if parsed.len() == 3 && parts.iter().all(|p| !p.is_empty() && p.len() <= 4) {
+                    valid_date(&parsed)
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+            if let Some(year) = year {
+                matches.push(Match {
+                    i,
+                    j,
+                    pattern: Pattern::Date,
+                    guesses: date_guesses(
+                        year,
+                        token.len() == 4 && separators.is_empty(),
+                        !separators.is_empty(),
+                    ),
+                });
+            }
+        }
+    }
+
+    matches
+}
+
+// Membentuk warning dan suggestion berdasarkan match terpanjang pada sequence
+fn feedback(score: u8, sequence: &[Match], length: usize) -> (Option<String>, Vec<String>) {
+    if score > 2 {
+        return (None, Vec::new());
+    }
+
+    let mut suggestions: Vec<String> = vec![String::from(
+        "Add another word or two. Uncommon words are better.",
+    )];
+    let longest = sequence
+        .iter()
+        .filter(|m| m.pattern != Pattern::Bruteforce)
+        .max_by_key(|m| m.j - m.i);
+
+    let warning = match longest.map(|m| &m.pattern) {
+        Some(Pattern::Dictionary {
+            user_input,
+            common_password,
+            rank,
+            reversed,
+            l33t,
+            uppercase,
+        }) => {
+            if *uppercase {
+                suggestions.push(String::from("Capitalization doesn't help very much."));
+            }
+            if *reversed {
+                suggestions.push(String::from("Reversed words aren't much harder to guess."));
+            }
+            if *l33t {
+                suggestions.push(String::from(
+                    "Predictable substitutions like '@' instead of 'a' don't help very much.",
+                ));
+            }
+            if *user_input {
+                Some("Avoid using parts of your email address in the password.")
+            } else if *common_password && *rank <= 10 && sequence.len() == 1 {
+                Some("This is a top-10 common password.")
+            } else if *common_password {
+                Some("This is similar to a commonly used password.")
+            } else if sequence.len() == 1 {
+                Some("A word by itself is easy to guess.")
+            } else {
+                None
+            }
+        }
+        Some(Pattern::Spatial { turns }) => {
+            suggestions.push(String::from(
+                "Use a longer keyboard pattern with more turns.",
+            ));
+            if *turns == 1 {
+                Some("Straight rows of keys are easy to guess.")
+            } else {
+                Some("Short keyboard patterns are easy to guess.")
+            }
+        }
+        Some(Pattern::Sequence) => {
+            suggestions.push(String::from("Avoid sequences."));
+            Some("Sequences like abc or 6543 are easy to guess.")
+        }
+        Some(Pattern::Repeat) => {
+            suggestions.push(String::from("Avoid repeated words and characters."));
+            Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\".")
+        }
+        Some(Pattern::Date) => {
+            suggestions.push(String::from(
+                "Avoid dates and years that are associated with you.",
+            ));
+            Some("Dates are often easy to guess.")
+        }
+        _ if length < 10 => Some("Short passwords are easy to guess."),
+        _ => None,
+    };
+
+    (warning.map(String::from), suggestions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn inputs() -> Vec<String> {
+        email_inputs(&Email::from("harun.gurubudi@digitalsekuriti.id"))
+    }
+
+    macro_rules! score_test_cases {
+        (
+            $(
+                ($test_name: ident, $passw: expr, $min: expr, $max: expr)
+            ),*
+        ) => {
+            $(
+                #[test]
+                fn $test_name() {
+                    let strength = estimate($passw, &inputs());
+                    assert!(
+                        ($min..=$max).contains(&strength.score),
+                        "{} scored {:?}",
+                        $passw,
+                        strength
+                    );
+                }
+            )*
+        };
+    }
+
+    score_test_cases! {
+        (common_password_test, "password", 0, 0),
+        (common_password_with_composition_test, "Password1!", 0, 1),
+        (l33t_password_test, "P@ssw0rd", 0, 1),
+        (keyboard_walk_test, "qwertyuiop", 0, 0),
+        (spatial_pattern_test, "zxcvfr4321", 0, 2),
+        (sequence_test, "abcdefgh", 0, 0),
+        (repeat_test, "aaaaaaaaaa", 0, 0),
+        (date_test, "17081945", 0, 1),
+        (email_local_part_test, "HarunGurubudi", 0, 2),
+        (email_domain_test, "digitalsekuriti2024", 0, 2),
+        (random_password_test, "rT9#kq!Zp2vL", 3, 4),
+        (passphrase_test, "correct horse battery staple sapi", 3, 4)
+    }
+
+    #[test]
+    fn test_email_inputs() {
+        assert_eq!(
+            vec!["harun.gurubudi", "harun", "gurubudi", "digitalsekuriti"],
+            inputs()
+        );
+    }
+
+    #[test]
+    fn test_user_input_penalty() {
+        let with_context = estimate("Gurubudi#2021", &inputs());
+        let without_context = estimate("Gurubudi#2021", &[]);
+        assert!(with_context.guesses_log10 < without_context.guesses_log10);
+        assert_eq!(
+            Some(String::from(
+                "Avoid using parts of your email address in the password."
+            )),
+            with_context.warning
+        );
+    }
+
+    #[test]
+    fn test_feedback() {
+        let strength = estimate("password", &[]);
+        assert_eq!(
+            Some(String::from("This is a top-10 common password.")),
+            strength.warning
+        );
+        assert!(!strength.suggestions.is_empty());
+
+        let strength = estimate("rT9#kq!Zp2vL", &[]);
+        assert_eq!(None, strength.warning);
+        assert!(strength.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_long_password_returns_quickly() {
+        let passw: String = "aB3$xY7!".repeat(1250);
+
+        let started = std::time::Instant::now();
+        let strength = estimate(&passw, &inputs());
+        assert!(started.elapsed().as_secs() < 1);
+        assert_eq!(4, strength.score);
+
+        // Block berulang dihitung sekali walaupun muncul di banyak posisi
+        let started = std::time::Instant::now();
+        estimate(&"abcdefghij".repeat(10), &[]);
+        assert!(started.elapsed().as_secs() < 1);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let serialized = serde_json::to_string(&estimate("password", &[])).unwrap();
+        assert!(serialized.starts_with("{\"score\":0,\"guesses_log10\":"));
+        assert!(serialized.contains("\"warning\":\"This is a top-10 common password.\""));
+    }
+}
@@ -0,0 +1,111 @@
+use super::function::to_hex;
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use zeroize::Zeroize;
+
+// Jumlah random bytes pada token (256 bit)
+const TOKEN_BYTES: usize = 32;
+
+/**
+Merepresentasikan secret token yang dikirim ke user, misal token reset password. Token hanya
+disimpan dalam bentuk digest (lihat `digest`), sehingga kebocoran storage tidak membocorkan token.
+Seperti Password, Debug menampilkan `[REDACTED]`, memory di-zeroize saat object di-drop, dan teks
+token hanya dapat diakses melalui `expose_secret`. Berbeda dengan Password, teks token tidak
+dinormalisasi, sehingga juga digunakan untuk credential layanan lain (misal password SMTP) yang
+harus dikirim apa adanya.
+*/
+#[derive(PartialEq, Eq)]
+pub struct Token {
+    value: String,
+}
+
+impl Token {
+    /// Mengembalikan token baru dari cryptographically secure random generator
+    pub fn generate() -> Token {
+        let mut bytes = [0u8; TOKEN_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let token = Token {
+            value: to_hex(&bytes),
+        };
+        bytes.zeroize();
+        token
+    }
+
+    /**
+    Mengembalikan token dari string literal, misal token yang dikirim kembali oleh user
+
+    # Arguments
+    * `value` - Teks token
+    */
+    pub fn from(value: &str) -> Token {
+        Token {
+            value: String::from(value),
+        }
+    }
+
+    /// Mengembalikan SHA-256 digest dari token dalam format hex, untuk disimpan di storage
+    pub fn digest(&self) -> String {
+        to_hex(&Sha256::digest(self.value.as_bytes()))
+    }
+
+    /// Mengembalikan teks token. Hanya digunakan saat token dikirim ke user.
+    pub fn expose_secret(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+impl<'de> Deserialize<'de> for Token {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(Token { value: s })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let first = Token::generate();
+        let second = Token::generate();
+
+        assert_eq!(64, first.expose_secret().len());
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_redacted_debug() {
+        let token: Token = serde_json::from_str("\"abc\"").unwrap();
+        assert_eq!("[REDACTED]", format!("{:?}", token));
+        assert_eq!("abc", token.expose_secret());
+    }
+
+    #[test]
+    fn test_digest() {
+        let token = Token::from("abc");
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            token.digest()
+        );
+        assert_eq!(token.digest(), Token::from("abc").digest());
+        assert_ne!(token.digest(), Token::from("abd").digest());
+    }
+}
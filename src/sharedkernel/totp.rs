@@ -0,0 +1,193 @@
+use hmac::{Hmac, Mac, NewMac};
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+use sha1::Sha1;
+
+/*
+==============================================================================================================================
+TOTP (RFC 6238)
+==============================================================================================================================
+Time-based one-time password dengan HMAC-SHA1, sesuai dengan aplikasi authenticator pada umumnya (Google Authenticator,
+Authy, 1Password, dll). Secret dibagikan kepada user dalam bentuk otpauth URI (biasanya ditampilkan sebagai QR code).
+*/
+
+/// Parameter TOTP
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TotpPolicy {
+    pub digits: u32,
+    /// Durasi satu time step dalam detik
+    pub step: u64,
+    /// Jumlah time step sebelum dan sesudah saat ini yang masih diterima (toleransi selisih jam)
+    pub drift: u64,
+}
+
+impl Default for TotpPolicy {
+    fn default() -> Self {
+        TotpPolicy {
+            digits: 6,
+            step: 30,
+            drift: 1,
+        }
+    }
+}
+
+/// Mengembalikan secret acak 160 bit, sesuai rekomendasi RFC 4226
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 20];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Encode secret ke base32 tanpa padding, format yang digunakan oleh aplikasi authenticator
+pub fn encode_secret(secret: &[u8]) -> String {
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, secret)
+}
+
+/**
+Mengembalikan kode HOTP (RFC 4226) untuk counter tertentu
+
+# Arguments
+* `secret` - Shared secret
+* `counter` - Counter, untuk TOTP berupa nomor time step
+* `digits` - Jumlah digit kode
+*/
+pub fn hotp(secret: &[u8], counter: u64, digits: u32) -> String {
+    let mut mac = Hmac::<Sha1>::new_varkey(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    let code = binary as u64 % 10u64.pow(digits);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+// Perbandingan constant time agar kode tidak dapat ditebak per digit dari waktu response
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/**
+Memverifikasi kode TOTP dan mengembalikan nomor time step yang cocok. Nomor time step digunakan
+untuk menolak kode yang sama (replay) maupun kode yang lebih lama dari kode terakhir yang diterima.
+
+# Arguments
+* `secret` - Shared secret
+* `code` - Kode dari user
+* `policy` - Parameter TOTP
+* `now` - Waktu saat ini dalam unix timestamp
+*/
+pub fn verify(secret: &[u8], code: &str, policy: &TotpPolicy, now: u64) -> Option<u64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let current = now / policy.step.max(1);
+    let mut matched = None;
+    // Seluruh window diperiksa agar waktu verifikasi tidak bergantung pada posisi kode yang cocok
+    for step in current.saturating_sub(policy.drift)..=current + policy.drift {
+        if constant_time_eq(&hotp(secret, step, policy.digits), &code) {
+            matched = Some(step);
+        }
+    }
+    matched
+}
+
+// Percent-encoding untuk komponen otpauth URI
+fn encode_component(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/**
+Mengembalikan otpauth URI (format Key Uri dari Google Authenticator) yang dapat ditampilkan sebagai
+QR code
+
+# Arguments
+* `issuer` - Nama aplikasi
+* `account_name` - Nama account, biasanya email
+* `secret` - Shared secret
+* `policy` - Parameter TOTP
+*/
+pub fn otpauth_uri(issuer: &str, account_name: &str, secret: &[u8], policy: &TotpPolicy) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode_component(issuer),
+        encode_component(account_name),
+        encode_secret(secret),
+        encode_component(issuer),
+        policy.digits,
+        policy.step
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        let policy = TotpPolicy {
+            digits: 8,
+            ..TotpPolicy::default()
+        };
+        for (time, expected) in [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ] {
+            assert_eq!(expected, hotp(SECRET, time / 30, 8));
+            assert_eq!(Some(time / 30), verify(SECRET, expected, &policy, time));
+        }
+    }
+
+    #[test]
+    fn test_verify_with_drift() {
+        let policy = TotpPolicy::default();
+        let now = 1234567890;
+        let step = now / 30;
+        let code = hotp(SECRET, step, 6);
+
+        assert_eq!("005924", code);
+        assert_eq!(Some(step), verify(SECRET, "005 924", &policy, now));
+        assert_eq!(Some(step), verify(SECRET, &code, &policy, now + 30));
+        assert_eq!(Some(step), verify(SECRET, &code, &policy, now - 30));
+        assert_eq!(None, verify(SECRET, &code, &policy, now + 60));
+        assert_eq!(None, verify(SECRET, "000000", &policy, now));
+        assert_eq!(None, verify(SECRET, "", &policy, now));
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let uri = otpauth_uri(
+            "Brokoli App",
+            "harun@digitalsekuriti.id",
+            b"Hello!\xde\xad\xbe\xef",
+            &TotpPolicy::default(),
+        );
+        assert_eq!(
+            "otpauth://totp/Brokoli%20App:harun@digitalsekuriti.id?secret=JBSWY3DPEHPK3PXP&issuer=Brokoli%20App&algorithm=SHA1&digits=6&period=30",
+            uri
+        );
+        assert_eq!(20, generate_secret().len());
+    }
+}
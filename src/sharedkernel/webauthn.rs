@@ -0,0 +1,591 @@
+use super::{
+    error,
+    function::{from_base64url, to_base64url},
+};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use p256::EncodedPoint;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+/*
+==============================================================================================================================
+WebAuthn
+==============================================================================================================================
+Bagian protokol dari WebAuthn Level 2 yang dibutuhkan oleh relying party : parsing client data, authenticator data,
+attestation object (format "none" dan "packed") dan verifikasi assertion. Hanya algoritma ES256 (ECDSA P-256 dengan SHA-256)
+yang didukung, algoritma yang didukung oleh seluruh authenticator dan platform passkey.
+
+Attestation "packed" dengan sertifikat (x5c) hanya diverifikasi signature-nya menggunakan public key dari sertifikat leaf,
+trust chain ke root CA vendor authenticator tidak diperiksa.
+*/
+
+/// COSE algorithm identifier untuk ES256
+pub const COSE_ALG_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+// Prefix DER SubjectPublicKeyInfo untuk public key EC P-256 (id-ecPublicKey, prime256v1)
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+fn invalid_response() -> error::ApplicationError<'static> {
+    error::bad_request_error!(
+        "Sorry, but the response from your security key is invalid.",
+        "invalid_webauthn_response"
+    )
+}
+
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes).to_vec()
+}
+
+/// Isi dari clientDataJSON yang dibuat oleh browser
+#[derive(Debug, Deserialize)]
+pub struct ClientData {
+    /// "webauthn.create" untuk registrasi, "webauthn.get" untuk autentikasi
+    #[serde(rename = "type")]
+    pub ceremony: String,
+    /// Challenge dalam format base64url
+    pub challenge: String,
+    pub origin: String,
+}
+
+impl ClientData {
+    /**
+    Membaca clientDataJSON
+
+    # Arguments
+    * `json` - clientDataJSON dalam bentuk bytes
+    */
+    pub fn parse(json: &[u8]) -> Result<ClientData, error::ApplicationError<'static>> {
+        serde_json::from_slice(json).map_err(|_| invalid_response())
+    }
+}
+
+/// Public key credential ES256
+#[derive(Debug, Clone)]
+pub struct PublicKey(VerifyingKey);
+
+impl PublicKey {
+    /**
+    Membaca public key dalam format COSE_Key (EC2, P-256, ES256)
+
+    # Arguments
+    * `value` - COSE_Key yang sudah di-decode dari CBOR
+    */
+    pub fn from_cose(value: &Value) -> Result<PublicKey, error::ApplicationError<'static>> {
+        let map = value.as_map().ok_or_else(invalid_response)?;
+        let get = |label: i64| {
+            map.iter()
+                .find(|(key, _)| key.as_integer().map(i128::from) == Some(label.into()))
+                .map(|(_, value)| value)
+        };
+        let integer = |label: i64| get(label).and_then(Value::as_integer).map(i128::from);
+        let coordinate = |label: i64| {
+            get(label)
+                .and_then(Value::as_bytes)
+                .filter(|bytes| bytes.len() == 32)
+                .map(|bytes| p256::FieldBytes::clone_from_slice(bytes))
+        };
+
+        // kty EC2 = 2, alg ES256 = -7, crv P-256 = 1
+        if integer(1) != Some(2)
+            || integer(3) != Some(COSE_ALG_ES256.into())
+            || integer(-1) != Some(1)
+        {
+            return Err(error::bad_request_error!(
+                "Sorry, but your security key uses an unsupported algorithm.",
+                "unsupported_webauthn_algorithm"
+            ));
+        }
+        let (x, y) = coordinate(-2)
+            .zip(coordinate(-3))
+            .ok_or_else(invalid_response)?;
+        let point = EncodedPoint::from_affine_coordinates(&x, &y, false);
+        VerifyingKey::from_encoded_point(&point)
+            .map(PublicKey)
+            .map_err(|_| invalid_response())
+    }
+
+    /**
+    Membaca public key yang disimpan dengan `encode`
+
+    # Arguments
+    * `text` - Titik SEC1 uncompressed dalam format base64url
+    */
+    pub fn decode(text: &str) -> Result<PublicKey, error::ApplicationError<'static>> {
+        let bytes = from_base64url(text).ok_or_else(invalid_response)?;
+        VerifyingKey::from_sec1_bytes(&bytes)
+            .map(PublicKey)
+            .map_err(|_| invalid_response())
+    }
+
+    /// Mengembalikan public key dalam format SEC1 uncompressed yang di-encode base64url
+    pub fn encode(&self) -> String {
+        to_base64url(self.0.to_encoded_point(false).as_bytes())
+    }
+
+    /**
+    Memverifikasi signature ECDSA (DER) atas message
+
+    # Arguments
+    * `message` - Data yang ditandatangani
+    * `signature` - Signature dalam format DER
+    */
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        Signature::from_der(signature)
+            .map(|signature| self.0.verify(message, &signature).is_ok())
+            .unwrap_or(false)
+    }
+
+    // Mengambil public key P-256 dari sertifikat X.509 (DER) tanpa parsing sertifikat secara penuh
+    fn from_certificate(certificate: &[u8]) -> Option<PublicKey> {
+        let start = certificate
+            .windows(P256_SPKI_PREFIX.len())
+            .position(|window| window == P256_SPKI_PREFIX)?
+            + P256_SPKI_PREFIX.len();
+        let point = certificate.get(start..start + 65)?;
+        VerifyingKey::from_sec1_bytes(point).ok().map(PublicKey)
+    }
+}
+
+/// Credential baru yang disertakan authenticator saat registrasi
+#[derive(Debug)]
+pub struct AttestedCredential {
+    pub aaguid: [u8; 16],
+    pub credential_id: Vec<u8>,
+    pub public_key: PublicKey,
+}
+
+/// Authenticator data yang ditandatangani oleh authenticator
+#[derive(Debug)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: Vec<u8>,
+    pub flags: u8,
+    pub sign_count: u32,
+    pub credential: Option<AttestedCredential>,
+}
+
+impl AuthenticatorData {
+    /**
+    Membaca authenticator data
+
+    # Arguments
+    * `bytes` - Authenticator data dalam bentuk bytes
+    */
+    pub fn parse(bytes: &[u8]) -> Result<AuthenticatorData, error::ApplicationError<'static>> {
+        if bytes.len() < 37 {
+            return Err(invalid_response());
+        }
+        let flags = bytes[32];
+        let sign_count = u32::from_be_bytes([bytes[33], bytes[34], bytes[35], bytes[36]]);
+
+        let credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+            let data = &bytes[37..];
+            if data.len() < 18 {
+                return Err(invalid_response());
+            }
+            let length = u16::from_be_bytes([data[16], data[17]]) as usize;
+            let credential_id = data.get(18..18 + length).ok_or_else(invalid_response)?;
+            // COSE_Key diikuti oleh extensions (jika ada), sehingga hanya satu item CBOR yang dibaca
+            let mut cursor = Cursor::new(&data[18 + length..]);
+            let key: Value =
+                ciborium::de::from_reader(&mut cursor).map_err(|_| invalid_response())?;
+            let mut aaguid = [0u8; 16];
+            aaguid.copy_from_slice(&data[..16]);
+            Some(AttestedCredential {
+                aaguid,
+                credential_id: credential_id.to_vec(),
+                public_key: PublicKey::from_cose(&key)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(AuthenticatorData {
+            rp_id_hash: bytes[..32].to_vec(),
+            flags,
+            sign_count,
+            credential,
+        })
+    }
+
+    pub fn is_user_present(&self) -> bool {
+        self.flags & FLAG_USER_PRESENT != 0
+    }
+
+    pub fn is_user_verified(&self) -> bool {
+        self.flags & FLAG_USER_VERIFIED != 0
+    }
+
+    /**
+    Memeriksa bahwa authenticator data dibuat untuk relying party ini dan user hadir
+    (serta terverifikasi dengan PIN/biometrik jika diwajibkan)
+
+    # Arguments
+    * `rp_id` - Relying party id, biasanya domain aplikasi
+    * `user_verification` - Apakah user verification diwajibkan
+    */
+    pub fn ensure_valid(
+        &self,
+        rp_id: &str,
+        user_verification: bool,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if self.rp_id_hash != sha256(rp_id.as_bytes())
+            || !self.is_user_present()
+            || (user_verification && !self.is_user_verified())
+        {
+            return Err(invalid_response());
+        }
+        Ok(())
+    }
+}
+
+/**
+Membaca dan memverifikasi attestation object hasil registrasi, lalu mengembalikan authenticator data
+yang berisi credential baru
+
+# Arguments
+* `attestation_object` - Attestation object (CBOR)
+* `client_data_json` - clientDataJSON dari browser
+*/
+pub fn verify_attestation(
+    attestation_object: &[u8],
+    client_data_json: &[u8],
+) -> Result<AuthenticatorData, error::ApplicationError<'static>> {
+    let value: Value =
+        ciborium::de::from_reader(attestation_object).map_err(|_| invalid_response())?;
+    let map = value.as_map().ok_or_else(invalid_response)?;
+    let get = |name: &str| {
+        map.iter()
+            .find(|(key, _)| key.as_text() == Some(name))
+            .map(|(_, value)| value)
+    };
+    let format = get("fmt")
+        .and_then(Value::as_text)
+        .ok_or_else(invalid_response)?;
+    let raw = get("authData")
+        .and_then(Value::as_bytes)
+        .ok_or_else(invalid_response)?;
+    let statement = get("attStmt")
+        .and_then(Value::as_map)
+        .ok_or_else(invalid_response)?;
+
+    let auth_data = AuthenticatorData::parse(raw)?;
+    let credential = auth_data.credential.as_ref().ok_or_else(invalid_response)?;
+
+    match format {
+        "none" if statement.is_empty() => {}
+        "packed" => {
+            let field = |name: &str| {
+                statement
+                    .iter()
+                    .find(|(key, _)| key.as_text() == Some(name))
+                    .map(|(_, value)| value)
+            };
+            let algorithm = field("alg").and_then(Value::as_integer).map(i128::from);
+            let signature = field("sig").and_then(Value::as_bytes);
+            let (Some(algorithm), Some(signature)) = (algorithm, signature) else {
+                return Err(invalid_response());
+            };
+            if algorithm != COSE_ALG_ES256.into() {
+                return Err(invalid_response());
+            }
+
+            // Full attestation ditandatangani dengan key sertifikat, self attestation dengan key credential
+            let key = match field("x5c") {
+                Some(chain) => chain
+                    .as_array()
+                    .and_then(|chain| chain.first())
+                    .and_then(Value::as_bytes)
+                    .and_then(|certificate| PublicKey::from_certificate(certificate))
+                    .ok_or_else(invalid_response)?,
+                None => credential.public_key.clone(),
+            };
+            let message = [raw.as_slice(), &sha256(client_data_json)].concat();
+            if !key.verify(&message, signature) {
+                return Err(invalid_response());
+            }
+        }
+        "none" => return Err(invalid_response()),
+        _ => {
+            return Err(error::bad_request_error!(
+                "Sorry, but your security key uses an unsupported attestation format.",
+                "unsupported_attestation_format"
+            ))
+        }
+    }
+
+    Ok(auth_data)
+}
+
+/**
+Memverifikasi assertion hasil autentikasi dan mengembalikan authenticator data-nya
+
+# Arguments
+* `public_key` - Public key credential yang tersimpan
+* `authenticator_data` - Authenticator data dari authenticator
+* `client_data_json` - clientDataJSON dari browser
+* `signature` - Signature dari authenticator (DER)
+*/
+pub fn verify_assertion(
+    public_key: &PublicKey,
+    authenticator_data: &[u8],
+    client_data_json: &[u8],
+    signature: &[u8],
+) -> Result<AuthenticatorData, error::ApplicationError<'static>> {
+    let auth_data = AuthenticatorData::parse(authenticator_data)?;
+    let message = [authenticator_data, &sha256(client_data_json)].concat();
+    if !public_key.verify(&message, signature) {
+        return Err(error::unauthorized_error!(
+            "Sorry, but your security key could not be verified.",
+            "invalid_webauthn_signature"
+        ));
+    }
+    Ok(auth_data)
+}
+
+/// Authenticator software (ES256) untuk pengujian ceremony registrasi dan autentikasi
+#[cfg(test)]
+pub mod software {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use rand::{rngs::OsRng, RngCore};
+
+    pub struct SoftwareAuthenticator {
+        key: SigningKey,
+        pub credential_id: Vec<u8>,
+        pub sign_count: u32,
+        pub user_verified: bool,
+    }
+
+    impl Default for SoftwareAuthenticator {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl SoftwareAuthenticator {
+        pub fn new() -> SoftwareAuthenticator {
+            let mut credential_id = vec![0u8; 16];
+            OsRng.fill_bytes(&mut credential_id);
+            SoftwareAuthenticator {
+                key: SigningKey::random(&mut OsRng),
+                credential_id,
+                sign_count: 0,
+                user_verified: true,
+            }
+        }
+
+        pub fn client_data(ceremony: &str, challenge: &str, origin: &str) -> Vec<u8> {
+            serde_json::to_vec(&serde_json::json!({
+                "type": ceremony,
+                "challenge": challenge,
+                "origin": origin,
+                "crossOrigin": false
+            }))
+            .unwrap()
+        }
+
+        fn cose_key(&self) -> Value {
+            let point = self.key.verifying_key().to_encoded_point(false);
+            Value::Map(vec![
+                (Value::from(1), Value::from(2)),
+                (Value::from(3), Value::from(COSE_ALG_ES256)),
+                (Value::from(-1), Value::from(1)),
+                (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+                (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+            ])
+        }
+
+        fn auth_data(&self, rp_id: &str, attested: bool) -> Vec<u8> {
+            let mut flags = FLAG_USER_PRESENT;
+            if self.user_verified {
+                flags |= FLAG_USER_VERIFIED;
+            }
+            if attested {
+                flags |= FLAG_ATTESTED_CREDENTIAL;
+            }
+            let mut data = sha256(rp_id.as_bytes());
+            data.push(flags);
+            data.extend_from_slice(&self.sign_count.to_be_bytes());
+            if attested {
+                data.extend_from_slice(&[0u8; 16]);
+                data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+                data.extend_from_slice(&self.credential_id);
+                ciborium::ser::into_writer(&self.cose_key(), &mut data).unwrap();
+            }
+            data
+        }
+
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            let signature: Signature = self.key.sign(message);
+            signature.to_der().as_bytes().to_vec()
+        }
+
+        /// Membuat credential baru, mengembalikan clientDataJSON dan attestation object
+        pub fn attest(
+            &self,
+            rp_id: &str,
+            challenge: &str,
+            origin: &str,
+            format: &str,
+        ) -> (Vec<u8>, Vec<u8>) {
+            let client_data = Self::client_data("webauthn.create", challenge, origin);
+            let auth_data = self.auth_data(rp_id, true);
+            let statement = match format {
+                "packed" => {
+                    let message = [auth_data.as_slice(), &sha256(&client_data)].concat();
+                    vec![
+                        (Value::from("alg"), Value::from(COSE_ALG_ES256)),
+                        (Value::from("sig"), Value::Bytes(self.sign(&message))),
+                    ]
+                }
+                _ => vec![],
+            };
+            let object = Value::Map(vec![
+                (Value::from("fmt"), Value::from(format)),
+                (Value::from("attStmt"), Value::Map(statement)),
+                (Value::from("authData"), Value::Bytes(auth_data)),
+            ]);
+            let mut attestation_object = Vec::new();
+            ciborium::ser::into_writer(&object, &mut attestation_object).unwrap();
+            (client_data, attestation_object)
+        }
+
+        /// Menandatangani challenge, mengembalikan clientDataJSON, authenticator data dan signature
+        pub fn assert(
+            &mut self,
+            rp_id: &str,
+            challenge: &str,
+            origin: &str,
+        ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+            self.sign_count += 1;
+            let client_data = Self::client_data("webauthn.get", challenge, origin);
+            let auth_data = self.auth_data(rp_id, false);
+            let message = [auth_data.as_slice(), &sha256(&client_data)].concat();
+            let signature = self.sign(&message);
+            (client_data, auth_data, signature)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::software::SoftwareAuthenticator;
+    use super::*;
+
+    const RP_ID: &str = "brokoli.id";
+    const ORIGIN: &str = "https://brokoli.id";
+
+    #[test]
+    fn test_attestation_formats() {
+        let authenticator = SoftwareAuthenticator::new();
+        for format in ["none", "packed"] {
+            let (client_data, object) = authenticator.attest(RP_ID, "Y2hhbGxlbmdl", ORIGIN, format);
+            let client = ClientData::parse(&client_data).unwrap();
+            assert_eq!("webauthn.create", client.ceremony);
+            assert_eq!("Y2hhbGxlbmdl", client.challenge);
+
+            let auth_data = verify_attestation(&object, &client_data).unwrap();
+            auth_data.ensure_valid(RP_ID, true).unwrap();
+            assert!(auth_data.ensure_valid("evil.id", false).is_err());
+            let credential = auth_data.credential.unwrap();
+            assert_eq!(authenticator.credential_id, credential.credential_id);
+
+            // Public key dapat disimpan dan dibaca kembali
+            let encoded = credential.public_key.encode();
+            assert_eq!(encoded, PublicKey::decode(&encoded).unwrap().encode());
+        }
+
+        // Signature packed tidak cocok jika client data diganti
+        let (_, object) = authenticator.attest(RP_ID, "Y2hhbGxlbmdl", ORIGIN, "packed");
+        let other = SoftwareAuthenticator::client_data("webauthn.create", "b3RoZXI", ORIGIN);
+        assert!(verify_attestation(&object, &other).is_err());
+
+        let (client_data, object) = authenticator.attest(RP_ID, "Y2hhbGxlbmdl", ORIGIN, "tpm");
+        let error = verify_attestation(&object, &client_data).unwrap_err();
+        assert_eq!("unsupported_attestation_format", error.error);
+        assert!(verify_attestation(b"garbage", &client_data).is_err());
+    }
+
+    // Menambahkan x5c berisi sertifikat tiruan yang hanya memuat SubjectPublicKeyInfo dari key
+    fn with_certificate(object: &[u8], key: &PublicKey) -> Vec<u8> {
+        let certificate = [
+            &[0x30, 0x82, 0x01, 0x00][..],
+            &P256_SPKI_PREFIX,
+            key.0.to_encoded_point(false).as_bytes(),
+        ]
+        .concat();
+        let value: Value = ciborium::de::from_reader(object).unwrap();
+        let mut map = value.into_map().unwrap();
+        for (name, value) in map.iter_mut() {
+            if name.as_text() == Some("attStmt") {
+                let mut statement = value.as_map().unwrap().clone();
+                statement.push((
+                    Value::from("x5c"),
+                    Value::Array(vec![Value::Bytes(certificate.clone())]),
+                ));
+                *value = Value::Map(statement);
+            }
+        }
+        let mut result = Vec::new();
+        ciborium::ser::into_writer(&Value::Map(map), &mut result).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_packed_attestation_with_certificate() {
+        let authenticator = SoftwareAuthenticator::new();
+        let (client_data, object) = authenticator.attest(RP_ID, "Y2hhbGxlbmdl", ORIGIN, "packed");
+        let key = verify_attestation(&object, &client_data)
+            .unwrap()
+            .credential
+            .unwrap()
+            .public_key;
+        assert!(verify_attestation(&with_certificate(&object, &key), &client_data).is_ok());
+
+        // Signature harus dibuat dengan key dari sertifikat
+        let other = SoftwareAuthenticator::new();
+        let (other_client_data, other_object) = other.attest(RP_ID, "Y2hhbGxlbmdl", ORIGIN, "none");
+        let other_key = verify_attestation(&other_object, &other_client_data)
+            .unwrap()
+            .credential
+            .unwrap()
+            .public_key;
+        assert!(verify_attestation(&with_certificate(&object, &other_key), &client_data).is_err());
+    }
+
+    #[test]
+    fn test_assertion() {
+        let mut authenticator = SoftwareAuthenticator::new();
+        let (client_data, object) = authenticator.attest(RP_ID, "Y2hhbGxlbmdl", ORIGIN, "none");
+        let public_key = verify_attestation(&object, &client_data)
+            .unwrap()
+            .credential
+            .unwrap()
+            .public_key;
+
+        let (client_data, auth_data, signature) = authenticator.assert(RP_ID, "bmV4dA", ORIGIN);
+        let result = verify_assertion(&public_key, &auth_data, &client_data, &signature).unwrap();
+        assert_eq!(1, result.sign_count);
+        assert!(result.credential.is_none());
+        result.ensure_valid(RP_ID, true).unwrap();
+
+        let other = SoftwareAuthenticator::client_data("webauthn.get", "b3RoZXI", ORIGIN);
+        let error = verify_assertion(&public_key, &auth_data, &other, &signature).unwrap_err();
+        assert_eq!("invalid_webauthn_signature", error.error);
+
+        authenticator.user_verified = false;
+        let (client_data, auth_data, signature) = authenticator.assert(RP_ID, "bmV4dA", ORIGIN);
+        let result = verify_assertion(&public_key, &auth_data, &client_data, &signature).unwrap();
+        assert!(result.ensure_valid(RP_ID, true).is_err());
+        result.ensure_valid(RP_ID, false).unwrap();
+    }
+}
@@ -1,8 +1,19 @@
-use super::super::sharedkernel::{email::Email, password::Hash};
+use super::super::sharedkernel::{
+    email::Email,
+    error,
+    function::get_now,
+    password::{Hash, Password},
+};
+use super::phone::PhoneFactor;
+use super::totp::TotpFactor;
+use super::trusted_device::TrustedDevice;
+use super::webauthn::WebAuthnCredential;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum AccountRole {
     #[serde(rename = "admin")]
     Admin,
@@ -11,7 +22,7 @@ pub enum AccountRole {
 }
 
 impl AccountRole {
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::should_implement_trait)]
     pub fn from_str(text: &str) -> AccountRole {
         match text {
             "admin" => AccountRole::Admin,
@@ -20,37 +31,415 @@ impl AccountRole {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum AccountStatus {
     #[serde(rename = "active")]
     Active,
+    /// Account baru yang email-nya belum diverifikasi
+    #[serde(rename = "pending_verification")]
+    PendingVerification,
     #[serde(rename = "deleted")]
     Deleted,
 }
 
 impl AccountStatus {
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::should_implement_trait)]
     pub fn from_str(text: &str) -> AccountStatus {
         match text {
             "active" => AccountStatus::Active,
+            "pending_verification" => AccountStatus::PendingVerification,
             _ => AccountStatus::Deleted,
         }
     }
 }
 
+/**
+Account user. Serialisasi hanya berisi field yang aman ditampilkan, sehingga test menggunakan Clone
+(hanya tersedia pada test) untuk menyalin account beserta hash dan faktor MFA.
+*/
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Clone))]
 pub struct Account {
     _id: Uuid,
     email: Email,
+    #[serde(default, skip_serializing)]
+    email_verified_at: Option<u64>,
     #[allow(dead_code)]
     #[serde(skip_serializing)]
     hash: Hash,
+    #[serde(default, skip_serializing)]
+    password_history: Vec<Hash>,
+    #[serde(default, skip_serializing)]
+    password_changed_at: u64,
+    #[serde(default, skip_serializing)]
+    must_change_password: bool,
+    #[serde(default, skip_serializing)]
+    totp: Option<TotpFactor>,
+    #[serde(default, skip_serializing)]
+    webauthn_credentials: Vec<WebAuthnCredential>,
+    #[serde(default, skip_serializing)]
+    recovery_codes: Vec<Hash>,
+    #[serde(default, skip_serializing)]
+    phone: Option<PhoneFactor>,
+    #[serde(default, skip_serializing)]
+    trusted_devices: Vec<TrustedDevice>,
     role: AccountRole,
     status: AccountStatus,
     created_at: u64,
     updated_at: u64,
 }
 
+impl Account {
+    /**
+    Mengembalikan account baru dengan status active
+
+    # Arguments
+    * `email` - Email pemilik account
+    * `hash` - Hashed password
+    * `role` - Role dari account
+    */
+    pub fn new(email: Email, hash: Hash, role: AccountRole) -> Account {
+        let now = get_now();
+        Account {
+            _id: Uuid::new_v4(),
+            email,
+            email_verified_at: None,
+            hash,
+            password_history: Vec::new(),
+            password_changed_at: now,
+            must_change_password: false,
+            totp: None,
+            webauthn_credentials: Vec::new(),
+            recovery_codes: Vec::new(),
+            phone: None,
+            trusted_devices: Vec::new(),
+            role,
+            status: AccountStatus::Active,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn id(&self) -> &Uuid {
+        &self._id
+    }
+
+    pub fn email(&self) -> &Email {
+        &self.email
+    }
+
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn role(&self) -> &AccountRole {
+        &self.role
+    }
+
+    pub fn status(&self) -> &AccountStatus {
+        &self.status
+    }
+
+    /**
+    Memeriksa apakah password sudah melewati batas usia maksimal. Account lama yang belum memiliki
+    `password_changed_at` menggunakan `created_at` sebagai acuan.
+
+    # Arguments
+    * `max_age` - Usia maksimal password dalam detik, None berarti password tidak pernah expired
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn is_password_expired(&self, max_age: Option<u64>, now: u64) -> bool {
+        let changed_at = match self.password_changed_at {
+            0 => self.created_at,
+            changed_at => changed_at,
+        };
+        match max_age {
+            Some(max_age) => now.saturating_sub(changed_at) >= max_age,
+            None => false,
+        }
+    }
+
+    /**
+    Memeriksa apakah user harus mengganti password sebelum dapat melakukan operasi lain,
+    baik karena password expired maupun ditandai oleh admin
+
+    # Arguments
+    * `max_age` - Usia maksimal password dalam detik
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn requires_password_change(&self, max_age: Option<u64>, now: u64) -> bool {
+        self.must_change_password || self.is_password_expired(max_age, now)
+    }
+
+    pub fn totp(&self) -> Option<&TotpFactor> {
+        self.totp.as_ref()
+    }
+
+    /**
+    Mengganti atau menghapus faktor TOTP milik account
+
+    # Arguments
+    * `totp` - Faktor TOTP yang baru, None untuk menonaktifkan TOTP
+    */
+    pub fn set_totp(&mut self, totp: Option<TotpFactor>) {
+        self.totp = totp;
+        self.updated_at = get_now();
+    }
+
+    pub fn webauthn_credentials(&self) -> &[WebAuthnCredential] {
+        &self.webauthn_credentials
+    }
+
+    /**
+    Mengembalikan credential WebAuthn milik account
+
+    # Arguments
+    * `id` - Credential id dalam format base64url
+    */
+    pub fn webauthn_credential_mut(&mut self, id: &str) -> Option<&mut WebAuthnCredential> {
+        self.webauthn_credentials
+            .iter_mut()
+            .find(|credential| credential.id() == id)
+    }
+
+    /**
+    Mendaftarkan credential WebAuthn (passkey) baru
+
+    # Arguments
+    * `credential` - Credential hasil registrasi
+    */
+    pub fn add_webauthn_credential(&mut self, credential: WebAuthnCredential) {
+        self.webauthn_credentials.push(credential);
+        self.updated_at = get_now();
+    }
+
+    pub fn phone(&self) -> Option<&PhoneFactor> {
+        self.phone.as_ref()
+    }
+
+    /**
+    Mengganti atau menghapus nomor telepon terverifikasi milik account
+
+    # Arguments
+    * `phone` - Nomor telepon yang sudah diverifikasi, None untuk menghapus
+    */
+    pub fn set_phone(&mut self, phone: Option<PhoneFactor>) {
+        self.phone = phone;
+        self.updated_at = get_now();
+    }
+
+    /// Mengembalikan hash dari recovery code yang belum digunakan
+    pub fn recovery_codes(&self) -> &[Hash] {
+        &self.recovery_codes
+    }
+
+    /// Mengembalikan jumlah recovery code yang belum digunakan
+    pub fn recovery_codes_remaining(&self) -> usize {
+        self.recovery_codes.len()
+    }
+
+    /**
+    Mengganti seluruh recovery code, recovery code sebelumnya tidak dapat digunakan lagi
+
+    # Arguments
+    * `recovery_codes` - Hash dari recovery code yang baru
+    */
+    pub fn set_recovery_codes(&mut self, recovery_codes: Vec<Hash>) {
+        self.recovery_codes = recovery_codes;
+        self.updated_at = get_now();
+    }
+
+    /**
+    Menggunakan recovery code. Recovery code yang cocok dihapus sehingga hanya dapat digunakan
+    satu kali, lalu hash-nya dikembalikan.
+
+    # Arguments
+    * `code` - Recovery code yang sudah dinormalisasi
+    */
+    pub fn use_recovery_code(&mut self, code: &Password) -> Option<Hash> {
+        let index = self
+            .recovery_codes
+            .iter()
+            .position(|hash| hash.verify_password(code).unwrap_or(false))?;
+        self.updated_at = get_now();
+        Some(self.recovery_codes.remove(index))
+    }
+
+    pub fn trusted_devices(&self) -> &[TrustedDevice] {
+        &self.trusted_devices
+    }
+
+    /**
+    Menambahkan trusted device. Trusted device yang sudah expired dihapus, dan jika jumlahnya
+    melebihi `max_devices` perangkat terlama ikut dihapus.
+
+    # Arguments
+    * `device` - Trusted device yang baru
+    * `max_devices` - Jumlah maksimal trusted device
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn add_trusted_device(&mut self, device: TrustedDevice, max_devices: usize, now: u64) {
+        self.trusted_devices
+            .retain(|device| !device.is_expired(now));
+        self.trusted_devices.push(device);
+        let excess = self.trusted_devices.len().saturating_sub(max_devices);
+        self.trusted_devices.drain(..excess);
+        self.updated_at = now;
+    }
+
+    /**
+    Menghapus trusted device, mengembalikan false jika tidak ditemukan
+
+    # Arguments
+    * `id` - Id trusted device
+    */
+    pub fn remove_trusted_device(&mut self, id: &Uuid) -> bool {
+        let length = self.trusted_devices.len();
+        self.trusted_devices.retain(|device| device.id() != id);
+        self.updated_at = get_now();
+        self.trusted_devices.len() != length
+    }
+
+    /// Menghapus seluruh trusted device
+    pub fn clear_trusted_devices(&mut self) {
+        self.trusted_devices.clear();
+        self.updated_at = get_now();
+    }
+
+    /**
+    Memeriksa trusted device token yang dikirim browser dan mencatat penggunaannya
+
+    # Arguments
+    * `digest` - Digest dari trusted device token
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn use_trusted_device(&mut self, digest: &str, now: u64) -> bool {
+        let used = self
+            .trusted_devices
+            .iter_mut()
+            .any(|device| device.verify(digest, now));
+        if used {
+            self.updated_at = now;
+        }
+        used
+    }
+
+    /// Memeriksa apakah account memiliki faktor autentikasi tambahan yang aktif
+    pub fn is_mfa_enabled(&self) -> bool {
+        self.totp.as_ref().is_some_and(TotpFactor::is_confirmed)
+            || !self.webauthn_credentials.is_empty()
+            || self.phone.is_some()
+    }
+
+    pub fn email_verified_at(&self) -> Option<u64> {
+        self.email_verified_at
+    }
+
+    /**
+    Mengganti email account dengan email yang sudah dikonfirmasi oleh pemiliknya
+
+    # Arguments
+    * `email` - Email yang baru
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn change_email(&mut self, email: Email, now: u64) {
+        self.email = email;
+        self.email_verified_at = Some(now);
+        self.updated_at = now;
+    }
+
+    /// Menandai account baru agar tidak dapat digunakan hingga email diverifikasi
+    pub fn require_email_verification(&mut self) {
+        self.status = AccountStatus::PendingVerification;
+        self.email_verified_at = None;
+        self.updated_at = get_now();
+    }
+
+    /**
+    Menandai email sudah diverifikasi, account yang menunggu verifikasi menjadi aktif
+
+    # Arguments
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn verify_email(&mut self, now: u64) {
+        if self.status == AccountStatus::PendingVerification {
+            self.status = AccountStatus::Active;
+        }
+        self.email_verified_at = Some(now);
+        self.updated_at = now;
+    }
+
+    /// Menandai account sebagai dihapus, account tidak dapat digunakan untuk login lagi
+    pub fn delete(&mut self) {
+        self.status = AccountStatus::Deleted;
+        self.updated_at = get_now();
+    }
+
+    /// Menandai account agar password wajib diganti pada login berikutnya (digunakan oleh admin)
+    pub fn require_password_change(&mut self) {
+        self.must_change_password = true;
+        self.updated_at = get_now();
+    }
+
+    /**
+    Memeriksa apakah password sama dengan password saat ini maupun password sebelumnya
+    yang masih tersimpan di dalam history
+
+    # Arguments
+    * `password` - Reference dari object Password
+    */
+    pub fn is_password_reused(&self, password: &Password) -> bool {
+        std::iter::once(&self.hash)
+            .chain(self.password_history.iter())
+            .any(|hash| hash.verify_password(password).unwrap_or(false))
+    }
+
+    /**
+    Menolak password yang termasuk dalam `history_depth` password terakhir (termasuk password
+    saat ini) dengan validation error code `password_reused`. History depth 0 berarti tidak ada
+    pemeriksaan.
+
+    # Arguments
+    * `password` - Reference dari object Password yang baru
+    * `history_depth` - Jumlah password terakhir yang tidak boleh digunakan kembali
+    */
+    pub fn ensure_password_not_reused(
+        &self,
+        password: &Password,
+        history_depth: usize,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if history_depth == 0 || !self.is_password_reused(password) {
+            return Ok(());
+        }
+
+        let mut reused = ValidationError::new("password_reused");
+        reused.add_param(Cow::from("history_depth"), &history_depth);
+        let mut errors = ValidationErrors::new();
+        errors.add("password", reused);
+        Err(error::ApplicationError::from_validation_errors(errors))
+    }
+
+    /**
+    Mengganti hash password. Hash sebelumnya disimpan ke dalam history yang dibatasi agar
+    hanya memuat `history_depth - 1` hash sebelumnya.
+
+    # Arguments
+    * `hash` - Hash dari password yang baru
+    * `history_depth` - Jumlah password terakhir yang tidak boleh digunakan kembali
+    */
+    pub fn change_hash(&mut self, hash: Hash, history_depth: usize) {
+        let previous = std::mem::replace(&mut self.hash, hash);
+        self.password_history.insert(0, previous);
+        self.password_history
+            .truncate(history_depth.saturating_sub(1));
+        self.must_change_password = false;
+        // Browser yang diingat harus melakukan MFA kembali setelah password diganti
+        self.trusted_devices.clear();
+        self.password_changed_at = get_now();
+        self.updated_at = self.password_changed_at;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -63,7 +452,16 @@ mod test {
         let entity: Account = Account {
             _id: my_uuid,
             email: Email::from("harun@digitalsekuriti.id"),
+            email_verified_at: None,
             hash: Hash::from("expected_hash"),
+            password_history: vec![Hash::from("previous_hash")],
+            password_changed_at: now,
+            must_change_password: false,
+            totp: None,
+            webauthn_credentials: Vec::new(),
+            recovery_codes: Vec::new(),
+            phone: None,
+            trusted_devices: Vec::new(),
             role: AccountRole::from_str("admin"),
             status: AccountStatus::from_str("active"),
             created_at: now,
@@ -90,10 +488,83 @@ mod test {
         let v: Account = serde_json::from_str(payload).unwrap();
         assert_eq!("61279487-2eab-406c-9265-c6985dcbc3be", v._id.to_string());
         assert_eq!("harun@digitalsekuriti.id", v.email.to_string());
-        assert_eq!("123456", v.hash.to_string());
+        assert_eq!("123456", v.hash.expose_secret());
+        assert!(!format!("{:?}", v).contains("123456"));
         assert_eq!(AccountRole::Admin, v.role);
         assert_eq!(AccountStatus::Active, v.status);
         assert_eq!(1669969469u64, v.created_at);
         assert_eq!(1669969469u64, v.updated_at);
+        assert_eq!(0u64, v.password_changed_at);
+        assert!(!v.must_change_password);
+    }
+
+    const KEY: &str = "$6$G/gkPn17kHYo0gTF$";
+
+    fn account_with_password(password: &str) -> Account {
+        let hash = Hash::from_password(KEY, &Password::from(password)).unwrap();
+        Account::new(
+            Email::from("harun@digitalsekuriti.id"),
+            hash,
+            AccountRole::User,
+        )
+    }
+
+    #[test]
+    fn test_password_history() {
+        let mut account = account_with_password("FirstPassw0rd!");
+        for password in ["SecondPassw0rd!", "ThirdPassw0rd!", "FourthPassw0rd!"] {
+            let hash = Hash::from_password(KEY, &Password::from(password)).unwrap();
+            account.change_hash(hash, 3);
+        }
+
+        assert_eq!(2, account.password_history.len());
+        assert!(account.is_password_reused(&Password::from("FourthPassw0rd!")));
+        assert!(account.is_password_reused(&Password::from("ThirdPassw0rd!")));
+        assert!(account.is_password_reused(&Password::from("SecondPassw0rd!")));
+        assert!(!account.is_password_reused(&Password::from("FirstPassw0rd!")));
+    }
+
+    #[test]
+    fn test_ensure_password_not_reused() {
+        let account = account_with_password("FirstPassw0rd!");
+        let password = Password::from("FirstPassw0rd!");
+
+        assert!(account.ensure_password_not_reused(&password, 0).is_ok());
+        let error = account
+            .ensure_password_not_reused(&password, 5)
+            .unwrap_err();
+        assert!(error.fields.unwrap()["password"][0].contains("password_reused"));
+        assert!(account
+            .ensure_password_not_reused(&Password::from("OtherPassw0rd!"), 5)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_password_expiry() {
+        let mut account = account_with_password("FirstPassw0rd!");
+        account.password_changed_at = 1669969469;
+        let day: u64 = 86400;
+
+        assert!(!account.is_password_expired(None, 1669969469 + 1000 * day));
+        assert!(!account.is_password_expired(Some(90 * day), 1669969469 + 89 * day));
+        assert!(account.is_password_expired(Some(90 * day), 1669969469 + 90 * day));
+
+        account.password_changed_at = 0;
+        account.created_at = 1669969469;
+        assert!(account.is_password_expired(Some(90 * day), 1669969469 + 90 * day));
+    }
+
+    #[test]
+    fn test_require_password_change() {
+        let mut account = account_with_password("FirstPassw0rd!");
+        let now = get_now();
+        assert!(!account.requires_password_change(None, now));
+
+        account.require_password_change();
+        assert!(account.requires_password_change(None, now));
+
+        let hash = Hash::from_password(KEY, &Password::from("SecondPassw0rd!")).unwrap();
+        account.change_hash(hash, 0);
+        assert!(!account.requires_password_change(None, now));
     }
 }
@@ -0,0 +1,796 @@
+use super::super::sharedkernel::{
+    client::ClientInfo,
+    email::Email,
+    error,
+    function::get_now,
+    password::{Hash, Password},
+    password_policy::PasswordPolicy,
+    rate_limit::RateLimiter,
+    token::Token,
+};
+use super::account::{Account, AccountStatus};
+use super::lockout::LockoutGuard;
+use super::repository::AccountRepository;
+use super::risk::{LoginEvent, RiskDecision, RiskEngine};
+use super::session::{
+    AuthenticationMethod, AuthenticationScope, Session, SessionRepository, DEFAULT_SESSION_TTL,
+};
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+// Nama route login pada konfigurasi rate limit
+pub const LOGIN_ROUTE: &str = "POST /sessions";
+
+/// Kredensial yang dikirim oleh user saat login
+#[derive(Debug, Deserialize)]
+pub struct Credential {
+    email: Email,
+    password: Password,
+}
+
+/// Password yang dikirim oleh user yang sedang login sebelum melakukan operasi sensitif
+#[derive(Debug, Deserialize)]
+pub struct Reauthentication {
+    password: Password,
+}
+
+/// Hasil login yang berhasil
+#[derive(Debug)]
+pub struct Authentication {
+    pub account: Account,
+    pub session: Session,
+    /// Hasil penilaian risiko, jika risk engine diaktifkan
+    pub risk: Option<LoginEvent>,
+}
+
+/**
+Mengembalikan hash dari password acak yang dibuat sekali per proses. Hash ini diverifikasi ketika
+email tidak terdaftar, sehingga waktu response login tidak dapat digunakan untuk enumerasi account.
+*/
+fn dummy_hash() -> &'static Hash {
+    static DUMMY_HASH: OnceLock<Hash> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        let password = Password::from(Token::generate().expose_secret());
+        Hash::generate(&password).unwrap_or_default()
+    })
+}
+
+/// Use case login menggunakan email dan password
+pub struct AuthenticationService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    policy: &'a PasswordPolicy,
+    session_ttl: u64,
+    lockout: Option<LockoutGuard<'a>>,
+    risk: Option<RiskEngine<'a>>,
+    rate_limiter: Option<RateLimiter<'a>>,
+}
+
+impl<'a> AuthenticationService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        policy: &'a PasswordPolicy,
+    ) -> AuthenticationService<'a> {
+        AuthenticationService {
+            accounts,
+            sessions,
+            policy,
+            session_ttl: DEFAULT_SESSION_TTL,
+            lockout: None,
+            risk: None,
+            rate_limiter: None,
+        }
+    }
+
+    /// Mengganti masa berlaku session (dalam detik) yang dibuat saat login
+    pub fn with_session_ttl(mut self, ttl: u64) -> AuthenticationService<'a> {
+        self.session_ttl = ttl;
+        self
+    }
+
+    /// Mengaktifkan penguncian account dan IP setelah kegagalan login berturut-turut
+    pub fn with_lockout(mut self, lockout: LockoutGuard<'a>) -> AuthenticationService<'a> {
+        self.lockout = Some(lockout);
+        self
+    }
+
+    /// Mengaktifkan rate limit untuk route `LOGIN_ROUTE`, diperiksa sebelum kredensial diverifikasi
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter<'a>) -> AuthenticationService<'a> {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Mengaktifkan penilaian risiko untuk setiap login dengan password yang benar
+    pub fn with_risk_engine(mut self, risk: RiskEngine<'a>) -> AuthenticationService<'a> {
+        self.risk = Some(risk);
+        self
+    }
+
+    /**
+    Memverifikasi kredensial user dan membuat session baru. Account dengan password expired atau
+    ditandai wajib ganti password mendapatkan session dengan scope `PasswordChange`, sedangkan account
+    dengan MFA aktif mendapatkan scope `StepUp` hingga faktor tambahan diverifikasi, kecuali login
+    dari trusted device yang masih berlaku. Jika lockout diaktifkan, login ditolak selama account
+    atau IP client terkunci. Jika risk engine diaktifkan, login berisiko diblokir atau mendapatkan
+    session dengan scope `StepUp`.
+
+    # Arguments
+    * `credential` - Email dan password dari user
+    * `client` - Informasi client yang melakukan login
+    */
+    pub fn login(
+        &self,
+        credential: &Credential,
+        client: &ClientInfo,
+    ) -> Result<Authentication, error::ApplicationError<'static>> {
+        let invalid_credential = || {
+            error::unauthorized_error!(
+                "Sorry, but your email or password is incorrect.",
+                "invalid_credential"
+            )
+        };
+
+        let now = get_now();
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .check(LOGIN_ROUTE, client, None, now)?
+                .ensure_allowed()?;
+        }
+        if let Some(lockout) = &self.lockout {
+            lockout.ensure_not_locked(&credential.email, &client.ip, now)?;
+        }
+
+        // Password selalu diverifikasi, termasuk untuk email yang tidak terdaftar atau account
+        // yang tidak aktif, agar seluruh kegagalan membutuhkan waktu dan response yang sama
+        let account = self.accounts.get_by_email(&credential.email)?;
+        let hash = match &account {
+            Some(account) if !account.hash().expose_secret().is_empty() => account.hash(),
+            _ => dummy_hash(),
+        };
+        let verified = hash.verify_password(&credential.password).unwrap_or(false);
+
+        let mut account = match account {
+            Some(account) if verified && *account.status() == AccountStatus::Active => account,
+            // Hanya diinformasikan kepada pemilik password yang benar
+            Some(account)
+                if verified && *account.status() == AccountStatus::PendingVerification =>
+            {
+                return Err(error::forbidden_error!(
+                    "Please verify your email address before signing in.",
+                    "email_not_verified"
+                ));
+            }
+            _ => {
+                if let Some(lockout) = &self.lockout {
+                    lockout.record_failure(&credential.email, &client.ip, now)?;
+                }
+                return Err(invalid_credential());
+            }
+        };
+
+        let risk = match &self.risk {
+            Some(engine) => {
+                let recent_failures = match &self.lockout {
+                    Some(lockout) => lockout.failures(&credential.email)?,
+                    None => 0,
+                };
+                Some(engine.assess(&account, client, recent_failures, now)?)
+            }
+            None => None,
+        };
+        let decision = risk.as_ref().map(|event| event.decision());
+        if decision == Some(RiskDecision::Block) {
+            return Err(error::forbidden_error!(
+                "Sorry, this sign-in attempt looks suspicious and has been blocked. Please check your email.",
+                "login_blocked"
+            ));
+        }
+
+        // Kegagalan sebelumnya tetap dihitung hingga step up yang diminta risk engine diselesaikan
+        if let (Some(lockout), false) = (&self.lockout, decision == Some(RiskDecision::StepUp)) {
+            lockout.record_success(&credential.email)?;
+        }
+
+        // Browser yang diingat melewati MFA, kecuali risk engine meminta step up
+        let trusted = account.is_mfa_enabled()
+            && decision != Some(RiskDecision::StepUp)
+            && client
+                .trusted_device
+                .as_deref()
+                .is_some_and(|digest| account.use_trusted_device(digest, now));
+        if trusted {
+            self.accounts.update(&account)?;
+        }
+
+        let scope =
+            if (account.is_mfa_enabled() && !trusted) || decision == Some(RiskDecision::StepUp) {
+                AuthenticationScope::StepUp
+            } else if account.requires_password_change(self.policy.max_age, now) {
+                AuthenticationScope::PasswordChange
+            } else {
+                AuthenticationScope::Full
+            };
+
+        let mut session = Session::new(account.id(), scope, self.session_ttl);
+        session.record_authentication(AuthenticationMethod::Password, now);
+        if let Some(event) = &risk {
+            session.link_login_event(event.id());
+        }
+        self.sessions.create(&session)?;
+
+        Ok(Authentication {
+            account,
+            session,
+            risk,
+        })
+    }
+
+    /**
+    Memverifikasi ulang password user yang sedang login, lalu memperbarui waktu autentikasi
+    session agar operasi sensitif dapat dilakukan. Kegagalan dihitung oleh lockout seperti
+    kegagalan login.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `request` - Password saat ini
+    * `client` - Informasi client yang melakukan autentikasi ulang
+    */
+    pub fn reauthenticate(
+        &self,
+        session: &mut Session,
+        request: &Reauthentication,
+        client: &ClientInfo,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let account = match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(error::unauthorized_error!()),
+        };
+
+        let now = get_now();
+        if let Some(lockout) = &self.lockout {
+            lockout.ensure_not_locked(account.email(), &client.ip, now)?;
+        }
+
+        if !account
+            .hash()
+            .verify_password(&request.password)
+            .unwrap_or(false)
+        {
+            if let Some(lockout) = &self.lockout {
+                lockout.record_failure(account.email(), &client.ip, now)?;
+            }
+            return Err(error::unauthorized_error!(
+                "Sorry, but your password is incorrect.",
+                "invalid_credential"
+            ));
+        }
+
+        if let Some(lockout) = &self.lockout {
+            lockout.record_success(account.email())?;
+        }
+        session.record_authentication(AuthenticationMethod::Password, now);
+        self.sessions.update(session)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{rate_limit::RateLimitConfig, store::MemoryStore};
+    use super::super::account::AccountRole;
+    use super::super::lockout::LockoutPolicy;
+    use super::super::repository::MockAccountRepository;
+    use super::super::risk::{MockLoginEventRepository, RiskPolicy};
+    use super::super::session::{AssuranceLevel, MockSessionRepository};
+    use super::*;
+    use uuid::Uuid;
+
+    const KEY: &str = "$6$G/gkPn17kHYo0gTF$";
+    const PASSWORD: &str = "MypassworD1234!";
+
+    fn credential(password: &str) -> Credential {
+        serde_json::from_str(&format!(
+            r#"{{"email": "harun@digitalsekuriti.id", "password": "{}"}}"#,
+            password
+        ))
+        .unwrap()
+    }
+
+    fn client() -> ClientInfo {
+        ClientInfo::new("10.0.0.1".parse().unwrap())
+    }
+
+    fn sessions() -> MockSessionRepository {
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_create().returning(|_| Ok(()));
+        sessions
+    }
+
+    fn account() -> Account {
+        Account::new(
+            Email::from("harun@digitalsekuriti.id"),
+            Hash::from_password(KEY, &Password::from(PASSWORD)).unwrap(),
+            AccountRole::User,
+        )
+    }
+
+    #[test]
+    fn test_login() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let policy = PasswordPolicy::default();
+
+        let authentication = AuthenticationService::new(&accounts, &sessions(), &policy)
+            .login(&credential(PASSWORD), &client())
+            .unwrap();
+
+        assert_eq!(AuthenticationScope::Full, authentication.session.scope());
+        assert_eq!(
+            authentication.account.id(),
+            authentication.session.account_id()
+        );
+        assert!(authentication.session.ensure_full_access().is_ok());
+        assert_eq!(
+            &[AuthenticationMethod::Password],
+            authentication.session.amr()
+        );
+    }
+
+    #[test]
+    fn test_reauthenticate() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(|_| Ok(Some(account())));
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().times(1).returning(|_| Ok(()));
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy {
+            account_threshold: 1,
+            ..LockoutPolicy::default()
+        };
+        let service = AuthenticationService::new(&accounts, &sessions, &policy)
+            .with_lockout(LockoutGuard::new(&store, &lockout_policy));
+
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        session.record_authentication(AuthenticationMethod::Password, 0);
+        assert!(session
+            .ensure_recent_authentication(300, AssuranceLevel::SingleFactor, get_now())
+            .is_err());
+
+        let reauthentication = |password: &str| Reauthentication {
+            password: Password::from(password),
+        };
+        service
+            .reauthenticate(&mut session, &reauthentication(PASSWORD), &client())
+            .unwrap();
+        assert!(session
+            .ensure_recent_authentication(300, AssuranceLevel::SingleFactor, get_now())
+            .is_ok());
+
+        let error = service
+            .reauthenticate(&mut session, &reauthentication("WrongPassw0rd!"), &client())
+            .unwrap_err();
+        assert_eq!("invalid_credential", error.error);
+        // Kegagalan autentikasi ulang ikut dihitung oleh lockout
+        let error = service
+            .reauthenticate(&mut session, &reauthentication(PASSWORD), &client())
+            .unwrap_err();
+        assert_eq!(429, error.code);
+    }
+
+    #[test]
+    fn test_reauthenticate_with_password_keeps_second_factor_stale() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(|_| Ok(Some(account())));
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().times(1).returning(|_| Ok(()));
+        let policy = PasswordPolicy::default();
+        let service = AuthenticationService::new(&accounts, &sessions, &policy);
+
+        // Login dengan password dan TOTP yang sudah melewati batas usia autentikasi
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        session.record_authentication(AuthenticationMethod::Password, 0);
+        session.record_authentication(AuthenticationMethod::Otp, 0);
+        assert_eq!(AssuranceLevel::MultiFactor, session.acr());
+
+        service
+            .reauthenticate(
+                &mut session,
+                &Reauthentication {
+                    password: Password::from(PASSWORD),
+                },
+                &client(),
+            )
+            .unwrap();
+        let error = session
+            .ensure_recent_authentication(300, AssuranceLevel::MultiFactor, get_now())
+            .unwrap_err();
+        assert_eq!("mfa_required", error.error);
+    }
+
+    #[test]
+    fn test_login_with_wrong_password() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let policy = PasswordPolicy::default();
+
+        let error = AuthenticationService::new(&accounts, &sessions(), &policy)
+            .login(&credential("WrongPassw0rd!"), &client())
+            .unwrap_err();
+
+        assert_eq!(401, error.code);
+        assert_eq!("invalid_credential", error.error);
+    }
+
+    #[test]
+    fn test_login_with_unknown_email() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_email().returning(|_| Ok(None));
+        let policy = PasswordPolicy::default();
+
+        let error = AuthenticationService::new(&accounts, &sessions(), &policy)
+            .login(&credential(PASSWORD), &client())
+            .unwrap_err();
+
+        assert_eq!("invalid_credential", error.error);
+    }
+
+    #[test]
+    fn test_login_with_expired_password() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let policy = PasswordPolicy {
+            max_age: Some(0),
+            ..PasswordPolicy::default()
+        };
+
+        let authentication = AuthenticationService::new(&accounts, &sessions(), &policy)
+            .login(&credential(PASSWORD), &client())
+            .unwrap();
+
+        assert_eq!(
+            AuthenticationScope::PasswordChange,
+            authentication.session.scope()
+        );
+        let error = authentication.session.ensure_full_access().unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("password_change_required", error.error);
+    }
+
+    #[test]
+    fn test_login_with_forced_password_change() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_email().returning(|_| {
+            let mut account = account();
+            account.require_password_change();
+            Ok(Some(account))
+        });
+        let policy = PasswordPolicy::default();
+
+        let authentication = AuthenticationService::new(&accounts, &sessions(), &policy)
+            .login(&credential(PASSWORD), &client())
+            .unwrap();
+
+        assert_eq!(
+            AuthenticationScope::PasswordChange,
+            authentication.session.scope()
+        );
+    }
+
+    #[test]
+    fn test_login_with_deleted_account() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_email().returning(|_| {
+            let payload = format!(
+                r#"{{"_id": "61279487-2eab-406c-9265-c6985dcbc3be", "email": "harun@digitalsekuriti.id", "hash": "{}", "role": "user", "status": "deleted", "created_at": 0, "updated_at": 0}}"#,
+                account().hash().expose_secret()
+            );
+            Ok(Some(serde_json::from_str(&payload).unwrap()))
+        });
+        let policy = PasswordPolicy::default();
+
+        let error = AuthenticationService::new(&accounts, &sessions(), &policy)
+            .login(&credential(PASSWORD), &client())
+            .unwrap_err();
+
+        assert_eq!("invalid_credential", error.error);
+    }
+
+    #[test]
+    fn test_login_with_unverified_email() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_email().returning(|_| {
+            let payload = format!(
+                r#"{{"_id": "61279487-2eab-406c-9265-c6985dcbc3be", "email": "harun@digitalsekuriti.id", "hash": "{}", "role": "user", "status": "pending_verification", "created_at": 0, "updated_at": 0}}"#,
+                account().hash().expose_secret()
+            );
+            Ok(Some(serde_json::from_str(&payload).unwrap()))
+        });
+        let policy = PasswordPolicy::default();
+        let sessions = sessions();
+        let service = AuthenticationService::new(&accounts, &sessions, &policy);
+
+        let error = service.login(&credential(PASSWORD), &client()).unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("email_not_verified", error.error);
+
+        // Password yang salah tetap mendapatkan error yang sama dengan account lain
+        let error = service
+            .login(&credential("WrongPassw0rd!"), &client())
+            .unwrap_err();
+        assert_eq!("invalid_credential", error.error);
+    }
+
+    #[test]
+    fn test_uniform_error_response() {
+        let mut known = MockAccountRepository::new();
+        known
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let mut unknown = MockAccountRepository::new();
+        unknown.expect_get_by_email().returning(|_| Ok(None));
+        let policy = PasswordPolicy::default();
+
+        let wrong_password = AuthenticationService::new(&known, &sessions(), &policy)
+            .login(&credential("WrongPassw0rd!"), &client())
+            .unwrap_err();
+        let wrong_email = AuthenticationService::new(&unknown, &sessions(), &policy)
+            .login(&credential(PASSWORD), &client())
+            .unwrap_err();
+
+        assert_eq!(wrong_password.to_string(), wrong_email.to_string());
+        assert_eq!(wrong_password.code, wrong_email.code);
+    }
+
+    #[test]
+    fn test_login_with_lockout() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy {
+            account_threshold: 2,
+            ..LockoutPolicy::default()
+        };
+        let sessions = sessions();
+        let service = AuthenticationService::new(&accounts, &sessions, &policy)
+            .with_lockout(LockoutGuard::new(&store, &lockout_policy));
+
+        for _ in 0..2 {
+            let error = service
+                .login(&credential("WrongPassw0rd!"), &client())
+                .unwrap_err();
+            assert_eq!("invalid_credential", error.error);
+        }
+
+        // Password yang benar tetap ditolak selama account terkunci
+        let error = service.login(&credential(PASSWORD), &client()).unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!("account_locked", error.error);
+
+        LockoutGuard::new(&store, &lockout_policy)
+            .unlock(&Email::from("harun@digitalsekuriti.id"))
+            .unwrap();
+        assert!(service.login(&credential(PASSWORD), &client()).is_ok());
+    }
+
+    #[test]
+    fn test_login_with_rate_limiter() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .times(2)
+            .returning(|_| Ok(Some(account())));
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let rate_limit_config: RateLimitConfig = serde_json::from_str(
+            r#"{"routes": {"POST /sessions": {"algorithm": "token_bucket", "key": "ip", "limit": 2, "window": 60}}}"#,
+        )
+        .unwrap();
+        let sessions = sessions();
+        let service = AuthenticationService::new(&accounts, &sessions, &policy)
+            .with_rate_limiter(RateLimiter::new(&store, &rate_limit_config));
+
+        assert!(service.login(&credential(PASSWORD), &client()).is_ok());
+        assert!(service
+            .login(&credential("WrongPassw0rd!"), &client())
+            .is_err());
+
+        // Kredensial tidak diverifikasi setelah limit tercapai
+        let error = service.login(&credential(PASSWORD), &client()).unwrap_err();
+        assert_eq!(429, error.code);
+        assert!(error
+            .headers
+            .iter()
+            .any(|(name, value)| *name == "Retry-After"
+                && (1..=30).contains(&value.parse::<u64>().unwrap())));
+        assert!(error
+            .headers
+            .iter()
+            .any(|(name, value)| *name == "RateLimit-Remaining" && value == "0"));
+    }
+
+    #[test]
+    fn test_login_with_risk_engine() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let mut events = MockLoginEventRepository::new();
+        events.expect_recent().returning(|_, _| Ok(Vec::new()));
+        events.expect_create().times(2).returning(|_| Ok(()));
+        let policy = PasswordPolicy::default();
+        let sessions = sessions();
+
+        let risk_policy = RiskPolicy {
+            step_up_threshold: 10,
+            ..RiskPolicy::default()
+        };
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = AuthenticationService::new(&accounts, &sessions, &policy)
+            .with_lockout(LockoutGuard::new(&store, &lockout_policy))
+            .with_risk_engine(RiskEngine::new(&events, &risk_policy));
+
+        let authentication = service.login(&credential(PASSWORD), &client()).unwrap();
+        assert_eq!(AuthenticationScope::Full, authentication.session.scope());
+        assert_eq!(RiskDecision::Allow, authentication.risk.unwrap().decision());
+
+        // Kegagalan login sebelumnya menaikkan skor risiko
+        let _ = service.login(&credential("WrongPassw0rd!"), &client());
+        let authentication = service.login(&credential(PASSWORD), &client()).unwrap();
+        assert_eq!(AuthenticationScope::StepUp, authentication.session.scope());
+        let event = authentication.risk.unwrap();
+        assert_eq!(Some(event.id()), authentication.session.login_event());
+
+        // Login yang membutuhkan step up tidak menghapus kegagalan sebelumnya
+        let email = Email::from("harun@digitalsekuriti.id");
+        let guard = LockoutGuard::new(&store, &lockout_policy);
+        assert_eq!(1, guard.failures(&email).unwrap());
+    }
+
+    #[test]
+    fn test_login_with_trusted_device() {
+        const TOKEN: &str = "trusted-device-token";
+        let mfa_account = |expires_at: u64| {
+            let mut account = account();
+            account.set_phone(Some(
+                serde_json::from_value(
+                    serde_json::json!({"number": "+6281234567890", "verified_at": 1}),
+                )
+                .unwrap(),
+            ));
+            let device = serde_json::json!({
+                "id": Uuid::new_v4(),
+                "digest": Token::from(TOKEN).digest(),
+                "name": "Firefox",
+                "created_at": 0,
+                "expires_at": expires_at,
+                "last_used_at": null
+            });
+            account.add_trusted_device(serde_json::from_value(device).unwrap(), 10, 0);
+            account
+        };
+        let policy = PasswordPolicy::default();
+        let sessions = sessions();
+
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(move |_| Ok(Some(mfa_account(u64::MAX))));
+        accounts
+            .expect_update()
+            .withf(|account| account.trusted_devices()[0].last_used_at().is_some())
+            .times(1)
+            .returning(|_| Ok(()));
+        let service = AuthenticationService::new(&accounts, &sessions, &policy);
+
+        let authentication = service
+            .login(&credential(PASSWORD), &client().with_trusted_device(TOKEN))
+            .unwrap();
+        assert_eq!(AuthenticationScope::Full, authentication.session.scope());
+
+        let authentication = service
+            .login(
+                &credential(PASSWORD),
+                &client().with_trusted_device("other-token"),
+            )
+            .unwrap();
+        assert_eq!(AuthenticationScope::StepUp, authentication.session.scope());
+
+        // Trusted device yang sudah expired tidak melewati MFA
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(move |_| Ok(Some(mfa_account(1))));
+        let authentication = AuthenticationService::new(&accounts, &sessions, &policy)
+            .login(&credential(PASSWORD), &client().with_trusted_device(TOKEN))
+            .unwrap();
+        assert_eq!(AuthenticationScope::StepUp, authentication.session.scope());
+    }
+
+    #[test]
+    fn test_login_blocked_by_risk_engine() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let mut events = MockLoginEventRepository::new();
+        events.expect_recent().returning(|_, _| Ok(Vec::new()));
+        events.expect_create().times(1).returning(|_| Ok(()));
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_create().times(0);
+        let policy = PasswordPolicy::default();
+        let risk_policy = RiskPolicy {
+            block_threshold: 0,
+            ..RiskPolicy::default()
+        };
+
+        let error = AuthenticationService::new(&accounts, &sessions, &policy)
+            .with_risk_engine(RiskEngine::new(&events, &risk_policy))
+            .login(&credential(PASSWORD), &client())
+            .unwrap_err();
+
+        assert_eq!(403, error.code);
+        assert_eq!("login_blocked", error.error);
+    }
+
+    // Durasi satu kali percobaan login yang gagal
+    fn login_duration(service: &AuthenticationService, credential: &Credential) -> f64 {
+        let start = std::time::Instant::now();
+        let _ = service.login(credential, &client());
+        start.elapsed().as_secs_f64()
+    }
+
+    fn median(mut durations: Vec<f64>) -> f64 {
+        durations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        durations[durations.len() / 2]
+    }
+
+    #[test]
+    fn test_comparable_timing_for_unknown_email() {
+        let mut known = MockAccountRepository::new();
+        known
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+        let mut unknown = MockAccountRepository::new();
+        unknown.expect_get_by_email().returning(|_| Ok(None));
+        let policy = PasswordPolicy::default();
+        let sessions = sessions();
+        let known = AuthenticationService::new(&known, &sessions, &policy);
+        let unknown = AuthenticationService::new(&unknown, &sessions, &policy);
+        let credential = credential("WrongPassw0rd!");
+
+        // Pemanasan agar dummy hash sudah terbentuk sebelum pengukuran
+        login_duration(&unknown, &credential);
+
+        // Pengukuran dilakukan bergantian agar beban mesin mempengaruhi kedua distribusi secara sama.
+        // Tanpa dummy hash, email yang tidak terdaftar ribuan kali lebih cepat.
+        let (mut known_durations, mut unknown_durations) = (Vec::new(), Vec::new());
+        for _ in 0..15 {
+            known_durations.push(login_duration(&known, &credential));
+            unknown_durations.push(login_duration(&unknown, &credential));
+        }
+        let (known_median, unknown_median) = (median(known_durations), median(unknown_durations));
+        let ratio = unknown_median / known_median;
+
+        assert!(
+            (0.33..3.0).contains(&ratio),
+            "known: {}s, unknown: {}s",
+            known_median,
+            unknown_median
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use super::super::sharedkernel::{error, function::get_now};
+use super::account::AccountStatus;
+use super::repository::AccountRepository;
+use super::session::{
+    AssuranceLevel, Session, SessionRepository, DEFAULT_REAUTHENTICATION_MAX_AGE,
+};
+
+/// Use case penghapusan account oleh pemiliknya
+pub struct AccountDeletionService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    reauthentication_max_age: u64,
+}
+
+impl<'a> AccountDeletionService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+    ) -> AccountDeletionService<'a> {
+        AccountDeletionService {
+            accounts,
+            sessions,
+            reauthentication_max_age: DEFAULT_REAUTHENTICATION_MAX_AGE,
+        }
+    }
+
+    /// Mengganti batas usia autentikasi terakhir (dalam detik) untuk menghapus account
+    pub fn with_reauthentication_max_age(mut self, max_age: u64) -> AccountDeletionService<'a> {
+        self.reauthentication_max_age = max_age;
+        self
+    }
+
+    /**
+    Menghapus account milik user yang sedang login lalu mencabut seluruh session-nya. Session harus
+    baru saja diautentikasi (dengan dua faktor jika MFA aktif), jika tidak error
+    `reauthentication_required` atau `mfa_required` dikembalikan.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    */
+    pub fn delete(&self, session: &Session) -> Result<(), error::ApplicationError<'static>> {
+        let mut account = match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(error::unauthorized_error!()),
+        };
+        session.ensure_recent_authentication(
+            self.reauthentication_max_age,
+            AssuranceLevel::required_for(&account),
+            get_now(),
+        )?;
+
+        account.delete();
+        self.accounts.update(&account)?;
+        self.sessions.revoke_all(account.id(), None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{email::Email, password::Hash};
+    use super::super::account::{Account, AccountRole};
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::{AuthenticationMethod, AuthenticationScope, MockSessionRepository};
+    use super::*;
+    use uuid::Uuid;
+
+    fn account() -> Account {
+        Account::new(
+            Email::from("harun@digitalsekuriti.id"),
+            Hash::from("hash"),
+            AccountRole::User,
+        )
+    }
+
+    #[test]
+    fn test_delete_requires_recent_authentication() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(|_| Ok(Some(account())));
+        accounts
+            .expect_update()
+            .withf(|account| *account.status() == AccountStatus::Deleted)
+            .times(1)
+            .returning(|_| Ok(()));
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all()
+            .withf(|_, except| except.is_none())
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let service = AccountDeletionService::new(&accounts, &sessions);
+
+        let mut stale = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        stale.record_authentication(AuthenticationMethod::Password, 0);
+        let error = service.delete(&stale).unwrap_err();
+        assert_eq!("reauthentication_required", error.error);
+
+        let step_up = Session::new(&Uuid::new_v4(), AuthenticationScope::StepUp, 60);
+        let error = service.delete(&step_up).unwrap_err();
+        assert_eq!("step_up_required", error.error);
+
+        let session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        service.delete(&session).unwrap();
+    }
+
+    #[test]
+    fn test_delete_requires_second_factor_when_mfa_enabled() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_id().returning(|_| {
+            let mut account = account();
+            account.set_phone(Some(
+                serde_json::from_value(
+                    serde_json::json!({"number": "+6281234567890", "verified_at": 1}),
+                )
+                .unwrap(),
+            ));
+            Ok(Some(account))
+        });
+        accounts.expect_update().times(1).returning(|_| Ok(()));
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all()
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let service = AccountDeletionService::new(&accounts, &sessions);
+
+        // Login dari trusted device hanya menggunakan password
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        session.record_authentication(AuthenticationMethod::Password, get_now());
+        let error = service.delete(&session).unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("mfa_required", error.error);
+
+        session.record_authentication(AuthenticationMethod::Sms, get_now());
+        service.delete(&session).unwrap();
+    }
+}
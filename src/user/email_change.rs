@@ -0,0 +1,543 @@
+use super::super::sharedkernel::{
+    email::Email,
+    error,
+    function::get_now,
+    mailer::{Mailer, Message},
+    store::KeyValueStore,
+    token::Token,
+};
+use super::account::{Account, AccountStatus};
+use super::repository::AccountRepository;
+use super::session::{
+    AssuranceLevel, Session, SessionRepository, DEFAULT_REAUTHENTICATION_MAX_AGE,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+// Default masa berlaku link konfirmasi email baru (dalam detik)
+pub const DEFAULT_CONFIRMATION_TTL: u64 = 3600;
+// Default masa berlaku link pembatalan yang dikirim ke email lama (dalam detik)
+pub const DEFAULT_UNDO_TTL: u64 = 7 * 86400;
+
+/// Permintaan ganti email oleh user yang sedang login
+#[derive(Debug, Deserialize)]
+pub struct EmailChange {
+    email: Email,
+}
+
+/// Token dari link konfirmasi maupun link pembatalan ganti email
+#[derive(Debug, Deserialize)]
+pub struct EmailChangeToken {
+    token: Token,
+}
+
+/// Permintaan ganti email yang tersimpan, dengan key digest dari token
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingChange {
+    account_id: Uuid,
+    old_email: Email,
+    new_email: Email,
+}
+
+fn invalid_token() -> error::ApplicationError<'static> {
+    error::bad_request_error!(
+        "Sorry, but your email change link is invalid or has expired.",
+        "invalid_email_change_token"
+    )
+}
+
+fn email_error(code: &'static str) -> error::ApplicationError<'static> {
+    let mut errors = ValidationErrors::new();
+    errors.add("email", ValidationError::new(code));
+    error::ApplicationError::from_validation_errors(errors)
+}
+
+fn with_token(url: &str, token: &Token) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}token={}", url, separator, token.expose_secret())
+}
+
+/**
+Use case ganti email. Email baru harus dikonfirmasi melalui link yang dikirim ke email baru, dan
+email lama menerima pemberitahuan dengan link untuk membatalkan atau mengembalikan perubahan.
+*/
+pub struct EmailChangeService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    store: &'a dyn KeyValueStore,
+    mailer: &'a dyn Mailer,
+    confirm_url: String,
+    undo_url: String,
+    confirmation_ttl: u64,
+    undo_ttl: u64,
+    reauthentication_max_age: u64,
+}
+
+impl<'a> EmailChangeService<'a> {
+    /**
+    Mengembalikan service ganti email
+
+    # Arguments
+    * `confirm_url` - URL halaman konfirmasi email baru, token ditambahkan sebagai query `token`
+    * `undo_url` - URL halaman pembatalan ganti email, token ditambahkan sebagai query `token`
+    */
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        store: &'a dyn KeyValueStore,
+        mailer: &'a dyn Mailer,
+        confirm_url: &str,
+        undo_url: &str,
+    ) -> EmailChangeService<'a> {
+        EmailChangeService {
+            accounts,
+            sessions,
+            store,
+            mailer,
+            confirm_url: String::from(confirm_url),
+            undo_url: String::from(undo_url),
+            confirmation_ttl: DEFAULT_CONFIRMATION_TTL,
+            undo_ttl: DEFAULT_UNDO_TTL,
+            reauthentication_max_age: DEFAULT_REAUTHENTICATION_MAX_AGE,
+        }
+    }
+
+    /// Mengganti masa berlaku link konfirmasi dan link pembatalan (dalam detik)
+    pub fn with_token_ttl(
+        mut self,
+        confirmation_ttl: u64,
+        undo_ttl: u64,
+    ) -> EmailChangeService<'a> {
+        self.confirmation_ttl = confirmation_ttl;
+        self.undo_ttl = undo_ttl;
+        self
+    }
+
+    /// Mengganti batas usia autentikasi terakhir (dalam detik) untuk meminta ganti email
+    pub fn with_reauthentication_max_age(mut self, max_age: u64) -> EmailChangeService<'a> {
+        self.reauthentication_max_age = max_age;
+        self
+    }
+
+    fn confirm_key(digest: &str) -> String {
+        format!("email_change:confirm:{}", digest)
+    }
+
+    fn undo_key(digest: &str) -> String {
+        format!("email_change:undo:{}", digest)
+    }
+
+    fn account_key(account_id: &Uuid) -> String {
+        format!("email_change:account:{}", account_id)
+    }
+
+    fn ensure_available(&self, email: &Email) -> Result<(), error::ApplicationError<'static>> {
+        match self.accounts.get_by_email(email)? {
+            Some(_) => Err(email_error("email_taken")),
+            None => Ok(()),
+        }
+    }
+
+    /// Mengambil dan menghapus token secara atomic, sehingga link hanya dapat digunakan sekali
+    fn take(&self, key: &str) -> Result<PendingChange, error::ApplicationError<'static>> {
+        match self.store.take(key)? {
+            Some(value) => serde_json::from_str(&value).map_err(|_| invalid_token()),
+            None => Err(invalid_token()),
+        }
+    }
+
+    fn account(&self, account_id: &Uuid) -> Result<Account, error::ApplicationError<'static>> {
+        match self.accounts.get_by_id(&account_id.to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => Ok(account),
+            _ => Err(invalid_token()),
+        }
+    }
+
+    /// Membatalkan link konfirmasi yang masih berlaku milik account
+    fn cancel_pending(&self, account_id: &Uuid) -> Result<(), error::ApplicationError<'static>> {
+        let account_key = Self::account_key(account_id);
+        if let Some(previous) = self.store.get(&account_key)? {
+            self.store.delete(&Self::confirm_key(&previous))?;
+            self.store.delete(&account_key)?;
+        }
+        Ok(())
+    }
+
+    /**
+    Memulai ganti email : mengirim link konfirmasi ke email baru dan pemberitahuan dengan link
+    pembatalan ke email lama. Email account belum diganti hingga link konfirmasi dibuka. Session
+    harus baru saja diautentikasi, dengan dua faktor jika MFA aktif.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `request` - Email yang baru
+    */
+    pub fn request(
+        &self,
+        session: &Session,
+        request: &EmailChange,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let account = match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(error::unauthorized_error!()),
+        };
+        session.ensure_recent_authentication(
+            self.reauthentication_max_age,
+            AssuranceLevel::required_for(&account),
+            get_now(),
+        )?;
+
+        if let Err(errors) = ValidationErrors::merge(Ok(()), "email", request.email.validate()) {
+            return Err(error::ApplicationError::from_validation_errors(errors));
+        }
+        if account.email().to_string().to_lowercase() == request.email.to_string().to_lowercase() {
+            return Err(email_error("email_unchanged"));
+        }
+        self.ensure_available(&request.email)?;
+
+        // Hanya permintaan terakhir yang dapat dikonfirmasi
+        self.cancel_pending(account.id())?;
+
+        let pending = PendingChange {
+            account_id: *account.id(),
+            old_email: account.email().clone(),
+            new_email: request.email.clone(),
+        };
+        let value = serde_json::to_string(&pending).map_err(|_| error::internal_server_error!())?;
+        let confirm_token = Token::generate();
+        let undo_token = Token::generate();
+        self.store.set(
+            &Self::confirm_key(&confirm_token.digest()),
+            &value,
+            self.confirmation_ttl,
+        )?;
+        self.store.set(
+            &Self::account_key(account.id()),
+            &confirm_token.digest(),
+            self.confirmation_ttl,
+        )?;
+        self.store
+            .set(&Self::undo_key(&undo_token.digest()), &value, self.undo_ttl)?;
+
+        self.mailer.send(&Message::new(
+            &request.email,
+            "Confirm your new email address",
+            &format!(
+                "We received a request to use this address for your account. Open the following link within {} minutes to confirm the change:\n\n{}\n\nIf you did not request this, you can safely ignore this email.",
+                self.confirmation_ttl / 60,
+                with_token(&self.confirm_url, &confirm_token)
+            ),
+        ))?;
+        // Pemberitahuan bersifat best-effort, perubahan belum terjadi sebelum dikonfirmasi
+        let _ = self.mailer.send(&Message::new(
+            account.email(),
+            "Your email address is being changed",
+            &format!(
+                "A request was made to change the email address of your account to {}. If this wasn't you, open the following link to cancel the change and sign out all sessions:\n\n{}",
+                request.email,
+                with_token(&self.undo_url, &undo_token)
+            ),
+        ));
+
+        Ok(())
+    }
+
+    /**
+    Mengganti email account setelah link konfirmasi dibuka dari email baru. Link hanya dapat
+    digunakan sekali.
+
+    # Arguments
+    * `confirmation` - Token dari link konfirmasi
+    */
+    pub fn confirm(
+        &self,
+        confirmation: &EmailChangeToken,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let pending = self.take(&Self::confirm_key(&confirmation.token.digest()))?;
+        self.store.delete(&Self::account_key(&pending.account_id))?;
+
+        let mut account = self.account(&pending.account_id)?;
+        if account.email() != &pending.old_email {
+            return Err(invalid_token());
+        }
+        // Email mungkin sudah digunakan account lain sejak permintaan dibuat
+        self.ensure_available(&pending.new_email)?;
+
+        account.change_email(pending.new_email, get_now());
+        self.accounts.update(&account)
+    }
+
+    /**
+    Membatalkan ganti email melalui link yang dikirim ke email lama. Jika email sudah diganti,
+    email lama dikembalikan. Permintaan kemungkinan dibuat oleh orang lain yang mengetahui
+    password, sehingga seluruh session dicabut, seluruh trusted device dihapus dan password wajib
+    diganti pada login berikutnya.
+
+    Jika email lama sudah digunakan account lain, account tetap diamankan namun email tidak
+    dikembalikan dan error `email_taken` dikembalikan.
+
+    # Arguments
+    * `request` - Token dari link pembatalan
+    */
+    pub fn undo(&self, request: &EmailChangeToken) -> Result<(), error::ApplicationError<'static>> {
+        let pending = self.take(&Self::undo_key(&request.token.digest()))?;
+        self.cancel_pending(&pending.account_id)?;
+
+        let mut account = self.account(&pending.account_id)?;
+        let restored = if account.email() == &pending.new_email {
+            let available = self.ensure_available(&pending.old_email);
+            if available.is_ok() {
+                account.change_email(pending.old_email, get_now());
+            }
+            available
+        } else {
+            Ok(())
+        };
+        account.clear_trusted_devices();
+        account.require_password_change();
+        self.accounts.update(&account)?;
+        self.sessions.revoke_all(account.id(), None)?;
+        restored
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{
+        mailer::capture::CapturingMailer, password::Hash, store::MemoryStore,
+    };
+    use super::super::account::AccountRole;
+    use super::super::fixture;
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::{AuthenticationMethod, AuthenticationScope, MockSessionRepository};
+    use super::super::trusted_device::TrustedDevice;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const CONFIRM_URL: &str = "https://brokoli.id/email/confirm";
+    const UNDO_URL: &str = "https://brokoli.id/email/undo";
+    const TAKEN: &str = "taken@digitalsekuriti.id";
+
+    fn repository() -> (MockAccountRepository, Arc<Mutex<Account>>) {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .withf(|email| email.to_string() == TAKEN)
+            .returning(|email| {
+                Ok(Some(Account::new(
+                    email.clone(),
+                    Hash::from("hash"),
+                    AccountRole::User,
+                )))
+            });
+        let stored = fixture::expect_account(
+            &mut accounts,
+            Account::new(
+                Email::from(fixture::EMAIL),
+                Hash::from("hash"),
+                AccountRole::User,
+            ),
+        );
+        (accounts, stored)
+    }
+
+    fn token(message: &Message) -> EmailChangeToken {
+        let (_, token) = message.body.split_once("token=").unwrap();
+        let token = token.split_whitespace().next().unwrap();
+        serde_json::from_str(&format!(r#"{{"token": "{}"}}"#, token)).unwrap()
+    }
+
+    fn change(email: &str) -> EmailChange {
+        serde_json::from_str(&format!(r#"{{"email": "{}"}}"#, email)).unwrap()
+    }
+
+    fn trusted_device() -> TrustedDevice {
+        serde_json::from_value(serde_json::json!({
+            "id": Uuid::new_v4(),
+            "digest": "digest",
+            "name": "Firefox",
+            "created_at": 0,
+            "expires_at": u64::MAX,
+            "last_used_at": null
+        }))
+        .unwrap()
+    }
+
+    fn session(account: &Account) -> Session {
+        Session::new(account.id(), AuthenticationScope::Full, 60)
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let (accounts, stored) = repository();
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service =
+            EmailChangeService::new(&accounts, &sessions, &store, &mailer, CONFIRM_URL, UNDO_URL);
+        let session = session(&stored.lock().unwrap());
+
+        let field_error = |email: &str| {
+            let error = service.request(&session, &change(email)).unwrap_err();
+            error.fields.unwrap()["email"][0].clone()
+        };
+        assert!(field_error("not-an-email").contains("email"));
+        assert!(field_error("HARUN@digitalsekuriti.id").contains("email_unchanged"));
+        assert!(field_error(TAKEN).contains("email_taken"));
+
+        let mut stale = Session::new(stored.lock().unwrap().id(), AuthenticationScope::Full, 60);
+        stale.record_authentication(AuthenticationMethod::Password, 0);
+        let error = service
+            .request(&stale, &change("new@digitalsekuriti.id"))
+            .unwrap_err();
+        assert_eq!("reauthentication_required", error.error);
+        assert!(mailer.sent().is_empty());
+    }
+
+    #[test]
+    fn test_request_and_confirm() {
+        let (accounts, stored) = repository();
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service =
+            EmailChangeService::new(&accounts, &sessions, &store, &mailer, CONFIRM_URL, UNDO_URL);
+        let session = session(&stored.lock().unwrap());
+
+        service
+            .request(&session, &change("first@digitalsekuriti.id"))
+            .unwrap();
+        service
+            .request(&session, &change("new@digitalsekuriti.id"))
+            .unwrap();
+        {
+            let sent = mailer.sent();
+            assert_eq!(4, sent.len());
+            assert_eq!("new@digitalsekuriti.id", sent[2].to.to_string());
+            assert!(sent[2].body.contains(CONFIRM_URL));
+            assert_eq!("harun@digitalsekuriti.id", sent[3].to.to_string());
+            assert!(sent[3].body.contains(UNDO_URL));
+        }
+        // Email belum diganti sebelum dikonfirmasi
+        assert_eq!(
+            "harun@digitalsekuriti.id",
+            stored.lock().unwrap().email().to_string()
+        );
+
+        // Hanya permintaan terakhir yang dapat dikonfirmasi
+        let first = token(&mailer.sent()[0]);
+        assert_eq!(
+            "invalid_email_change_token",
+            service.confirm(&first).unwrap_err().error
+        );
+
+        let confirmation = token(&mailer.sent()[2]);
+        service.confirm(&confirmation).unwrap();
+        assert_eq!(
+            "new@digitalsekuriti.id",
+            stored.lock().unwrap().email().to_string()
+        );
+        assert!(stored.lock().unwrap().email_verified_at().is_some());
+        assert_eq!(
+            "invalid_email_change_token",
+            service.confirm(&confirmation).unwrap_err().error
+        );
+    }
+
+    #[test]
+    fn test_undo() {
+        let (accounts, stored) = repository();
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all()
+            .withf(|_, except| except.is_none())
+            .times(2)
+            .returning(|_, _| Ok(()));
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service =
+            EmailChangeService::new(&accounts, &sessions, &store, &mailer, CONFIRM_URL, UNDO_URL);
+        let session = session(&stored.lock().unwrap());
+
+        // Pembatalan sebelum konfirmasi membatalkan link konfirmasi
+        service
+            .request(&session, &change("new@digitalsekuriti.id"))
+            .unwrap();
+        service.undo(&token(&mailer.sent()[1])).unwrap();
+        assert_eq!(
+            "invalid_email_change_token",
+            service
+                .confirm(&token(&mailer.sent()[0]))
+                .unwrap_err()
+                .error
+        );
+
+        // Pembatalan setelah konfirmasi mengembalikan email lama dan mengamankan account
+        service
+            .request(&session, &change("new@digitalsekuriti.id"))
+            .unwrap();
+        service.confirm(&token(&mailer.sent()[2])).unwrap();
+        stored
+            .lock()
+            .unwrap()
+            .add_trusted_device(trusted_device(), 10, 0);
+        let undo = token(&mailer.sent()[3]);
+        service.undo(&undo).unwrap();
+        {
+            let account = stored.lock().unwrap();
+            assert_eq!("harun@digitalsekuriti.id", account.email().to_string());
+            assert!(account.trusted_devices().is_empty());
+            assert!(account.requires_password_change(None, get_now()));
+        }
+        assert_eq!(
+            "invalid_email_change_token",
+            service.undo(&undo).unwrap_err().error
+        );
+    }
+
+    #[test]
+    fn test_undo_when_old_email_taken() {
+        // Email lama sudah didaftarkan account lain setelah email diganti
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .withf(|email| email.to_string() == fixture::EMAIL)
+            .returning(|email| {
+                Ok(Some(Account::new(
+                    email.clone(),
+                    Hash::from("hash"),
+                    AccountRole::User,
+                )))
+            });
+        let stored = fixture::expect_account(
+            &mut accounts,
+            Account::new(
+                Email::from(fixture::EMAIL),
+                Hash::from("hash"),
+                AccountRole::User,
+            ),
+        );
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all()
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service =
+            EmailChangeService::new(&accounts, &sessions, &store, &mailer, CONFIRM_URL, UNDO_URL);
+        let session = session(&stored.lock().unwrap());
+
+        service
+            .request(&session, &change("new@digitalsekuriti.id"))
+            .unwrap();
+        service.confirm(&token(&mailer.sent()[0])).unwrap();
+        let error = service.undo(&token(&mailer.sent()[1])).unwrap_err();
+        assert!(error.fields.unwrap()["email"][0].contains("email_taken"));
+
+        let account = stored.lock().unwrap();
+        assert_eq!("new@digitalsekuriti.id", account.email().to_string());
+        assert!(account.requires_password_change(None, get_now()));
+    }
+}
@@ -0,0 +1,58 @@
+use super::super::sharedkernel::{
+    email::Email,
+    password::{Hash, Password},
+};
+use super::account::{Account, AccountRole};
+use super::repository::MockAccountRepository;
+use std::sync::{Arc, Mutex};
+
+pub const KEY: &str = "$6$G/gkPn17kHYo0gTF$";
+pub const PASSWORD: &str = "MypassworD1234!";
+pub const EMAIL: &str = "harun@digitalsekuriti.id";
+
+/// Account user aktif dengan email `EMAIL` dan password `PASSWORD`
+pub fn account() -> Account {
+    Account::new(
+        Email::from(EMAIL),
+        Hash::from_password(KEY, &Password::from(PASSWORD)).unwrap(),
+        AccountRole::User,
+    )
+}
+
+/**
+Menambahkan expectation `get_by_id`, `get_by_email` dan `update` untuk satu account yang menyimpan
+perubahan terakhir. Expectation lain yang lebih spesifik harus ditambahkan sebelumnya, karena
+mockall menggunakan expectation pertama yang cocok.
+
+# Arguments
+* `accounts` - Mock repository
+* `account` - Account yang disimpan
+*/
+pub fn expect_account(
+    accounts: &mut MockAccountRepository,
+    account: Account,
+) -> Arc<Mutex<Account>> {
+    let stored = Arc::new(Mutex::new(account));
+    let reader = stored.clone();
+    accounts
+        .expect_get_by_id()
+        .returning(move |_| Ok(Some(reader.lock().unwrap().clone())));
+    let reader = stored.clone();
+    accounts.expect_get_by_email().returning(move |email| {
+        let account = reader.lock().unwrap();
+        Ok((account.email() == email).then(|| account.clone()))
+    });
+    let writer = stored.clone();
+    accounts.expect_update().returning(move |account| {
+        *writer.lock().unwrap() = account.clone();
+        Ok(())
+    });
+    stored
+}
+
+/// Repository satu account yang menyimpan perubahan terakhir
+pub fn repository(account: Account) -> (MockAccountRepository, Arc<Mutex<Account>>) {
+    let mut accounts = MockAccountRepository::new();
+    let stored = expect_account(&mut accounts, account);
+    (accounts, stored)
+}
@@ -0,0 +1,473 @@
+use super::super::sharedkernel::{email::Email, error, store::KeyValueStore};
+use serde::Deserialize;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/**
+Aturan penguncian setelah kegagalan login berturut-turut. Setiap penguncian berikutnya
+berlangsung dua kali lebih lama dari sebelumnya, hingga `max_lock_duration`.
+*/
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LockoutPolicy {
+    pub account_threshold: u32,
+    pub ip_threshold: u32,
+    pub lock_duration: u64,
+    pub max_lock_duration: u64,
+    /// Counter kegagalan di-reset jika tidak ada kegagalan baru selama periode ini (dalam detik)
+    pub failure_window: u64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        LockoutPolicy {
+            account_threshold: 5,
+            ip_threshold: 20,
+            lock_duration: 60,
+            max_lock_duration: 86400,
+            failure_window: 900,
+        }
+    }
+}
+
+/**
+Mencatat kegagalan login per account dan per IP, serta menolak login selama terkunci. Account
+diidentifikasi berdasarkan email, sehingga email yang tidak terdaftar juga dapat terkunci dan
+lockout tidak dapat digunakan untuk enumerasi account.
+
+Kode faktor kedua (TOTP dan recovery code) yang salah dihitung terpisah per account, dengan threshold
+dan durasi penguncian yang sama, agar kode 6 digit tidak dapat ditebak dari session `StepUp`.
+
+Setiap subjek (account, IP atau faktor kedua account) memiliki tiga key : counter kegagalan, counter penguncian dan waktu
+berakhirnya penguncian. Counter ditambah secara atomic sehingga request paralel tidak dapat
+melewati threshold.
+*/
+pub struct LockoutGuard<'a> {
+    store: &'a dyn KeyValueStore,
+    policy: &'a LockoutPolicy,
+}
+
+impl<'a> LockoutGuard<'a> {
+    pub fn new(store: &'a dyn KeyValueStore, policy: &'a LockoutPolicy) -> LockoutGuard<'a> {
+        LockoutGuard { store, policy }
+    }
+
+    fn account_key(email: &Email) -> String {
+        format!("lockout:account:{}", email.to_string().to_lowercase())
+    }
+
+    fn ip_key(ip: &IpAddr) -> String {
+        format!("lockout:ip:{}", ip)
+    }
+
+    fn factor_key(account_id: &Uuid) -> String {
+        format!("lockout:mfa:{}", account_id)
+    }
+
+    fn failures_key(key: &str) -> String {
+        format!("{}:failures", key)
+    }
+
+    fn lockouts_key(key: &str) -> String {
+        format!("{}:lockouts", key)
+    }
+
+    fn locked_until_key(key: &str) -> String {
+        format!("{}:locked_until", key)
+    }
+
+    fn counter(&self, key: &str) -> Result<u64, error::ApplicationError<'static>> {
+        Ok(self
+            .store
+            .get(key)?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default())
+    }
+
+    fn is_locked(&self, key: &str, now: u64) -> Result<bool, error::ApplicationError<'static>> {
+        Ok(self.counter(&Self::locked_until_key(key))? > now)
+    }
+
+    /**
+    Menolak login jika IP atau account sedang terkunci
+
+    # Arguments
+    * `email` - Email yang digunakan untuk login
+    * `ip` - Alamat IP client
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn ensure_not_locked(
+        &self,
+        email: &Email,
+        ip: &IpAddr,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if self.is_locked(&Self::ip_key(ip), now)? {
+            return Err(error::too_many_request_error!());
+        }
+        self.ensure_account_not_locked(email, now)
+    }
+
+    /**
+    Menolak autentikasi dengan password jika account sedang terkunci, termasuk saat password
+    dimasukkan kembali sebelum operasi sensitif
+
+    # Arguments
+    * `email` - Email dari account
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn ensure_account_not_locked(
+        &self,
+        email: &Email,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if self.is_locked(&Self::account_key(email), now)? {
+            return Err(error::too_many_request_error!(
+                "Sorry, your account is temporarily locked because of too many failed login attempts. Please try again later.",
+                "account_locked"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /**
+    Mengembalikan jumlah kegagalan login account sejak login berhasil atau penguncian terakhir
+
+    # Arguments
+    * `email` - Email yang digunakan untuk login
+    */
+    pub fn failures(&self, email: &Email) -> Result<u32, error::ApplicationError<'static>> {
+        let failures = self.counter(&Self::failures_key(&Self::account_key(email)))?;
+        let threshold = self.policy.account_threshold.max(1) as u64;
+        Ok((failures % threshold) as u32)
+    }
+
+    /**
+    Menambah counter kegagalan dan mengunci setiap kali counter mencapai kelipatan threshold.
+    Counter tidak di-reset saat terkunci, sehingga dari request paralel hanya satu yang memicu
+    penguncian. Counter penguncian diingat selama `max_lock_duration` ditambah `failure_window`.
+    */
+    fn increment(
+        &self,
+        key: &str,
+        threshold: u32,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let failures = self
+            .store
+            .incr_with_ttl(&Self::failures_key(key), self.policy.failure_window)?;
+        if failures % threshold.max(1) as u64 != 0 {
+            return Ok(());
+        }
+
+        let lockouts = self.store.incr_with_ttl(
+            &Self::lockouts_key(key),
+            self.policy.max_lock_duration + self.policy.failure_window,
+        )?;
+        let multiplier = 2u64.saturating_pow((lockouts - 1).min(u32::MAX as u64) as u32);
+        let duration = self
+            .policy
+            .lock_duration
+            .saturating_mul(multiplier)
+            .min(self.policy.max_lock_duration);
+        self.store.set(
+            &Self::locked_until_key(key),
+            &(now + duration).to_string(),
+            duration,
+        )
+    }
+
+    /// Menghapus seluruh state lockout milik subjek
+    fn reset(&self, key: &str) -> Result<(), error::ApplicationError<'static>> {
+        self.store.delete(&Self::failures_key(key))?;
+        self.store.delete(&Self::lockouts_key(key))?;
+        self.store.delete(&Self::locked_until_key(key))
+    }
+
+    /**
+    Mencatat kegagalan login pada account dan IP
+
+    # Arguments
+    * `email` - Email yang digunakan untuk login
+    * `ip` - Alamat IP client
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn record_failure(
+        &self,
+        email: &Email,
+        ip: &IpAddr,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.record_account_failure(email, now)?;
+        self.increment(&Self::ip_key(ip), self.policy.ip_threshold, now)
+    }
+
+    /**
+    Mencatat password yang salah pada account saja, misal saat password dimasukkan kembali dari
+    session yang sudah login sehingga IP client tidak tersedia
+
+    # Arguments
+    * `email` - Email dari account
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn record_account_failure(
+        &self,
+        email: &Email,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.increment(
+            &Self::account_key(email),
+            self.policy.account_threshold,
+            now,
+        )
+    }
+
+    /**
+    Me-reset counter kegagalan account setelah login berhasil. Counter IP tidak di-reset agar
+    penyerang tidak dapat me-reset counter dengan login menggunakan account miliknya sendiri.
+
+    # Arguments
+    * `email` - Email yang digunakan untuk login
+    */
+    pub fn record_success(&self, email: &Email) -> Result<(), error::ApplicationError<'static>> {
+        self.reset(&Self::account_key(email))
+    }
+
+    /**
+    Membuka kunci account secara manual (digunakan oleh admin)
+
+    # Arguments
+    * `email` - Email dari account yang akan dibuka kuncinya
+    */
+    pub fn unlock(&self, email: &Email) -> Result<(), error::ApplicationError<'static>> {
+        self.reset(&Self::account_key(email))
+    }
+
+    /**
+    Menolak verifikasi faktor kedua jika terlalu banyak kode salah yang dimasukkan
+
+    # Arguments
+    * `account_id` - Id dari account
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn ensure_factor_not_locked(
+        &self,
+        account_id: &Uuid,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if self.is_locked(&Self::factor_key(account_id), now)? {
+            return Err(error::too_many_request_error!(
+                "Sorry, too many incorrect verification codes were entered. Please try again later.",
+                "mfa_locked"
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+    Mencatat kode faktor kedua yang salah
+
+    # Arguments
+    * `account_id` - Id dari account
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn record_factor_failure(
+        &self,
+        account_id: &Uuid,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.increment(
+            &Self::factor_key(account_id),
+            self.policy.account_threshold,
+            now,
+        )
+    }
+
+    /**
+    Me-reset counter kode faktor kedua yang salah setelah verifikasi berhasil
+
+    # Arguments
+    * `account_id` - Id dari account
+    */
+    pub fn record_factor_success(
+        &self,
+        account_id: &Uuid,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.reset(&Self::factor_key(account_id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::store::MemoryStore;
+    use super::*;
+
+    const NOW: u64 = 1669969469;
+
+    fn email() -> Email {
+        Email::from("harun@digitalsekuriti.id")
+    }
+
+    fn ip() -> IpAddr {
+        "10.0.0.1".parse().unwrap()
+    }
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy {
+            account_threshold: 3,
+            ip_threshold: 5,
+            lock_duration: 60,
+            max_lock_duration: 150,
+            failure_window: 900,
+        }
+    }
+
+    #[test]
+    fn test_lock_account_after_threshold() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let guard = LockoutGuard::new(&store, &policy);
+
+        for _ in 0..2 {
+            guard.record_failure(&email(), &ip(), NOW).unwrap();
+        }
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW).is_ok());
+
+        guard.record_failure(&email(), &ip(), NOW).unwrap();
+        let error = guard.ensure_not_locked(&email(), &ip(), NOW).unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!("account_locked", error.error);
+
+        // Email tidak case sensitive
+        let upper = Email::from("HARUN@digitalsekuriti.id");
+        assert!(guard.ensure_not_locked(&upper, &ip(), NOW).is_err());
+
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW + 60).is_ok());
+    }
+
+    #[test]
+    fn test_escalating_lock_duration() {
+        let store = MemoryStore::new();
+        let policy = LockoutPolicy {
+            ip_threshold: 100,
+            ..policy()
+        };
+        let guard = LockoutGuard::new(&store, &policy);
+        let mut now = NOW;
+
+        for expected in [60, 120, 150, 150] {
+            for _ in 0..3 {
+                guard.record_failure(&email(), &ip(), now).unwrap();
+            }
+            assert!(guard
+                .ensure_not_locked(&email(), &ip(), now + expected - 1)
+                .is_err());
+            assert!(guard
+                .ensure_not_locked(&email(), &ip(), now + expected)
+                .is_ok());
+            now += expected;
+        }
+    }
+
+    #[test]
+    fn test_lock_ip_after_threshold() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let guard = LockoutGuard::new(&store, &policy);
+
+        for i in 0..5 {
+            let email = Email::from(&format!("user{}@digitalsekuriti.id", i));
+            guard.record_failure(&email, &ip(), NOW).unwrap();
+        }
+
+        let error = guard.ensure_not_locked(&email(), &ip(), NOW).unwrap_err();
+        assert_eq!("too_many_request", error.error);
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+        assert!(guard.ensure_not_locked(&email(), &other_ip, NOW).is_ok());
+    }
+
+    #[test]
+    fn test_success_and_unlock() {
+        let store = MemoryStore::new();
+        let policy = LockoutPolicy {
+            ip_threshold: 100,
+            ..policy()
+        };
+        let guard = LockoutGuard::new(&store, &policy);
+
+        for _ in 0..2 {
+            guard.record_failure(&email(), &ip(), NOW).unwrap();
+        }
+        assert_eq!(2, guard.failures(&email()).unwrap());
+        guard.record_success(&email()).unwrap();
+        assert_eq!(0, guard.failures(&email()).unwrap());
+        guard.record_failure(&email(), &ip(), NOW).unwrap();
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW).is_ok());
+
+        for _ in 0..3 {
+            guard.record_failure(&email(), &ip(), NOW).unwrap();
+        }
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW).is_err());
+        guard.unlock(&email()).unwrap();
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW).is_ok());
+    }
+
+    #[test]
+    fn test_lock_factor_after_threshold() {
+        let store = MemoryStore::new();
+        let policy = policy();
+        let guard = LockoutGuard::new(&store, &policy);
+        let account_id = Uuid::new_v4();
+
+        for _ in 0..2 {
+            guard.record_factor_failure(&account_id, NOW).unwrap();
+        }
+        guard.record_factor_success(&account_id).unwrap();
+        for _ in 0..2 {
+            guard.record_factor_failure(&account_id, NOW).unwrap();
+        }
+        assert!(guard.ensure_factor_not_locked(&account_id, NOW).is_ok());
+
+        guard.record_factor_failure(&account_id, NOW).unwrap();
+        let error = guard
+            .ensure_factor_not_locked(&account_id, NOW)
+            .unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!("mfa_locked", error.error);
+        assert!(guard.ensure_factor_not_locked(&Uuid::new_v4(), NOW).is_ok());
+
+        // Kode yang salah tidak mengunci login dengan password
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW).is_ok());
+        assert!(guard
+            .ensure_factor_not_locked(&account_id, NOW + 60)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_failures() {
+        let store = MemoryStore::new();
+        let policy = LockoutPolicy {
+            account_threshold: 50,
+            ip_threshold: 1000,
+            ..policy()
+        };
+        let guard = LockoutGuard::new(&store, &policy);
+
+        std::thread::scope(|scope| {
+            for _ in 0..10 {
+                scope.spawn(|| {
+                    let guard = LockoutGuard::new(&store, &policy);
+                    for _ in 0..5 {
+                        guard.record_failure(&email(), &ip(), NOW).unwrap();
+                    }
+                });
+            }
+        });
+
+        // Tidak ada kegagalan yang hilang, sehingga account terkunci tepat satu kali
+        assert_eq!(0, guard.failures(&email()).unwrap());
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW).is_err());
+        assert!(guard.ensure_not_locked(&email(), &ip(), NOW + 60).is_ok());
+    }
+}
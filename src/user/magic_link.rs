@@ -0,0 +1,644 @@
+use super::super::sharedkernel::{
+    email::Email,
+    error,
+    function::get_now,
+    mailer::{Mailer, Message},
+    password_policy::PasswordPolicy,
+    rate_limit::{RateLimit, RateLimitAlgorithm, RateLimitKey},
+    signature,
+    store::KeyValueStore,
+    token::Token,
+};
+use super::account::{Account, AccountStatus};
+use super::authentication::Authentication;
+use super::repository::AccountRepository;
+use super::risk::RiskEngine;
+use super::session::{
+    AuthenticationMethod, AuthenticationScope, Session, SessionRepository, DEFAULT_SESSION_TTL,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Default masa berlaku magic link (dalam detik)
+pub const DEFAULT_MAGIC_LINK_TTL: u64 = 600;
+// Default jumlah link maksimal yang dikirim ke satu email dalam DEFAULT_MAGIC_LINK_SEND_WINDOW
+pub const DEFAULT_MAGIC_LINK_MAX_SENDS: u32 = 5;
+// Default periode pembatasan pengiriman link (dalam detik)
+pub const DEFAULT_MAGIC_LINK_SEND_WINDOW: u64 = 3600;
+
+/// Permintaan login menggunakan link yang dikirim ke email
+#[derive(Debug, Deserialize)]
+pub struct MagicLinkRequest {
+    email: Email,
+}
+
+/**
+Response permintaan magic link. Response selalu dikembalikan, baik email terdaftar maupun tidak.
+`binding` disimpan oleh browser yang meminta link (misal dalam cookie HttpOnly) dan wajib dikirim
+kembali saat link dibuka, sehingga link tidak dapat digunakan dari browser lain.
+*/
+#[derive(Debug, Serialize)]
+pub struct MagicLinkRequested {
+    pub binding: String,
+    pub expires_in: u64,
+}
+
+/// Data dari magic link yang dibuka user, ditambah binding milik browser
+#[derive(Debug, Deserialize)]
+pub struct MagicLinkCompletion {
+    token: Token,
+    expires_at: u64,
+    signature: String,
+    binding: Token,
+}
+
+/// Magic link yang belum digunakan, disimpan dengan key digest dari token
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLink {
+    account_id: Uuid,
+    /// Session yang menunggu step up, kosong untuk link login
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<Uuid>,
+}
+
+fn invalid_link() -> error::ApplicationError<'static> {
+    error::bad_request_error!(
+        "Sorry, but your sign-in link is invalid or has expired.",
+        "invalid_magic_link"
+    )
+}
+
+fn step_up_unavailable() -> error::ApplicationError<'static> {
+    error::forbidden_error!(
+        "Please verify your sign-in with your authenticator app or security key.",
+        "email_step_up_unavailable"
+    )
+}
+
+fn signed_message(token: &str, expires_at: u64, binding_digest: &str) -> String {
+    format!("magic_link:{}:{}:{}", token, expires_at, binding_digest)
+}
+
+/// Use case login tanpa password menggunakan link yang dikirim ke email
+pub struct MagicLinkService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    store: &'a dyn KeyValueStore,
+    mailer: &'a dyn Mailer,
+    password_policy: &'a PasswordPolicy,
+    secret: &'a [u8],
+    login_url: String,
+    token_ttl: u64,
+    session_ttl: u64,
+    send_limit: RateLimit,
+    risk: Option<RiskEngine<'a>>,
+}
+
+impl<'a> MagicLinkService<'a> {
+    /**
+    Mengembalikan service magic link. `mailer` harus mengirim email di background (QueuedMailer),
+    agar lama pengiriman tidak membedakan response untuk email terdaftar dan tidak terdaftar.
+
+    # Arguments
+    * `secret` - Secret key untuk menandatangani link
+    * `login_url` - URL halaman penyelesaian login, parameter link ditambahkan sebagai query
+    */
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        store: &'a dyn KeyValueStore,
+        mailer: &'a dyn Mailer,
+        password_policy: &'a PasswordPolicy,
+        secret: &'a [u8],
+        login_url: &str,
+    ) -> MagicLinkService<'a> {
+        MagicLinkService {
+            accounts,
+            sessions,
+            store,
+            mailer,
+            password_policy,
+            secret,
+            login_url: String::from(login_url),
+            token_ttl: DEFAULT_MAGIC_LINK_TTL,
+            session_ttl: DEFAULT_SESSION_TTL,
+            send_limit: RateLimit {
+                algorithm: RateLimitAlgorithm::SlidingWindow,
+                key: RateLimitKey::Route,
+                limit: DEFAULT_MAGIC_LINK_MAX_SENDS,
+                window: DEFAULT_MAGIC_LINK_SEND_WINDOW,
+            },
+            risk: None,
+        }
+    }
+
+    /// Mengganti masa berlaku magic link (dalam detik)
+    pub fn with_token_ttl(mut self, ttl: u64) -> MagicLinkService<'a> {
+        self.token_ttl = ttl;
+        self
+    }
+
+    /// Mengganti masa berlaku session (dalam detik) yang dibuat saat login
+    pub fn with_session_ttl(mut self, ttl: u64) -> MagicLinkService<'a> {
+        self.session_ttl = ttl;
+        self
+    }
+
+    /**
+    Mengganti batas pengiriman link ke satu email
+
+    # Arguments
+    * `max_sends` - Jumlah link maksimal dalam satu periode
+    * `window` - Periode pembatasan dalam detik
+    */
+    pub fn with_send_limit(mut self, max_sends: u32, window: u64) -> MagicLinkService<'a> {
+        self.send_limit.limit = max_sends;
+        self.send_limit.window = window;
+        self
+    }
+
+    /// Mencatat penyelesaian step up pada riwayat login, agar perangkat user dikenal oleh risk engine
+    pub fn with_risk_engine(mut self, risk: RiskEngine<'a>) -> MagicLinkService<'a> {
+        self.risk = Some(risk);
+        self
+    }
+
+    fn link_key(token: &Token) -> String {
+        format!("magic_link:{}", token.digest())
+    }
+
+    /**
+    Membatasi jumlah link yang dikirim ke satu email, agar fitur ini tidak digunakan untuk
+    membanjiri inbox user. Email yang tidak terdaftar juga dihitung agar tidak dapat digunakan
+    untuk enumerasi account.
+
+    # Arguments
+    * `email` - Email tujuan
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    fn ensure_send_allowed(
+        &self,
+        email: &Email,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let key = format!("magic_link:sends:{}", email.to_string().to_lowercase());
+        if !self.send_limit.check(self.store, &key, now)?.allowed {
+            return Err(error::too_many_request_error!(
+                "Sorry, too many sign-in links have been requested for this email. Please try again later.",
+                "magic_link_throttled"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Menyimpan link yang belum digunakan lalu mengirimkannya ke email account
+    fn send_link(
+        &self,
+        account: &Account,
+        pending: &PendingLink,
+        binding: &Token,
+        subject: &str,
+        purpose: &str,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let token = Token::generate();
+        let expires_at = get_now() + self.token_ttl;
+        let value = serde_json::to_string(pending).map_err(|_| error::internal_server_error!())?;
+        self.store
+            .set(&Self::link_key(&token), &value, self.token_ttl)?;
+
+        let signature = signature::sign(
+            self.secret,
+            &signed_message(token.expose_secret(), expires_at, &binding.digest()),
+        );
+        let separator = if self.login_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        // Kegagalan pengiriman tidak dikembalikan agar response tetap identik
+        let _ = self.mailer.send(&Message::new(
+            account.email(),
+            subject,
+            &format!(
+                "Open the following link within {} minutes, in the same browser you used to request it, to {}:\n\n{}{}token={}&expires_at={}&signature={}\n\nIf you did not request this, you can safely ignore this email.",
+                self.token_ttl / 60,
+                purpose,
+                self.login_url,
+                separator,
+                token.expose_secret(),
+                expires_at,
+                signature
+            ),
+        ));
+        Ok(())
+    }
+
+    /// Memeriksa signature link lalu mengambil link yang belum digunakan
+    fn take_pending(
+        &self,
+        completion: &MagicLinkCompletion,
+        now: u64,
+    ) -> Result<PendingLink, error::ApplicationError<'static>> {
+        let message = signed_message(
+            completion.token.expose_secret(),
+            completion.expires_at,
+            &completion.binding.digest(),
+        );
+        if now >= completion.expires_at
+            || !signature::verify(self.secret, &message, &completion.signature)
+        {
+            return Err(invalid_link());
+        }
+
+        // Link diambil dan dihapus secara atomic, sehingga request paralel tidak dapat menggunakannya dua kali
+        match self.store.take(&Self::link_key(&completion.token))? {
+            Some(value) => serde_json::from_str(&value).map_err(|_| invalid_link()),
+            None => Err(invalid_link()),
+        }
+    }
+
+    /**
+    Mengirimkan magic link ke email user. Response selalu sama baik email terdaftar maupun tidak,
+    agar tidak dapat digunakan untuk enumerasi account. Jumlah link per email dibatasi, error
+    `magic_link_throttled` dikembalikan jika batas terlampaui.
+
+    # Arguments
+    * `request` - Email dari user
+    */
+    pub fn request(
+        &self,
+        request: &MagicLinkRequest,
+    ) -> Result<MagicLinkRequested, error::ApplicationError<'static>> {
+        self.ensure_send_allowed(&request.email, get_now())?;
+        let binding = Token::generate();
+        let response = MagicLinkRequested {
+            binding: String::from(binding.expose_secret()),
+            expires_in: self.token_ttl,
+        };
+
+        let account = match self.accounts.get_by_email(&request.email)? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Ok(response),
+        };
+
+        let pending = PendingLink {
+            account_id: *account.id(),
+            session_id: None,
+        };
+        self.send_link(&account, &pending, &binding, "Your sign-in link", "sign in")?;
+
+        Ok(response)
+    }
+
+    /**
+    Menyelesaikan login menggunakan magic link. Link hanya dapat digunakan sekali dan hanya dari
+    browser yang memintanya. Account dengan MFA aktif mendapatkan session dengan scope `StepUp`.
+
+    # Arguments
+    * `completion` - Parameter dari link dan binding milik browser
+    */
+    pub fn complete(
+        &self,
+        completion: &MagicLinkCompletion,
+    ) -> Result<Authentication, error::ApplicationError<'static>> {
+        let now = get_now();
+        let pending = self.take_pending(completion, now)?;
+        if pending.session_id.is_some() {
+            return Err(invalid_link());
+        }
+
+        let account = match self.accounts.get_by_id(&pending.account_id.to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(invalid_link()),
+        };
+
+        let scope = if account.is_mfa_enabled() {
+            AuthenticationScope::StepUp
+        } else if account.requires_password_change(self.password_policy.max_age, now) {
+            AuthenticationScope::PasswordChange
+        } else {
+            AuthenticationScope::Full
+        };
+        let mut session = Session::new(account.id(), scope, self.session_ttl);
+        session.record_authentication(AuthenticationMethod::Email, now);
+        self.sessions.create(&session)?;
+
+        Ok(Authentication {
+            account,
+            session,
+            risk: None,
+        })
+    }
+
+    /**
+    Mengirimkan link ke email user untuk menyelesaikan step up yang diminta risk engine. Hanya
+    untuk account tanpa MFA, account dengan MFA harus menggunakan faktor keduanya.
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    */
+    pub fn request_step_up(
+        &self,
+        session: &Session,
+    ) -> Result<MagicLinkRequested, error::ApplicationError<'static>> {
+        if session.scope() != AuthenticationScope::StepUp {
+            return Err(step_up_unavailable());
+        }
+        let account = match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(error::unauthorized_error!()),
+        };
+        if account.is_mfa_enabled() {
+            return Err(step_up_unavailable());
+        }
+        self.ensure_send_allowed(account.email(), get_now())?;
+
+        let binding = Token::generate();
+        let pending = PendingLink {
+            account_id: *account.id(),
+            session_id: Some(*session.id()),
+        };
+        self.send_link(
+            &account,
+            &pending,
+            &binding,
+            "Confirm your sign-in",
+            "confirm your sign-in from a new device or location",
+        )?;
+
+        Ok(MagicLinkRequested {
+            binding: String::from(binding.expose_secret()),
+            expires_in: self.token_ttl,
+        })
+    }
+
+    /**
+    Menyelesaikan step up menggunakan link dari `request_step_up`. Link hanya berlaku untuk session
+    yang memintanya.
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` yang meminta link
+    * `completion` - Parameter dari link dan binding milik browser
+    */
+    pub fn complete_step_up(
+        &self,
+        session: &mut Session,
+        completion: &MagicLinkCompletion,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let now = get_now();
+        let pending = self.take_pending(completion, now)?;
+        if pending.session_id != Some(*session.id())
+            || pending.account_id != *session.account_id()
+            || session.scope() != AuthenticationScope::StepUp
+        {
+            return Err(invalid_link());
+        }
+
+        let account = match self.accounts.get_by_id(&pending.account_id.to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(invalid_link()),
+        };
+        // MFA mungkin diaktifkan setelah link dikirim
+        if account.is_mfa_enabled() {
+            return Err(step_up_unavailable());
+        }
+
+        session.record_authentication(AuthenticationMethod::Email, now);
+        session
+            .complete_step_up(account.requires_password_change(self.password_policy.max_age, now));
+        if let Some(risk) = &self.risk {
+            risk.complete_step_up(session, now)?;
+        }
+        self.sessions.update(session)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{
+        mailer::{capture::CapturingMailer, MockMailer},
+        store::MemoryStore,
+    };
+    use super::super::fixture;
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::MockSessionRepository;
+    use super::*;
+    use std::collections::HashMap;
+
+    const SECRET: &[u8] = b"magic-link-secret";
+    const LOGIN_URL: &str = "https://brokoli.id/login/magic";
+
+    fn request(email: &str) -> MagicLinkRequest {
+        serde_json::from_str(&format!(r#"{{"email": "{}"}}"#, email)).unwrap()
+    }
+
+    /// Mengambil parameter query dari link di dalam email terakhir
+    fn link_params(body: &str) -> HashMap<String, String> {
+        let link = body
+            .split_whitespace()
+            .find(|word| word.starts_with(LOGIN_URL))
+            .unwrap();
+        link.split_once('?')
+            .unwrap()
+            .1
+            .split('&')
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap();
+                (String::from(key), String::from(value))
+            })
+            .collect()
+    }
+
+    fn completion(params: &HashMap<String, String>, binding: &str) -> MagicLinkCompletion {
+        serde_json::from_value(serde_json::json!({
+            "token": params["token"],
+            "expires_at": params["expires_at"].parse::<u64>().unwrap(),
+            "signature": params["signature"],
+            "binding": binding,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_request_for_unknown_email() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_email().returning(|_| Ok(None));
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(0);
+        let policy = PasswordPolicy::default();
+        let service = MagicLinkService::new(
+            &accounts, &sessions, &store, &mailer, &policy, SECRET, LOGIN_URL,
+        );
+
+        let response = service
+            .request(&request("unknown@digitalsekuriti.id"))
+            .unwrap();
+        assert_eq!(64, response.binding.len());
+        assert_eq!(DEFAULT_MAGIC_LINK_TTL, response.expires_in);
+    }
+
+    #[test]
+    fn test_request_and_complete() {
+        let account = fixture::account();
+        let account_id = *account.id();
+        let (accounts, _) = fixture::repository(account);
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_create().times(1).returning(|_| Ok(()));
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let policy = PasswordPolicy::default();
+        let service = MagicLinkService::new(
+            &accounts, &sessions, &store, &mailer, &policy, SECRET, LOGIN_URL,
+        );
+
+        let response = service
+            .request(&request("harun@digitalsekuriti.id"))
+            .unwrap();
+        let params = link_params(&mailer.sent()[0].body);
+
+        // Link dari browser lain atau link yang diubah ditolak
+        let other = Token::generate();
+        let error = service
+            .complete(&completion(&params, other.expose_secret()))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+        let mut tampered = params.clone();
+        tampered.insert(
+            String::from("expires_at"),
+            (params["expires_at"].parse::<u64>().unwrap() + 60).to_string(),
+        );
+        let error = service
+            .complete(&completion(&tampered, &response.binding))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+
+        let authentication = service
+            .complete(&completion(&params, &response.binding))
+            .unwrap();
+        assert_eq!(&account_id, authentication.session.account_id());
+        assert_eq!(AuthenticationScope::Full, authentication.session.scope());
+        assert_eq!(&[AuthenticationMethod::Email], authentication.session.amr());
+
+        // Link hanya dapat digunakan sekali
+        let error = service
+            .complete(&completion(&params, &response.binding))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+    }
+
+    #[test]
+    fn test_step_up_without_mfa() {
+        let account = fixture::account();
+        let account_id = *account.id();
+        let (accounts, _) = fixture::repository(account);
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().times(1).returning(|_| Ok(()));
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let policy = PasswordPolicy::default();
+        let service = MagicLinkService::new(
+            &accounts, &sessions, &store, &mailer, &policy, SECRET, LOGIN_URL,
+        );
+
+        let full = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let error = service.request_step_up(&full).unwrap_err();
+        assert_eq!("email_step_up_unavailable", error.error);
+
+        let mut session = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        session.record_authentication(AuthenticationMethod::Password, get_now());
+        let response = service.request_step_up(&session).unwrap();
+        let params = link_params(&mailer.sent()[0].body);
+
+        // Link step up hanya berlaku untuk session yang memintanya
+        let mut other = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        let error = service
+            .complete_step_up(&mut other, &completion(&params, &response.binding))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+        let response = service.request_step_up(&session).unwrap();
+        let params = link_params(&mailer.sent()[1].body);
+        let error = service
+            .complete(&completion(&params, &response.binding))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+
+        // Link login tidak dapat digunakan untuk step up
+        let response = service
+            .request(&request("harun@digitalsekuriti.id"))
+            .unwrap();
+        let params = link_params(&mailer.sent()[2].body);
+        let error = service
+            .complete_step_up(&mut session, &completion(&params, &response.binding))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+
+        let response = service.request_step_up(&session).unwrap();
+        let params = link_params(&mailer.sent()[3].body);
+        service
+            .complete_step_up(&mut session, &completion(&params, &response.binding))
+            .unwrap();
+        assert_eq!(AuthenticationScope::Full, session.scope());
+        assert_eq!(
+            &[AuthenticationMethod::Password, AuthenticationMethod::Email],
+            session.amr()
+        );
+    }
+
+    #[test]
+    fn test_request_throttled_per_email() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|email| Ok(Some(fixture::account()).filter(|a| a.email() == email)));
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let policy = PasswordPolicy::default();
+        let service = MagicLinkService::new(
+            &accounts, &sessions, &store, &mailer, &policy, SECRET, LOGIN_URL,
+        )
+        .with_send_limit(2, 3600);
+
+        for email in ["harun@digitalsekuriti.id", "unknown@digitalsekuriti.id"] {
+            for _ in 0..2 {
+                service.request(&request(email)).unwrap();
+            }
+            // Email terdaftar maupun tidak mendapatkan error yang sama
+            let error = service.request(&request(email)).unwrap_err();
+            assert_eq!(429, error.code);
+            assert_eq!("magic_link_throttled", error.error);
+        }
+        let error = service
+            .request(&request("HARUN@digitalsekuriti.id"))
+            .unwrap_err();
+        assert_eq!("magic_link_throttled", error.error);
+        assert_eq!(2, mailer.sent().len());
+    }
+
+    #[test]
+    fn test_expired_link() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(fixture::account())));
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let policy = PasswordPolicy::default();
+        let service = MagicLinkService::new(
+            &accounts, &sessions, &store, &mailer, &policy, SECRET, LOGIN_URL,
+        )
+        .with_token_ttl(0);
+
+        let response = service
+            .request(&request("harun@digitalsekuriti.id"))
+            .unwrap();
+        let params = link_params(&mailer.sent()[0].body);
+        let error = service
+            .complete(&completion(&params, &response.binding))
+            .unwrap_err();
+        assert_eq!("invalid_magic_link", error.error);
+    }
+}
@@ -1,3 +1,20 @@
-pub mod registration;
 pub mod account;
-pub mod repository;
\ No newline at end of file
+pub mod authentication;
+pub mod deletion;
+pub mod email_change;
+#[cfg(test)]
+mod fixture;
+pub mod lockout;
+pub mod magic_link;
+pub mod password_change;
+pub mod password_reset;
+pub mod phone;
+pub mod recovery;
+pub mod registration;
+pub mod repository;
+pub mod risk;
+pub mod session;
+pub mod totp;
+pub mod trusted_device;
+pub mod verification;
+pub mod webauthn;
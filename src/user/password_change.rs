@@ -0,0 +1,290 @@
+use super::super::sharedkernel::{
+    breach::{BreachCorpus, BreachScreening},
+    error,
+    mailer::{Mailer, Message},
+    password::{Hash, Password},
+    password_policy::PasswordPolicy,
+    strength,
+};
+use super::account::{Account, AccountStatus};
+use super::repository::AccountRepository;
+use super::session::{Session, SessionRepository};
+use serde::Deserialize;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+/// Data ganti password yang dikirim oleh user
+#[derive(Debug, Deserialize)]
+pub struct PasswordChange {
+    current_password: Password,
+    password: Password,
+}
+
+/**
+Memvalidasi password baru milik account : password policy (dengan email sebagai penalti kekuatan
+password), breach corpus jika dikonfigurasi, dan password history.
+
+# Arguments
+* `account` - Account pemilik password
+* `password` - Password yang baru
+* `policy` - Password policy yang berlaku
+* `breach_corpus` - Breach corpus yang digunakan untuk screening, jika ada
+*/
+pub fn validate_new_password(
+    account: &Account,
+    password: &Password,
+    policy: &PasswordPolicy,
+    breach_corpus: Option<&dyn BreachCorpus>,
+) -> Result<(), error::ApplicationError<'static>> {
+    let check = policy
+        .check(password)
+        .with_user_inputs(strength::email_inputs(account.email()));
+    if let Err(errors) = ValidationErrors::merge(Ok(()), "password", check.validate()) {
+        return Err(error::ApplicationError::from_validation_errors(errors));
+    }
+
+    if let Some(corpus) = breach_corpus {
+        BreachScreening::new(corpus).check(password)?;
+    }
+
+    account.ensure_password_not_reused(password, policy.history_depth)
+}
+
+/// Use case ganti password oleh user yang sedang login
+pub struct PasswordChangeService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    mailer: &'a dyn Mailer,
+    policy: &'a PasswordPolicy,
+    breach_corpus: Option<&'a dyn BreachCorpus>,
+}
+
+impl<'a> PasswordChangeService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        mailer: &'a dyn Mailer,
+        policy: &'a PasswordPolicy,
+    ) -> PasswordChangeService<'a> {
+        PasswordChangeService {
+            accounts,
+            sessions,
+            mailer,
+            policy,
+            breach_corpus: None,
+        }
+    }
+
+    /// Mengaktifkan pemeriksaan password baru terhadap breach corpus
+    pub fn with_breach_corpus(mut self, corpus: &'a dyn BreachCorpus) -> PasswordChangeService<'a> {
+        self.breach_corpus = Some(corpus);
+        self
+    }
+
+    /**
+    Mengganti password setelah memverifikasi password saat ini. Seluruh session lain milik account
+    dicabut, session saat ini mendapatkan akses penuh kembali, dan user menerima notifikasi email.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `change` - Password saat ini dan password yang baru
+    */
+    pub fn change_password(
+        &self,
+        session: &mut Session,
+        change: &PasswordChange,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let mut account = match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(error::unauthorized_error!()),
+        };
+
+        if !account
+            .hash()
+            .verify_password(&change.current_password)
+            .unwrap_or(false)
+        {
+            let mut errors = ValidationErrors::new();
+            errors.add(
+                "current_password",
+                ValidationError::new("invalid_current_password"),
+            );
+            return Err(error::ApplicationError::from_validation_errors(errors));
+        }
+
+        validate_new_password(&account, &change.password, self.policy, self.breach_corpus)?;
+
+        account.change_hash(Hash::generate(&change.password)?, self.policy.history_depth);
+        self.accounts.update(&account)?;
+
+        self.sessions
+            .revoke_all(account.id(), Some(*session.id()))?;
+        session.grant_full_access();
+        self.sessions.update(session)?;
+
+        // Notifikasi bersifat best-effort, password sudah terlanjur diganti
+        let _ = self.mailer.send(&Message::new(
+            account.email(),
+            "Your password has been changed",
+            &format!(
+                "The password for your account {} has just been changed. If you did not make this change, please reset your password immediately.",
+                account.email()
+            ),
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{
+        breach::MockBreachCorpus, email::Email, mailer::MockMailer,
+    };
+    use super::super::account::AccountRole;
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::{AuthenticationScope, MockSessionRepository};
+    use super::*;
+    use uuid::Uuid;
+
+    const KEY: &str = "$6$G/gkPn17kHYo0gTF$";
+    const PASSWORD: &str = "MypassworD1234!";
+    const NEW_PASSWORD: &str = "Another-Passphrase-2024";
+
+    fn change(current_password: &str, password: &str) -> PasswordChange {
+        serde_json::from_str(&format!(
+            r#"{{"current_password": "{}", "password": "{}"}}"#,
+            current_password, password
+        ))
+        .unwrap()
+    }
+
+    fn account() -> Account {
+        Account::new(
+            Email::from("harun@digitalsekuriti.id"),
+            Hash::from_password(KEY, &Password::from(PASSWORD)).unwrap(),
+            AccountRole::User,
+        )
+    }
+
+    fn accounts() -> MockAccountRepository {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(|_| Ok(Some(account())));
+        accounts
+    }
+
+    #[test]
+    fn test_change_password() {
+        let mut accounts = accounts();
+        accounts
+            .expect_update()
+            .withf(|account| {
+                account
+                    .hash()
+                    .verify_password(&Password::from(NEW_PASSWORD))
+                    .unwrap()
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::PasswordChange, 60);
+        let current_session = *session.id();
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all()
+            .withf(move |_, except| *except == Some(current_session))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        sessions
+            .expect_update()
+            .withf(|session| session.scope() == AuthenticationScope::Full)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
+            .withf(|message| message.to == Email::from("harun@digitalsekuriti.id"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let policy = PasswordPolicy::default();
+        let result = PasswordChangeService::new(&accounts, &sessions, &mailer, &policy)
+            .change_password(&mut session, &change(PASSWORD, NEW_PASSWORD));
+
+        assert!(result.is_ok());
+        assert!(session.ensure_full_access().is_ok());
+    }
+
+    #[test]
+    fn test_change_password_with_wrong_current_password() {
+        let mut accounts = accounts();
+        accounts.expect_update().times(0);
+        let sessions = MockSessionRepository::new();
+        let mailer = MockMailer::new();
+        let policy = PasswordPolicy::default();
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+
+        let error = PasswordChangeService::new(&accounts, &sessions, &mailer, &policy)
+            .change_password(&mut session, &change("WrongPassw0rd!", NEW_PASSWORD))
+            .unwrap_err();
+
+        assert!(error.fields.unwrap()["current_password"][0].contains("invalid_current_password"));
+    }
+
+    #[test]
+    fn test_change_password_rejects_policy_violation() {
+        let mut accounts = accounts();
+        accounts.expect_update().times(0);
+        let sessions = MockSessionRepository::new();
+        let mailer = MockMailer::new();
+        let policy = PasswordPolicy::default();
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+
+        let error = PasswordChangeService::new(&accounts, &sessions, &mailer, &policy)
+            .change_password(&mut session, &change(PASSWORD, "short"))
+            .unwrap_err();
+
+        assert!(error.fields.unwrap().contains_key("password"));
+    }
+
+    #[test]
+    fn test_change_password_rejects_breached_password() {
+        let mut accounts = accounts();
+        accounts.expect_update().times(0);
+        let sessions = MockSessionRepository::new();
+        let mailer = MockMailer::new();
+        let mut corpus = MockBreachCorpus::new();
+        corpus.expect_contains().returning(|_| Ok(true));
+        let policy = PasswordPolicy::default();
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+
+        let error = PasswordChangeService::new(&accounts, &sessions, &mailer, &policy)
+            .with_breach_corpus(&corpus)
+            .change_password(&mut session, &change(PASSWORD, NEW_PASSWORD))
+            .unwrap_err();
+
+        assert!(error.fields.unwrap()["password"][0].contains("password_breached"));
+    }
+
+    #[test]
+    fn test_change_password_rejects_reused_password() {
+        let mut accounts = accounts();
+        accounts.expect_update().times(0);
+        let sessions = MockSessionRepository::new();
+        let mailer = MockMailer::new();
+        let policy = PasswordPolicy {
+            history_depth: 3,
+            ..PasswordPolicy::default()
+        };
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+
+        let error = PasswordChangeService::new(&accounts, &sessions, &mailer, &policy)
+            .change_password(&mut session, &change(PASSWORD, PASSWORD))
+            .unwrap_err();
+
+        assert!(error.fields.unwrap()["password"][0].contains("password_reused"));
+    }
+}
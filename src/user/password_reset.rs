@@ -0,0 +1,484 @@
+use super::super::sharedkernel::{
+    breach::BreachCorpus,
+    email::Email,
+    error,
+    function::get_now,
+    mailer::{Mailer, Message},
+    password::{Hash, Password},
+    password_policy::PasswordPolicy,
+    token::Token,
+};
+use super::account::AccountStatus;
+use super::password_change::validate_new_password;
+use super::repository::AccountRepository;
+use super::session::SessionRepository;
+use mockall::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Default masa berlaku token reset password (dalam detik)
+pub const DEFAULT_RESET_TOKEN_TTL: u64 = 3600;
+
+/// Token reset password yang tersimpan. Hanya digest dari token yang disimpan.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PasswordResetToken {
+    _id: Uuid,
+    account_id: Uuid,
+    digest: String,
+    expires_at: u64,
+    used_at: Option<u64>,
+}
+
+impl PasswordResetToken {
+    /**
+    Mengembalikan record token reset password baru
+
+    # Arguments
+    * `account_id` - Id dari account yang akan di-reset
+    * `token` - Token yang dikirim ke user
+    * `ttl` - Masa berlaku token dalam detik
+    */
+    pub fn new(account_id: &Uuid, token: &Token, ttl: u64) -> PasswordResetToken {
+        PasswordResetToken {
+            _id: Uuid::new_v4(),
+            account_id: *account_id,
+            digest: token.digest(),
+            expires_at: get_now() + ttl,
+            used_at: None,
+        }
+    }
+
+    pub fn account_id(&self) -> &Uuid {
+        &self.account_id
+    }
+
+    /**
+    Memeriksa apakah token masih dapat digunakan : belum pernah digunakan dan belum expired
+
+    # Arguments
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn is_usable(&self, now: u64) -> bool {
+        self.used_at.is_none() && now < self.expires_at
+    }
+
+    /// Menandai token sudah digunakan
+    pub fn mark_used(&mut self, now: u64) {
+        self.used_at = Some(now);
+    }
+}
+
+#[automock]
+pub trait PasswordResetRepository {
+    fn create(&self, token: &PasswordResetToken) -> Result<(), error::ApplicationError<'static>>;
+    fn get_by_digest(
+        &self,
+        digest: &str,
+    ) -> Result<Option<PasswordResetToken>, error::ApplicationError<'static>>;
+    /**
+    Menandai token sebagai sudah digunakan secara atomic, misal dengan
+    `UPDATE ... SET used_at = now WHERE digest = ? AND used_at IS NULL AND expires_at > now`.
+    Mengembalikan false jika tidak ada token yang diubah (sudah digunakan, expired atau tidak ada),
+    sehingga dari beberapa request paralel hanya satu yang berhasil menggunakan token.
+    */
+    fn consume(&self, digest: &str, now: u64) -> Result<bool, error::ApplicationError<'static>>;
+    /// Menghapus seluruh token reset password milik account
+    fn delete_by_account(&self, account_id: &Uuid) -> Result<(), error::ApplicationError<'static>>;
+}
+
+/// Permintaan reset password ("forgot password")
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    email: Email,
+}
+
+/// Konfirmasi reset password menggunakan token yang dikirim melalui email
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetConfirmation {
+    token: Token,
+    password: Password,
+}
+
+/// Use case self-service reset password
+pub struct PasswordResetService<'a> {
+    accounts: &'a dyn AccountRepository,
+    tokens: &'a dyn PasswordResetRepository,
+    sessions: &'a dyn SessionRepository,
+    mailer: &'a dyn Mailer,
+    policy: &'a PasswordPolicy,
+    reset_url: String,
+    token_ttl: u64,
+    breach_corpus: Option<&'a dyn BreachCorpus>,
+}
+
+impl<'a> PasswordResetService<'a> {
+    /**
+    Mengembalikan service reset password
+
+    # Arguments
+    * `reset_url` - URL halaman reset password, token ditambahkan sebagai query `token`
+    */
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        tokens: &'a dyn PasswordResetRepository,
+        sessions: &'a dyn SessionRepository,
+        mailer: &'a dyn Mailer,
+        policy: &'a PasswordPolicy,
+        reset_url: &str,
+    ) -> PasswordResetService<'a> {
+        PasswordResetService {
+            accounts,
+            tokens,
+            sessions,
+            mailer,
+            policy,
+            reset_url: String::from(reset_url),
+            token_ttl: DEFAULT_RESET_TOKEN_TTL,
+            breach_corpus: None,
+        }
+    }
+
+    /// Mengganti masa berlaku token reset password (dalam detik)
+    pub fn with_token_ttl(mut self, ttl: u64) -> PasswordResetService<'a> {
+        self.token_ttl = ttl;
+        self
+    }
+
+    /// Mengaktifkan pemeriksaan password baru terhadap breach corpus
+    pub fn with_breach_corpus(mut self, corpus: &'a dyn BreachCorpus) -> PasswordResetService<'a> {
+        self.breach_corpus = Some(corpus);
+        self
+    }
+
+    /**
+    Mengirimkan link reset password ke email user. Response selalu sama baik email terdaftar
+    maupun tidak, agar tidak dapat digunakan untuk enumerasi account. `mailer` harus mengirim
+    email di background (QueuedMailer) agar waktu pengiriman tidak menambah response time, namun
+    penyimpanan token untuk email terdaftar tetap membutuhkan waktu tambahan.
+
+    # Arguments
+    * `request` - Email dari user
+    */
+    pub fn request_reset(
+        &self,
+        request: &PasswordResetRequest,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let account = match self.accounts.get_by_email(&request.email)? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Ok(()),
+        };
+
+        // Hanya token terakhir yang berlaku
+        self.tokens.delete_by_account(account.id())?;
+
+        let token = Token::generate();
+        self.tokens.create(&PasswordResetToken::new(
+            account.id(),
+            &token,
+            self.token_ttl,
+        ))?;
+
+        let separator = if self.reset_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        // Kegagalan pengiriman tidak dikembalikan agar response tetap identik
+        let _ = self.mailer.send(&Message::new(
+            account.email(),
+            "Reset your password",
+            &format!(
+                "We received a request to reset the password for your account. Open the following link within {} minutes to choose a new password:\n\n{}{}token={}\n\nIf you did not request this, you can safely ignore this email.",
+                self.token_ttl / 60,
+                self.reset_url,
+                separator,
+                token.expose_secret()
+            ),
+        ));
+
+        Ok(())
+    }
+
+    /**
+    Mengganti password menggunakan token reset password. Token hanya dapat digunakan sekali,
+    dan seluruh session milik account dicabut setelah password diganti.
+
+    # Arguments
+    * `confirmation` - Token dan password yang baru
+    */
+    pub fn confirm_reset(
+        &self,
+        confirmation: &PasswordResetConfirmation,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let invalid_token = || {
+            error::bad_request_error!(
+                "Sorry, but your password reset link is invalid or has expired.",
+                "invalid_reset_token"
+            )
+        };
+        let now = get_now();
+
+        let digest = confirmation.token.digest();
+        let token = match self.tokens.get_by_digest(&digest)? {
+            Some(token) if token.is_usable(now) => token,
+            _ => return Err(invalid_token()),
+        };
+
+        let mut account = match self.accounts.get_by_id(&token.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => account,
+            _ => return Err(invalid_token()),
+        };
+
+        validate_new_password(
+            &account,
+            &confirmation.password,
+            self.policy,
+            self.breach_corpus,
+        )?;
+
+        // Request paralel dengan token yang sama dapat lolos pemeriksaan di atas, hanya satu
+        // yang berhasil menggunakan token
+        if !self.tokens.consume(&digest, now)? {
+            return Err(invalid_token());
+        }
+
+        account.change_hash(
+            Hash::generate(&confirmation.password)?,
+            self.policy.history_depth,
+        );
+        self.accounts.update(&account)?;
+        self.sessions.revoke_all(account.id(), None)?;
+
+        // Notifikasi bersifat best-effort, password sudah terlanjur diganti
+        let _ = self.mailer.send(&Message::new(
+            account.email(),
+            "Your password has been reset",
+            &format!(
+                "The password for your account {} has just been reset. If you did not make this change, please contact us immediately.",
+                account.email()
+            ),
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::mailer::MockMailer;
+    use super::super::account::{Account, AccountRole};
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::MockSessionRepository;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const KEY: &str = "$6$G/gkPn17kHYo0gTF$";
+    const NEW_PASSWORD: &str = "Another-Passphrase-2024";
+    const RESET_URL: &str = "https://brokoli.id/reset-password";
+
+    fn request(email: &str) -> PasswordResetRequest {
+        serde_json::from_str(&format!(r#"{{"email": "{}"}}"#, email)).unwrap()
+    }
+
+    fn confirmation(token: &str, password: &str) -> PasswordResetConfirmation {
+        serde_json::from_str(&format!(
+            r#"{{"token": "{}", "password": "{}"}}"#,
+            token, password
+        ))
+        .unwrap()
+    }
+
+    fn account() -> Account {
+        Account::new(
+            Email::from("harun@digitalsekuriti.id"),
+            Hash::from_password(KEY, &Password::from("MypassworD1234!")).unwrap(),
+            AccountRole::User,
+        )
+    }
+
+    #[test]
+    fn test_request_reset_for_unknown_email() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_get_by_email().returning(|_| Ok(None));
+        let mut tokens = MockPasswordResetRepository::new();
+        tokens.expect_create().times(0);
+        let sessions = MockSessionRepository::new();
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(0);
+        let policy = PasswordPolicy::default();
+
+        let result =
+            PasswordResetService::new(&accounts, &tokens, &sessions, &mailer, &policy, RESET_URL)
+                .request_reset(&request("unknown@digitalsekuriti.id"));
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_request_reset() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_email()
+            .returning(|_| Ok(Some(account())));
+
+        let stored: Arc<Mutex<Option<PasswordResetToken>>> = Arc::new(Mutex::new(None));
+        let mut tokens = MockPasswordResetRepository::new();
+        tokens
+            .expect_delete_by_account()
+            .times(1)
+            .returning(|_| Ok(()));
+        let store = stored.clone();
+        tokens.expect_create().times(1).returning(move |token| {
+            *store.lock().unwrap() = Some(token.clone());
+            Ok(())
+        });
+
+        let sent: Arc<Mutex<Option<Message>>> = Arc::new(Mutex::new(None));
+        let outbox = sent.clone();
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(1).returning(move |message| {
+            *outbox.lock().unwrap() = Some(message.clone());
+            Ok(())
+        });
+        let sessions = MockSessionRepository::new();
+        let policy = PasswordPolicy::default();
+
+        PasswordResetService::new(&accounts, &tokens, &sessions, &mailer, &policy, RESET_URL)
+            .request_reset(&request("harun@digitalsekuriti.id"))
+            .unwrap();
+
+        let message = sent.lock().unwrap().take().unwrap();
+        let token = message.body.split("token=").nth(1).unwrap();
+        let token = Token::from(token.split_whitespace().next().unwrap());
+        let stored = stored.lock().unwrap().take().unwrap();
+
+        assert!(message.body.contains(RESET_URL));
+        assert_eq!(token.digest(), stored.digest);
+        assert_ne!(token.expose_secret(), stored.digest);
+        assert!(stored.is_usable(get_now()));
+    }
+
+    #[test]
+    fn test_confirm_reset() {
+        let token = Token::generate();
+        let record = PasswordResetToken::new(&Uuid::new_v4(), &token, 60);
+
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(|_| Ok(Some(account())));
+        accounts
+            .expect_update()
+            .withf(|account| {
+                account
+                    .hash()
+                    .verify_password(&Password::from(NEW_PASSWORD))
+                    .unwrap()
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut tokens = MockPasswordResetRepository::new();
+        let digest = token.digest();
+        tokens
+            .expect_get_by_digest()
+            .withf(move |d| d == digest)
+            .returning(move |_| Ok(Some(record.clone())));
+        let digest = token.digest();
+        tokens
+            .expect_consume()
+            .withf(move |d, _| d == digest)
+            .times(1)
+            .returning(|_, _| Ok(true));
+
+        let mut sessions = MockSessionRepository::new();
+        sessions
+            .expect_revoke_all()
+            .withf(|_, except| except.is_none())
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(1).returning(|_| Ok(()));
+        let policy = PasswordPolicy::default();
+
+        let result =
+            PasswordResetService::new(&accounts, &tokens, &sessions, &mailer, &policy, RESET_URL)
+                .confirm_reset(&confirmation(token.expose_secret(), NEW_PASSWORD));
+
+        assert!(result.is_ok());
+    }
+
+    macro_rules! invalid_token_test_cases {
+        (
+            $(
+                ($test_name: ident, $record: expr)
+            ),*
+        ) => {
+            $(
+                #[test]
+                fn $test_name() {
+                    let token = Token::generate();
+                    let record: Option<PasswordResetToken> = $record(&token);
+
+                    let accounts = MockAccountRepository::new();
+                    let mut tokens = MockPasswordResetRepository::new();
+                    tokens
+                        .expect_get_by_digest()
+                        .returning(move |_| Ok(record.clone()));
+                    tokens.expect_consume().times(0);
+                    let sessions = MockSessionRepository::new();
+                    let mailer = MockMailer::new();
+                    let policy = PasswordPolicy::default();
+
+                    let error = PasswordResetService::new(
+                        &accounts, &tokens, &sessions, &mailer, &policy, RESET_URL,
+                    )
+                    .confirm_reset(&confirmation(token.expose_secret(), NEW_PASSWORD))
+                    .unwrap_err();
+
+                    assert_eq!("invalid_reset_token", error.error);
+                }
+            )*
+        };
+    }
+
+    #[test]
+    fn test_confirm_reset_with_consumed_token() {
+        let token = Token::generate();
+        let record = PasswordResetToken::new(&Uuid::new_v4(), &token, 60);
+
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(|_| Ok(Some(account())));
+        accounts.expect_update().times(0);
+
+        // Request lain menggunakan token di antara pemeriksaan dan penggantian password
+        let mut tokens = MockPasswordResetRepository::new();
+        tokens
+            .expect_get_by_digest()
+            .returning(move |_| Ok(Some(record.clone())));
+        tokens.expect_consume().times(1).returning(|_, _| Ok(false));
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_revoke_all().times(0);
+        let mailer = MockMailer::new();
+        let policy = PasswordPolicy::default();
+
+        let error =
+            PasswordResetService::new(&accounts, &tokens, &sessions, &mailer, &policy, RESET_URL)
+                .confirm_reset(&confirmation(token.expose_secret(), NEW_PASSWORD))
+                .unwrap_err();
+
+        assert_eq!("invalid_reset_token", error.error);
+    }
+
+    invalid_token_test_cases! {
+        (unknown_token_test, |_: &Token| None),
+        (expired_token_test, |token: &Token| Some(PasswordResetToken::new(&Uuid::new_v4(), token, 0))),
+        (used_token_test, |token: &Token| {
+            let mut record = PasswordResetToken::new(&Uuid::new_v4(), token, 60);
+            record.mark_used(get_now());
+            Some(record)
+        })
+    }
+}
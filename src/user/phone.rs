@@ -0,0 +1,607 @@
+use super::super::sharedkernel::{
+    error,
+    function::get_now,
+    password_policy::PasswordPolicy,
+    phone::PhoneNumber,
+    rate_limit::{RateLimit, RateLimitAlgorithm, RateLimitKey},
+    sms::{SmsChannel, SmsMessage, SmsProvider},
+    store::KeyValueStore,
+    token::Token,
+};
+use super::account::{Account, AccountStatus};
+use super::repository::AccountRepository;
+use super::risk::RiskEngine;
+use super::session::{AuthenticationMethod, Session, SessionRepository};
+use rand::{rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+/// Nomor telepon terverifikasi milik account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PhoneFactor {
+    number: PhoneNumber,
+    verified_at: u64,
+}
+
+impl PhoneFactor {
+    pub fn number(&self) -> &PhoneNumber {
+        &self.number
+    }
+
+    pub fn verified_at(&self) -> u64 {
+        self.verified_at
+    }
+}
+
+/// Kebijakan kode OTP yang dikirim melalui SMS atau panggilan suara
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PhoneOtpPolicy {
+    pub digits: u32,
+    /// Masa berlaku kode dalam detik
+    pub ttl: u64,
+    /// Jumlah percobaan kode yang salah sebelum kode dibatalkan
+    pub max_attempts: u32,
+    /// Jumlah pengiriman kode maksimal ke satu nomor telepon dalam `send_window`
+    pub max_sends: u32,
+    /// Periode pembatasan pengiriman dalam detik
+    pub send_window: u64,
+}
+
+impl Default for PhoneOtpPolicy {
+    fn default() -> Self {
+        PhoneOtpPolicy {
+            digits: 6,
+            ttl: 300,
+            max_attempts: 5,
+            max_sends: 3,
+            send_window: 3600,
+        }
+    }
+}
+
+/// Permintaan menambahkan nomor telepon sebagai faktor kedua
+#[derive(Debug, Deserialize)]
+pub struct PhoneEnrollment {
+    phone_number: PhoneNumber,
+    #[serde(default)]
+    channel: SmsChannel,
+}
+
+/// Kode OTP yang dikirim oleh user
+#[derive(Debug, Deserialize)]
+pub struct PhoneCode {
+    code: String,
+}
+
+/// Informasi kode yang baru dikirim
+#[derive(Debug, Serialize)]
+pub struct PhoneCodeSent {
+    /// Nomor tujuan yang disamarkan
+    pub phone_number: String,
+    /// Masa berlaku kode dalam detik
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+enum Purpose {
+    #[serde(rename = "enrollment")]
+    Enrollment,
+    #[serde(rename = "login")]
+    Login,
+}
+
+/// Kode yang menunggu verifikasi, kode hanya disimpan dalam bentuk digest
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingCode {
+    purpose: Purpose,
+    number: PhoneNumber,
+    #[serde(default)]
+    channel: SmsChannel,
+    digest: String,
+    expires_at: u64,
+}
+
+fn invalid_code() -> error::ApplicationError<'static> {
+    error::unauthorized_error!(
+        "Sorry, but the verification code is incorrect or has expired.",
+        "invalid_phone_code"
+    )
+}
+
+/// Use case faktor kedua berupa kode OTP melalui SMS atau panggilan suara
+pub struct PhoneOtpService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    store: &'a dyn KeyValueStore,
+    provider: &'a dyn SmsProvider,
+    password_policy: &'a PasswordPolicy,
+    policy: PhoneOtpPolicy,
+    risk: Option<RiskEngine<'a>>,
+}
+
+impl<'a> PhoneOtpService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        store: &'a dyn KeyValueStore,
+        provider: &'a dyn SmsProvider,
+        password_policy: &'a PasswordPolicy,
+    ) -> PhoneOtpService<'a> {
+        PhoneOtpService {
+            accounts,
+            sessions,
+            store,
+            provider,
+            password_policy,
+            policy: PhoneOtpPolicy::default(),
+            risk: None,
+        }
+    }
+
+    /// Mengganti kebijakan kode OTP
+    pub fn with_policy(mut self, policy: PhoneOtpPolicy) -> PhoneOtpService<'a> {
+        self.policy = policy;
+        self
+    }
+
+    /// Mencatat penyelesaian step up pada riwayat login, agar perangkat user dikenal oleh risk engine
+    pub fn with_risk_engine(mut self, risk: RiskEngine<'a>) -> PhoneOtpService<'a> {
+        self.risk = Some(risk);
+        self
+    }
+
+    fn account(&self, session: &Session) -> Result<Account, error::ApplicationError<'static>> {
+        match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => Ok(account),
+            _ => Err(error::unauthorized_error!()),
+        }
+    }
+
+    fn code_key(account_id: &Uuid) -> String {
+        format!("phone_otp:code:{}", account_id)
+    }
+
+    fn attempts_key(account_id: &Uuid) -> String {
+        format!("phone_otp:attempts:{}", account_id)
+    }
+
+    /**
+    Membatasi jumlah pengiriman ke satu nomor telepon dalam satu periode, agar fitur ini tidak
+    disalahgunakan untuk mengirim SMS massal (SMS pumping) ke nomor milik orang lain
+
+    # Arguments
+    * `number` - Nomor telepon tujuan
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    fn ensure_send_allowed(
+        &self,
+        number: &PhoneNumber,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let limit = RateLimit {
+            algorithm: RateLimitAlgorithm::SlidingWindow,
+            key: RateLimitKey::Route,
+            limit: self.policy.max_sends,
+            window: self.policy.send_window,
+        };
+        let decision = limit.check(self.store, &format!("phone_otp:sends:{}", number), now)?;
+        if !decision.allowed {
+            return Err(error::too_many_request_error!(
+                "Sorry, too many codes have been sent to this phone number. Please try again later.",
+                "sms_rate_limited"
+            ));
+        }
+        Ok(())
+    }
+
+    fn send_code(
+        &self,
+        account_id: &Uuid,
+        purpose: Purpose,
+        number: &PhoneNumber,
+        channel: SmsChannel,
+    ) -> Result<PhoneCodeSent, error::ApplicationError<'static>> {
+        let now = get_now();
+        self.ensure_send_allowed(number, now)?;
+
+        let code: String = (0..self.policy.digits)
+            .map(|_| char::from(b'0' + OsRng.gen_range(0..10)))
+            .collect();
+        let pending = PendingCode {
+            purpose,
+            number: number.clone(),
+            channel,
+            digest: Token::from(&code).digest(),
+            expires_at: now + self.policy.ttl,
+        };
+        let value = serde_json::to_string(&pending).map_err(|_| error::internal_server_error!())?;
+        self.store
+            .set(&Self::code_key(account_id), &value, self.policy.ttl)?;
+        self.store.delete(&Self::attempts_key(account_id))?;
+
+        let body = match channel {
+            SmsChannel::Sms => format!(
+                "Your verification code is {}. It expires in {} minutes. Never share this code with anyone.",
+                code,
+                self.policy.ttl.div_ceil(60)
+            ),
+            // Digit dipisahkan agar dibacakan satu per satu, lalu diulang
+            SmsChannel::Voice => {
+                let spoken = code.chars().map(String::from).collect::<Vec<String>>().join(", ");
+                format!(
+                    "Your verification code is {}. Again, your code is {}.",
+                    spoken, spoken
+                )
+            }
+        };
+        self.provider
+            .send(&SmsMessage::new(number, channel, &body))?;
+
+        Ok(PhoneCodeSent {
+            phone_number: number.masked(),
+            expires_in: self.policy.ttl,
+        })
+    }
+
+    /**
+    Memverifikasi kode OTP. Kode dibatalkan setelah berhasil digunakan atau setelah terlalu banyak
+    percobaan yang salah. Setiap percobaan dihitung dengan counter atomic sebelum kode dibandingkan,
+    sehingga request paralel tidak dapat mencoba lebih dari `max_attempts` kode.
+
+    # Arguments
+    * `account_id` - Id account pemilik kode
+    * `purpose` - Tujuan kode yang diharapkan
+    * `code` - Kode dari user
+    */
+    fn check_code(
+        &self,
+        account_id: &Uuid,
+        purpose: Purpose,
+        code: &PhoneCode,
+    ) -> Result<PendingCode, error::ApplicationError<'static>> {
+        let now = get_now();
+        let key = Self::code_key(account_id);
+        let value = self.store.get(&key)?.ok_or_else(invalid_code)?;
+        let pending: PendingCode = serde_json::from_str(&value).map_err(|_| invalid_code())?;
+        if pending.purpose != purpose || now >= pending.expires_at {
+            return Err(invalid_code());
+        }
+
+        let attempts_key = Self::attempts_key(account_id);
+        let attempts = self
+            .store
+            .incr_with_ttl(&attempts_key, pending.expires_at - now)?;
+        if attempts > self.policy.max_attempts as u64 {
+            self.store.delete(&key)?;
+            return Err(invalid_code());
+        }
+
+        let code: String = code.code.chars().filter(|c| !c.is_whitespace()).collect();
+        if Token::from(&code).digest() != pending.digest {
+            return Err(invalid_code());
+        }
+        // Hanya satu request yang dapat menggunakan kode, dan kode harus belum diganti
+        if self.store.take(&key)?.as_deref() != Some(value.as_str()) {
+            return Err(invalid_code());
+        }
+        self.store.delete(&attempts_key)?;
+        Ok(pending)
+    }
+
+    /**
+    Mengirim kode verifikasi ke nomor telepon yang akan ditambahkan ke account
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `request` - Nomor telepon dan channel pengiriman
+    */
+    pub fn enroll(
+        &self,
+        session: &Session,
+        request: &PhoneEnrollment,
+    ) -> Result<PhoneCodeSent, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        if !request.phone_number.is_valid() {
+            let mut errors = ValidationErrors::new();
+            errors.add("phone_number", ValidationError::new("invalid_phone_number"));
+            return Err(error::ApplicationError::from_validation_errors(errors));
+        }
+        let account = self.account(session)?;
+        self.send_code(
+            account.id(),
+            Purpose::Enrollment,
+            &request.phone_number,
+            request.channel,
+        )
+    }
+
+    /**
+    Menyimpan nomor telepon sebagai nomor terverifikasi setelah kode yang dikirim ke nomor
+    tersebut dimasukkan
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `code` - Kode yang diterima user
+    */
+    pub fn confirm(
+        &self,
+        session: &Session,
+        code: &PhoneCode,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let mut account = self.account(session)?;
+        let pending = self.check_code(account.id(), Purpose::Enrollment, code)?;
+        account.set_phone(Some(PhoneFactor {
+            number: pending.number,
+            verified_at: get_now(),
+        }));
+        self.accounts.update(&account)
+    }
+
+    /**
+    Mengirim kode login ke nomor telepon terverifikasi milik account
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    * `channel` - SMS atau panggilan suara
+    */
+    pub fn send_login_code(
+        &self,
+        session: &Session,
+        channel: SmsChannel,
+    ) -> Result<PhoneCodeSent, error::ApplicationError<'static>> {
+        let account = self.account(session)?;
+        let number = match account.phone() {
+            Some(phone) => phone.number.clone(),
+            None => {
+                return Err(error::bad_request_error!(
+                    "Sorry, but your account has no verified phone number.",
+                    "phone_not_verified"
+                ))
+            }
+        };
+        self.send_code(account.id(), Purpose::Login, &number, channel)
+    }
+
+    /**
+    Memverifikasi kode login sebagai faktor kedua, lalu menaikkan scope session
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    * `code` - Kode yang diterima user
+    */
+    pub fn verify(
+        &self,
+        session: &mut Session,
+        code: &PhoneCode,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let account = self.account(session)?;
+        let pending = self.check_code(account.id(), Purpose::Login, code)?;
+        // Nomor telepon mungkin sudah diganti setelah kode dikirim
+        if account.phone().map(PhoneFactor::number) != Some(&pending.number) {
+            return Err(invalid_code());
+        }
+
+        let now = get_now();
+        session.record_authentication(
+            match pending.channel {
+                SmsChannel::Sms => AuthenticationMethod::Sms,
+                SmsChannel::Voice => AuthenticationMethod::Telephone,
+            },
+            now,
+        );
+        session
+            .complete_step_up(account.requires_password_change(self.password_policy.max_age, now));
+        if let Some(risk) = &self.risk {
+            risk.complete_step_up(session, now)?;
+        }
+        self.sessions.update(session)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{sms::MockSmsProvider, store::MemoryStore};
+    use super::super::fixture;
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::{AuthenticationScope, MockSessionRepository};
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Repository satu account yang menyimpan perubahan terakhir
+    fn accounts() -> (MockAccountRepository, Arc<Mutex<Account>>, Uuid) {
+        let account = fixture::account();
+        let id = *account.id();
+        let (accounts, stored) = fixture::repository(account);
+        (accounts, stored, id)
+    }
+
+    /// SmsProvider yang menyimpan seluruh pesan terkirim
+    fn provider() -> (MockSmsProvider, Arc<Mutex<Vec<SmsMessage>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut provider = MockSmsProvider::new();
+        let messages = sent.clone();
+        provider.expect_send().returning(move |message| {
+            messages.lock().unwrap().push(message.clone());
+            Ok(())
+        });
+        (provider, sent)
+    }
+
+    fn last_code(sent: &Arc<Mutex<Vec<SmsMessage>>>) -> String {
+        let messages = sent.lock().unwrap();
+        let body = &messages.last().unwrap().body;
+        body.chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit() || *c == ',' || *c == ' ')
+            .filter(char::is_ascii_digit)
+            .collect()
+    }
+
+    fn enrollment(phone_number: &str, channel: SmsChannel) -> PhoneEnrollment {
+        PhoneEnrollment {
+            phone_number: PhoneNumber::from(phone_number),
+            channel,
+        }
+    }
+
+    fn code(code: &str) -> PhoneCode {
+        PhoneCode {
+            code: String::from(code),
+        }
+    }
+
+    #[test]
+    fn test_enroll_and_verify() {
+        let (accounts, stored, account_id) = accounts();
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().times(1).returning(|_| Ok(()));
+        let store = MemoryStore::new();
+        let (provider, sent) = provider();
+        let policy = PasswordPolicy::default();
+        let service = PhoneOtpService::new(&accounts, &sessions, &store, &provider, &policy);
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+
+        let error = service
+            .enroll(&session, &enrollment("0812345", SmsChannel::Sms))
+            .unwrap_err();
+        assert!(error.fields.unwrap()["phone_number"][0].contains("invalid_phone_number"));
+
+        let result = service
+            .enroll(&session, &enrollment("+62 812-3456-7890", SmsChannel::Sms))
+            .unwrap();
+        assert_eq!("+62*********90", result.phone_number);
+        assert_eq!(300, result.expires_in);
+        let enrollment_code = last_code(&sent);
+        assert_eq!(6, enrollment_code.len());
+        assert_eq!(
+            PhoneNumber::from("+6281234567890"),
+            sent.lock().unwrap()[0].to
+        );
+
+        assert!(service.confirm(&session, &code("x")).is_err());
+        service.confirm(&session, &code(&enrollment_code)).unwrap();
+        assert!(stored.lock().unwrap().is_mfa_enabled());
+        // Kode hanya dapat digunakan satu kali
+        assert!(service.confirm(&session, &code(&enrollment_code)).is_err());
+
+        let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        service.send_login_code(&login, SmsChannel::Voice).unwrap();
+        let message = sent.lock().unwrap().last().unwrap().clone();
+        assert_eq!(SmsChannel::Voice, message.channel);
+        let login_code = last_code(&sent);
+        assert_eq!(6, login_code.len());
+
+        // Kode login tidak dapat digunakan untuk enrollment dan sebaliknya
+        assert!(service.confirm(&session, &code(&login_code)).is_err());
+        service.verify(&mut login, &code(&login_code)).unwrap();
+        assert_eq!(AuthenticationScope::Full, login.scope());
+    }
+
+    #[test]
+    fn test_attempt_limit_and_expiry() {
+        let (accounts, _, account_id) = accounts();
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let (provider, sent) = provider();
+        let policy = PasswordPolicy::default();
+        let service = PhoneOtpService::new(&accounts, &sessions, &store, &provider, &policy)
+            .with_policy(PhoneOtpPolicy {
+                max_attempts: 3,
+                ..PhoneOtpPolicy::default()
+            });
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+
+        service
+            .enroll(&session, &enrollment("+6281234567890", SmsChannel::Sms))
+            .unwrap();
+        let valid = last_code(&sent);
+        let wrong = if valid == "000000" {
+            "111111"
+        } else {
+            "000000"
+        };
+        for _ in 0..3 {
+            let error = service.confirm(&session, &code(wrong)).unwrap_err();
+            assert_eq!("invalid_phone_code", error.error);
+        }
+        assert!(service.confirm(&session, &code(&valid)).is_err());
+
+        // Percobaan paralel tetap dibatasi oleh max_attempts
+        service
+            .enroll(&session, &enrollment("+6281234567890", SmsChannel::Sms))
+            .unwrap();
+        let valid = last_code(&sent);
+        let wrong = if valid == "000000" {
+            "111111"
+        } else {
+            "000000"
+        };
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let service =
+                        PhoneOtpService::new(&accounts, &sessions, &store, &provider, &policy)
+                            .with_policy(PhoneOtpPolicy {
+                                max_attempts: 3,
+                                ..PhoneOtpPolicy::default()
+                            });
+                    assert!(service.confirm(&session, &code(wrong)).is_err());
+                });
+            }
+        });
+        assert!(service.confirm(&session, &code(&valid)).is_err());
+
+        let service = PhoneOtpService::new(&accounts, &sessions, &store, &provider, &policy)
+            .with_policy(PhoneOtpPolicy {
+                ttl: 0,
+                ..PhoneOtpPolicy::default()
+            });
+        service
+            .enroll(&session, &enrollment("+6281234567891", SmsChannel::Sms))
+            .unwrap();
+        assert!(service.confirm(&session, &code(&last_code(&sent))).is_err());
+
+        // Login membutuhkan nomor telepon terverifikasi
+        let login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        let error = service
+            .send_login_code(&login, SmsChannel::Sms)
+            .unwrap_err();
+        assert_eq!("phone_not_verified", error.error);
+    }
+
+    #[test]
+    fn test_rate_limit_per_phone_number() {
+        let (accounts, _, account_id) = accounts();
+        let sessions = MockSessionRepository::new();
+        let store = MemoryStore::new();
+        let (provider, sent) = provider();
+        let policy = PasswordPolicy::default();
+        let service = PhoneOtpService::new(&accounts, &sessions, &store, &provider, &policy)
+            .with_policy(PhoneOtpPolicy {
+                max_sends: 2,
+                ..PhoneOtpPolicy::default()
+            });
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+
+        for _ in 0..2 {
+            service
+                .enroll(&session, &enrollment("+6281234567890", SmsChannel::Sms))
+                .unwrap();
+        }
+        let error = service
+            .enroll(
+                &session,
+                &enrollment("+62 812 3456 7890", SmsChannel::Voice),
+            )
+            .unwrap_err();
+        assert_eq!("sms_rate_limited", error.error);
+        assert_eq!(2, sent.lock().unwrap().len());
+
+        service
+            .enroll(&session, &enrollment("+6281234567891", SmsChannel::Sms))
+            .unwrap();
+    }
+}
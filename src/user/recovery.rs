@@ -0,0 +1,560 @@
+use super::super::sharedkernel::{
+    error,
+    function::get_now,
+    mailer::{Mailer, Message},
+    password::{Hash, Password},
+    password_policy::PasswordPolicy,
+    store::KeyValueStore,
+};
+use super::account::{Account, AccountStatus};
+use super::lockout::LockoutGuard;
+use super::repository::AccountRepository;
+use super::risk::RiskEngine;
+use super::session::{AuthenticationMethod, Session, SessionRepository};
+use rand::{rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use validator::{ValidationError, ValidationErrors};
+
+// Karakter recovery code, tanpa karakter yang mudah tertukar (0/o, 1/l/i)
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+// Panjang recovery code tanpa pemisah
+const CODE_LENGTH: usize = 10;
+// Masa berlaku penanda recovery code yang sudah digunakan (dalam detik), cukup lama hingga account
+// tersimpan tanpa code tersebut
+const USED_CODE_TTL: u64 = 86400;
+
+/// Kebijakan recovery code MFA
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RecoveryCodePolicy {
+    /// Jumlah recovery code yang dibuat
+    pub count: usize,
+    /// User diperingatkan jika sisa recovery code kurang dari atau sama dengan nilai ini
+    pub warning_threshold: usize,
+}
+
+impl Default for RecoveryCodePolicy {
+    fn default() -> Self {
+        RecoveryCodePolicy {
+            count: 10,
+            warning_threshold: 3,
+        }
+    }
+}
+
+/// Recovery code baru yang hanya ditampilkan satu kali kepada user
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub codes: Vec<String>,
+}
+
+/// Sisa recovery code milik account
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct RecoveryStatus {
+    pub remaining: usize,
+    /// True jika sisa recovery code sudah sedikit dan sebaiknya dibuat ulang
+    pub low: bool,
+}
+
+impl RecoveryStatus {
+    fn from(account: &Account, policy: &RecoveryCodePolicy) -> RecoveryStatus {
+        let remaining = account.recovery_codes_remaining();
+        RecoveryStatus {
+            remaining,
+            low: remaining <= policy.warning_threshold,
+        }
+    }
+}
+
+/// Recovery code yang dikirim oleh user saat login
+#[derive(Debug, Deserialize)]
+pub struct RecoveryCode {
+    code: String,
+}
+
+/// Permintaan membuat ulang recovery code, membutuhkan password untuk autentikasi ulang
+#[derive(Debug, Deserialize)]
+pub struct RecoveryCodeRegeneration {
+    password: Password,
+}
+
+// Huruf kecil tanpa pemisah, sehingga "ABCDE-FGHJK" dan "abcdefghjk" dianggap sama
+fn normalize(code: &str) -> Password {
+    let code: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    Password::from(&code)
+}
+
+/**
+Membuat recovery code baru untuk account dan menyimpan hash-nya, menggantikan recovery code
+sebelumnya. Recovery code dikembalikan dalam format `xxxxx-xxxxx`.
+
+# Arguments
+* `account` - Account pemilik recovery code
+* `policy` - Kebijakan recovery code
+*/
+pub fn generate(
+    account: &mut Account,
+    policy: &RecoveryCodePolicy,
+) -> Result<Vec<String>, error::ApplicationError<'static>> {
+    let mut codes = Vec::with_capacity(policy.count);
+    let mut hashes = Vec::with_capacity(policy.count);
+    for _ in 0..policy.count {
+        let code: String = (0..CODE_LENGTH)
+            .map(|_| ALPHABET[OsRng.gen_range(0..ALPHABET.len())] as char)
+            .collect();
+        hashes.push(Hash::generate(&normalize(&code))?);
+        codes.push(format!(
+            "{}-{}",
+            &code[..CODE_LENGTH / 2],
+            &code[CODE_LENGTH / 2..]
+        ));
+    }
+    account.set_recovery_codes(hashes);
+    Ok(codes)
+}
+
+/// Use case penggunaan dan pembuatan ulang recovery code
+pub struct RecoveryCodeService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    password_policy: &'a PasswordPolicy,
+    store: &'a dyn KeyValueStore,
+    lockout: LockoutGuard<'a>,
+    policy: RecoveryCodePolicy,
+    mailer: Option<&'a dyn Mailer>,
+    risk: Option<RiskEngine<'a>>,
+}
+
+impl<'a> RecoveryCodeService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        password_policy: &'a PasswordPolicy,
+        store: &'a dyn KeyValueStore,
+        lockout: LockoutGuard<'a>,
+    ) -> RecoveryCodeService<'a> {
+        RecoveryCodeService {
+            accounts,
+            sessions,
+            password_policy,
+            store,
+            lockout,
+            policy: RecoveryCodePolicy::default(),
+            mailer: None,
+            risk: None,
+        }
+    }
+
+    /// Mengganti jumlah recovery code dan batas peringatan
+    pub fn with_policy(mut self, policy: RecoveryCodePolicy) -> RecoveryCodeService<'a> {
+        self.policy = policy;
+        self
+    }
+
+    /// Mengirim email peringatan ketika sisa recovery code sudah sedikit
+    pub fn with_mailer(mut self, mailer: &'a dyn Mailer) -> RecoveryCodeService<'a> {
+        self.mailer = Some(mailer);
+        self
+    }
+
+    /// Mencatat penyelesaian step up pada riwayat login, agar perangkat user dikenal oleh risk engine
+    pub fn with_risk_engine(mut self, risk: RiskEngine<'a>) -> RecoveryCodeService<'a> {
+        self.risk = Some(risk);
+        self
+    }
+
+    fn account(&self, session: &Session) -> Result<Account, error::ApplicationError<'static>> {
+        match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => Ok(account),
+            _ => Err(error::unauthorized_error!()),
+        }
+    }
+
+    /**
+    Menandai recovery code sebagai sudah digunakan secara atomic. Request paralel dapat membaca
+    account yang sama sebelum account tersimpan, sehingga hanya penanda di store yang menjamin
+    setiap code digunakan satu kali.
+    */
+    fn consume(
+        &self,
+        account: &Account,
+        hash: &Hash,
+    ) -> Result<bool, error::ApplicationError<'static>> {
+        let digest = Sha256::digest(hash.expose_secret().as_bytes());
+        self.store.set_if_absent(
+            &format!("recovery:used:{}:{:x}", account.id(), digest),
+            "1",
+            USED_CODE_TTL,
+        )
+    }
+
+    /**
+    Mengembalikan sisa recovery code milik user
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    */
+    pub fn status(
+        &self,
+        session: &Session,
+    ) -> Result<RecoveryStatus, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let account = self.account(session)?;
+        Ok(RecoveryStatus::from(&account, &self.policy))
+    }
+
+    /**
+    Membuat ulang recovery code setelah user memasukkan password kembali. Password yang salah
+    dihitung sebagai kegagalan login account.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `request` - Password saat ini
+    */
+    pub fn regenerate(
+        &self,
+        session: &Session,
+        request: &RecoveryCodeRegeneration,
+    ) -> Result<RecoveryCodes, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let mut account = self.account(session)?;
+        let now = get_now();
+        self.lockout
+            .ensure_account_not_locked(account.email(), now)?;
+        if !account
+            .hash()
+            .verify_password(&request.password)
+            .unwrap_or(false)
+        {
+            self.lockout.record_account_failure(account.email(), now)?;
+            let mut errors = ValidationErrors::new();
+            errors.add("password", ValidationError::new("invalid_current_password"));
+            return Err(error::ApplicationError::from_validation_errors(errors));
+        }
+        if !account.is_mfa_enabled() {
+            return Err(error::bad_request_error!(
+                "Please enable two-factor authentication first.",
+                "mfa_not_enabled"
+            ));
+        }
+
+        let codes = generate(&mut account, &self.policy)?;
+        self.accounts.update(&account)?;
+        Ok(RecoveryCodes { codes })
+    }
+
+    /**
+    Menggunakan recovery code sebagai pengganti faktor kedua saat login, lalu menaikkan scope
+    session. User diperingatkan jika sisa recovery code sudah sedikit. Code yang salah dihitung
+    bersama kode TOTP yang salah, sehingga faktor kedua account terkunci setelah threshold lockout.
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    * `code` - Recovery code dari user
+    */
+    pub fn verify(
+        &self,
+        session: &mut Session,
+        code: &RecoveryCode,
+    ) -> Result<RecoveryStatus, error::ApplicationError<'static>> {
+        let mut account = self.account(session)?;
+        let now = get_now();
+        self.lockout.ensure_factor_not_locked(account.id(), now)?;
+        let consumed = account.is_mfa_enabled()
+            && match account.use_recovery_code(&normalize(&code.code)) {
+                Some(hash) => self.consume(&account, &hash)?,
+                None => false,
+            };
+        if !consumed {
+            self.lockout.record_factor_failure(account.id(), now)?;
+            return Err(error::unauthorized_error!(
+                "Sorry, but the recovery code is incorrect or has already been used.",
+                "invalid_recovery_code"
+            ));
+        }
+        self.lockout.record_factor_success(account.id())?;
+        self.accounts.update(&account)?;
+
+        session.record_authentication(AuthenticationMethod::Otp, now);
+        session
+            .complete_step_up(account.requires_password_change(self.password_policy.max_age, now));
+        if let Some(risk) = &self.risk {
+            risk.complete_step_up(session, now)?;
+        }
+        self.sessions.update(session)?;
+
+        let status = RecoveryStatus::from(&account, &self.policy);
+        if let (true, Some(mailer)) = (status.low, self.mailer) {
+            let body = format!(
+                "A recovery code was just used to sign in to your account. You have {} recovery code(s) left.\n\nPlease generate new recovery codes from your security settings. If this wasn't you, change your password immediately.",
+                status.remaining
+            );
+            // Kegagalan pengiriman email tidak menggagalkan login
+            let _ = mailer.send(&Message::new(
+                account.email(),
+                "You are running low on recovery codes",
+                &body,
+            ));
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{email::Email, mailer::MockMailer, store::MemoryStore};
+    use super::super::fixture::{self, PASSWORD};
+    use super::super::lockout::LockoutPolicy;
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::{AuthenticationScope, MockSessionRepository};
+    use super::*;
+
+    /// Account dengan MFA aktif (passkey tiruan) beserta recovery code-nya
+    fn mfa_account(policy: &RecoveryCodePolicy) -> (Account, Vec<String>) {
+        let mut account = fixture::account();
+        account.add_webauthn_credential(
+            serde_json::from_value(serde_json::json!({
+                "id": "abc",
+                "public_key": "",
+                "sign_count": 0,
+                "name": "Passkey",
+                "created_at": 0,
+                "last_used_at": null
+            }))
+            .unwrap(),
+        );
+        let codes = generate(&mut account, policy).unwrap();
+        (account, codes)
+    }
+
+    /// Repository satu account dengan MFA aktif yang menyimpan perubahan terakhir
+    fn accounts(policy: &RecoveryCodePolicy) -> (MockAccountRepository, Vec<String>, Session) {
+        let (account, codes) = mfa_account(policy);
+        let session = Session::new(account.id(), AuthenticationScope::StepUp, 60);
+        let (accounts, _) = fixture::repository(account);
+        (accounts, codes, session)
+    }
+
+    fn code(code: &str) -> RecoveryCode {
+        RecoveryCode {
+            code: String::from(code),
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let policy = RecoveryCodePolicy::default();
+        let (_, codes, _) = accounts(&policy);
+        assert_eq!(10, codes.len());
+        for code in &codes {
+            assert_eq!(11, code.len());
+            assert_eq!(Some(5), code.find('-'));
+        }
+        let mut unique = codes.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(10, unique.len());
+    }
+
+    #[test]
+    fn test_verify_once() {
+        let policy = RecoveryCodePolicy {
+            count: 5,
+            warning_threshold: 3,
+        };
+        let (accounts, codes, mut session) = accounts(&policy);
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let password_policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = RecoveryCodeService::new(
+            &accounts,
+            &sessions,
+            &password_policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+        )
+        .with_policy(policy.clone());
+
+        let error = service
+            .verify(&mut session, &code("aaaaa-aaaaa"))
+            .unwrap_err();
+        assert_eq!("invalid_recovery_code", error.error);
+        assert_eq!(AuthenticationScope::StepUp, session.scope());
+
+        // Huruf besar dan tanpa pemisah tetap diterima
+        let status = service
+            .verify(
+                &mut session,
+                &code(&codes[0].to_uppercase().replace('-', " ")),
+            )
+            .unwrap();
+        assert_eq!(
+            RecoveryStatus {
+                remaining: 4,
+                low: false
+            },
+            status
+        );
+        assert_eq!(AuthenticationScope::Full, session.scope());
+
+        assert!(service.verify(&mut session, &code(&codes[0])).is_err());
+        let status = service.verify(&mut session, &code(&codes[1])).unwrap();
+        assert!(status.low);
+        assert_eq!(status, service.status(&session).unwrap());
+    }
+
+    #[test]
+    fn test_parallel_verify_accepts_code_once() {
+        let policy = RecoveryCodePolicy::default();
+        let (account, codes) = mfa_account(&policy);
+        let account_id = *account.id();
+        // Seluruh request paralel membaca account sebelum salah satunya tersimpan
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(move |_| Ok(Some(account.clone())));
+        accounts.expect_update().returning(|_| Ok(()));
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let password_policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+
+        let accepted = std::sync::atomic::AtomicU32::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let service = RecoveryCodeService::new(
+                        &accounts,
+                        &sessions,
+                        &password_policy,
+                        &store,
+                        LockoutGuard::new(&store, &lockout_policy),
+                    );
+                    let mut session = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+                    if service.verify(&mut session, &code(&codes[0])).is_ok() {
+                        accepted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert_eq!(1, accepted.into_inner());
+    }
+
+    #[test]
+    fn test_lock_after_wrong_codes() {
+        let policy = RecoveryCodePolicy::default();
+        let (accounts, codes, mut session) = accounts(&policy);
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let password_policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy {
+            account_threshold: 3,
+            ..LockoutPolicy::default()
+        };
+        let service = RecoveryCodeService::new(
+            &accounts,
+            &sessions,
+            &password_policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+        );
+
+        for _ in 0..3 {
+            let error = service
+                .verify(&mut session, &code("aaaaa-aaaaa"))
+                .unwrap_err();
+            assert_eq!("invalid_recovery_code", error.error);
+        }
+
+        // Code yang benar juga ditolak selama faktor kedua terkunci
+        let error = service.verify(&mut session, &code(&codes[0])).unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!("mfa_locked", error.error);
+        assert_eq!(AuthenticationScope::StepUp, session.scope());
+    }
+
+    #[test]
+    fn test_warn_when_few_remain() {
+        let policy = RecoveryCodePolicy {
+            count: 2,
+            warning_threshold: 1,
+        };
+        let (accounts, codes, mut session) = accounts(&policy);
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
+            .withf(|message| message.body.contains("1 recovery code(s) left"))
+            .times(1)
+            .returning(|_| Ok(()));
+        let password_policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = RecoveryCodeService::new(
+            &accounts,
+            &sessions,
+            &password_policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+        )
+        .with_policy(policy)
+        .with_mailer(&mailer);
+
+        assert!(service.verify(&mut session, &code(&codes[1])).unwrap().low);
+    }
+
+    #[test]
+    fn test_regenerate() {
+        let policy = RecoveryCodePolicy::default();
+        let (accounts, codes, mut session) = accounts(&policy);
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let password_policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = RecoveryCodeService::new(
+            &accounts,
+            &sessions,
+            &password_policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+        );
+
+        let request = |password: &str| RecoveryCodeRegeneration {
+            password: Password::from(password),
+        };
+        let error = service
+            .regenerate(&session, &request(PASSWORD))
+            .unwrap_err();
+        assert_eq!("step_up_required", error.error);
+        session.grant_full_access();
+
+        let error = service
+            .regenerate(&session, &request("WrongPassw0rd!"))
+            .unwrap_err();
+        assert!(error.fields.unwrap()["password"][0].contains("invalid_current_password"));
+        assert_eq!(
+            1,
+            LockoutGuard::new(&store, &lockout_policy)
+                .failures(&Email::from("harun@digitalsekuriti.id"))
+                .unwrap()
+        );
+
+        let regenerated = service.regenerate(&session, &request(PASSWORD)).unwrap();
+        assert_eq!(10, regenerated.codes.len());
+        assert!(service.verify(&mut session, &code(&codes[0])).is_err());
+        service
+            .verify(&mut session, &code(&regenerated.codes[0]))
+            .unwrap();
+    }
+}
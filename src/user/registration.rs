@@ -1,6 +1,23 @@
-use super::super::sharedkernel::{email::Email, password::Password};
+use super::super::sharedkernel::{
+    breach::{BreachCorpus, BreachScreening},
+    client::ClientInfo,
+    email::Email,
+    error,
+    function::get_now,
+    password::Password,
+    password_policy::PasswordPolicy,
+    proof_of_work::{ProofOfWork, Solution},
+    rate_limit::RateLimiter,
+    strength::{self, Strength},
+};
+use super::account::{Account, AccountStatus};
+use super::repository::AccountRepository;
+use super::verification::EmailVerificationService;
 use serde::Deserialize;
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
+
+// Nama route registrasi pada konfigurasi rate limit
+pub const REGISTRATION_ROUTE: &str = "POST /accounts";
 
 #[derive(Debug, Validate, Deserialize)]
 pub struct Registration {
@@ -8,12 +25,182 @@ pub struct Registration {
     email: Email,
     #[validate]
     password: Password,
+    /// Solusi proof of work, wajib jika registrasi dari IP client melebihi threshold
+    #[serde(default)]
+    proof_of_work: Option<Solution>,
+}
+
+impl Registration {
+    /**
+    Mengembalikan object yang dapat divalidasi dengan password policy yang dikonfigurasi,
+    menggantikan PasswordPolicy::default() yang digunakan oleh `validate()`
+
+    # Arguments
+    * `policy` - Reference dari PasswordPolicy yang berlaku
+    */
+    pub fn check<'a>(&'a self, policy: &'a PasswordPolicy) -> RegistrationCheck<'a> {
+        RegistrationCheck {
+            registration: self,
+            policy,
+        }
+    }
+}
+
+/// Pasangan registration dan password policy yang siap divalidasi
+pub struct RegistrationCheck<'a> {
+    registration: &'a Registration,
+    policy: &'a PasswordPolicy,
+}
+
+impl Validate for RegistrationCheck<'_> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let result = ValidationErrors::merge(Ok(()), "email", self.registration.email.validate());
+        ValidationErrors::merge(
+            result,
+            "password",
+            self.policy
+                .check(&self.registration.password)
+                .with_user_inputs(strength::email_inputs(&self.registration.email))
+                .validate(),
+        )
+    }
+}
+
+/// Use case pendaftaran account baru
+pub struct RegistrationService<'a> {
+    accounts: &'a dyn AccountRepository,
+    policy: &'a PasswordPolicy,
+    breach_corpus: Option<&'a dyn BreachCorpus>,
+    proof_of_work: Option<ProofOfWork<'a>>,
+    verification: Option<EmailVerificationService<'a>>,
+    rate_limiter: Option<RateLimiter<'a>>,
+}
+
+impl<'a> RegistrationService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        policy: &'a PasswordPolicy,
+    ) -> RegistrationService<'a> {
+        RegistrationService {
+            accounts,
+            policy,
+            breach_corpus: None,
+            proof_of_work: None,
+            verification: None,
+            rate_limiter: None,
+        }
+    }
+
+    /// Mengaktifkan pemeriksaan password terhadap breach corpus
+    pub fn with_breach_corpus(mut self, corpus: &'a dyn BreachCorpus) -> RegistrationService<'a> {
+        self.breach_corpus = Some(corpus);
+        self
+    }
+
+    /// Mengaktifkan rate limit untuk route `REGISTRATION_ROUTE`
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter<'a>) -> RegistrationService<'a> {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Mewajibkan proof of work ketika registrasi dari satu IP melebihi threshold
+    pub fn with_proof_of_work(mut self, proof_of_work: ProofOfWork<'a>) -> RegistrationService<'a> {
+        self.proof_of_work = Some(proof_of_work);
+        self
+    }
+
+    /// Mewajibkan verifikasi email, account baru tidak dapat digunakan hingga email diverifikasi
+    pub fn with_email_verification(
+        mut self,
+        verification: EmailVerificationService<'a>,
+    ) -> RegistrationService<'a> {
+        self.verification = Some(verification);
+        self
+    }
+
+    /**
+    Memvalidasi dan mendaftarkan account baru. Jika verifikasi email diaktifkan, account langsung
+    disimpan dengan status `PendingVerification` dan email verifikasi dikirim, sehingga account
+    tidak pernah aktif sebelum email diverifikasi.
+
+    # Arguments
+    * `registration` - Data registrasi dari user
+    * `client` - Informasi client yang melakukan registrasi
+    */
+    pub fn register(
+        &self,
+        registration: Registration,
+        client: &ClientInfo,
+    ) -> Result<Account, error::ApplicationError<'static>> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .check(REGISTRATION_ROUTE, client, None, get_now())?
+                .ensure_allowed()?;
+        }
+        if let Some(proof_of_work) = &self.proof_of_work {
+            proof_of_work.ensure_solved(registration.proof_of_work.as_ref(), client, get_now())?;
+        }
+
+        if let Some(err) = error::ApplicationError::validate(registration.check(self.policy)) {
+            return Err(err);
+        }
+
+        if let Some(corpus) = self.breach_corpus {
+            BreachScreening::new(corpus).check(&registration.password)?;
+        }
+
+        let status = match self.verification {
+            Some(_) => AccountStatus::PendingVerification,
+            None => AccountStatus::Active,
+        };
+        let account = self.accounts.register(registration, status)?;
+        if let Some(verification) = &self.verification {
+            verification.start(&account)?;
+        }
+        Ok(account)
+    }
+
+    /**
+    Mengestimasi kekuatan password pada registrasi, dengan email sebagai penalti. Dapat digunakan
+    oleh form registrasi untuk menampilkan strength meter.
+
+    # Arguments
+    * `registration` - Data registrasi dari user
+    */
+    pub fn strength(&self, registration: &Registration) -> Strength {
+        strength::estimate(
+            registration.password.expose_secret(),
+            &strength::email_inputs(&registration.email),
+        )
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use super::super::super::sharedkernel::{
+        breach::MockBreachCorpus,
+        mailer::MockMailer,
+        password::Hash,
+        proof_of_work::{self, ProofOfWorkPolicy},
+        rate_limit::RateLimitConfig,
+        store::MemoryStore,
+    };
+    use super::super::account::AccountRole;
+    use super::super::repository::MockAccountRepository;
     use super::*;
 
+    fn registration(password: &str) -> Registration {
+        serde_json::from_str(&format!(
+            r#"{{"email": "harun@digitalsekuriti.id", "password": "{}"}}"#,
+            password
+        ))
+        .unwrap()
+    }
+
+    fn client() -> ClientInfo {
+        ClientInfo::new("10.0.0.1".parse().unwrap())
+    }
+
     #[test]
     fn test_deserialize() {
         let payload: &str = r#"{
@@ -26,6 +213,210 @@ mod test {
             String::from("harun@digitalsekuriti.id"),
             v.email.to_string()
         );
-        assert_eq!(String::from("1234qweR!"), v.password.to_string());
+        assert_eq!("1234qweR!", v.password.expose_secret());
+        assert!(!format!("{:?}", v).contains("1234qweR!"));
+    }
+
+    #[test]
+    fn test_check_with_policy() {
+        let payload: &str = r#"{
+            "email": "harun@digitalsekuriti.id",
+            "password":"correct horse battery staple"
+        }"#;
+
+        let v: Registration = serde_json::from_str(payload).unwrap();
+        assert!(v.validate().is_err());
+
+        let policy = PasswordPolicy {
+            min_length: 16,
+            require_uppercase: false,
+            require_digit: false,
+            ..PasswordPolicy::default()
+        };
+        assert!(v.check(&policy).validate().is_ok());
+    }
+
+    #[test]
+    fn test_register_rejects_breached_password() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_register().times(0);
+        let mut corpus = MockBreachCorpus::new();
+        corpus.expect_contains().returning(|_| Ok(true));
+        let policy = PasswordPolicy::default();
+
+        let error = RegistrationService::new(&accounts, &policy)
+            .with_breach_corpus(&corpus)
+            .register(registration("Password1!"), &client())
+            .unwrap_err();
+
+        assert!(error.fields.unwrap()["password"][0].contains("password_breached"));
+    }
+
+    #[test]
+    fn test_register_rejects_policy_violation() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_register().times(0);
+        let policy = PasswordPolicy::default();
+
+        let error = RegistrationService::new(&accounts, &policy)
+            .register(registration("password"), &client())
+            .unwrap_err();
+
+        assert!(error.fields.unwrap().contains_key("password"));
+    }
+
+    #[test]
+    fn test_register_rejects_password_containing_email() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_register().times(0);
+        let policy = PasswordPolicy {
+            min_strength_score: Some(3),
+            ..PasswordPolicy::default()
+        };
+        let service = RegistrationService::new(&accounts, &policy);
+
+        let v = registration("Digitalsekuriti#1");
+        assert!(service.strength(&v).score < 3);
+
+        let error = service.register(v, &client()).unwrap_err();
+        let fields = error.fields.unwrap();
+        assert!(fields["password"][0].contains("password_too_weak"));
+    }
+
+    #[test]
+    fn test_register() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_register()
+            .withf(|_, status| *status == AccountStatus::Active)
+            .times(1)
+            .returning(|r, _| {
+                Ok(Account::new(
+                    Email::from(&r.email.to_string()),
+                    Hash::from("expected_hash"),
+                    AccountRole::User,
+                ))
+            });
+        let mut corpus = MockBreachCorpus::new();
+        corpus.expect_contains().returning(|_| Ok(false));
+        let policy = PasswordPolicy::default();
+
+        let result = RegistrationService::new(&accounts, &policy)
+            .with_breach_corpus(&corpus)
+            .register(registration("MypassworD1234!"), &client());
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_register_with_email_verification() {
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_register()
+            .withf(|_, status| *status == AccountStatus::PendingVerification)
+            .times(1)
+            .returning(|r, _| {
+                let mut account = Account::new(
+                    Email::from(&r.email.to_string()),
+                    Hash::from("expected_hash"),
+                    AccountRole::User,
+                );
+                account.require_email_verification();
+                Ok(account)
+            });
+        // Status tidak diubah dengan write kedua setelah account disimpan
+        accounts.expect_update().times(0);
+        let store = MemoryStore::new();
+        let mut mailer = MockMailer::new();
+        mailer
+            .expect_send()
+            .withf(|message| {
+                message
+                    .body
+                    .contains("https://brokoli.id/verify-email?token=")
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+        let policy = PasswordPolicy::default();
+
+        let account = RegistrationService::new(&accounts, &policy)
+            .with_email_verification(EmailVerificationService::new(
+                &accounts,
+                &store,
+                &mailer,
+                "https://brokoli.id/verify-email",
+            ))
+            .register(registration("MypassworD1234!"), &client())
+            .unwrap();
+
+        assert_eq!(AccountStatus::PendingVerification, *account.status());
+        assert!(account.email_verified_at().is_none());
+    }
+
+    #[test]
+    fn test_register_with_rate_limiter() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_register().times(1).returning(|r, _| {
+            Ok(Account::new(
+                Email::from(&r.email.to_string()),
+                Hash::from("expected_hash"),
+                AccountRole::User,
+            ))
+        });
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let rate_limit_config: RateLimitConfig = serde_json::from_str(
+            r#"{"routes": {"POST /accounts": {"algorithm": "sliding_window", "key": "ip", "limit": 1, "window": 3600}}}"#,
+        )
+        .unwrap();
+        let service = RegistrationService::new(&accounts, &policy)
+            .with_rate_limiter(RateLimiter::new(&store, &rate_limit_config));
+
+        assert!(service
+            .register(registration("MypassworD1234!"), &client())
+            .is_ok());
+        let error = service
+            .register(registration("MypassworD1234!"), &client())
+            .unwrap_err();
+        assert_eq!(429, error.code);
+        assert!(error.headers.iter().any(|(name, _)| *name == "Retry-After"));
+    }
+
+    #[test]
+    fn test_register_requires_proof_of_work() {
+        let mut accounts = MockAccountRepository::new();
+        accounts.expect_register().times(2).returning(|r, _| {
+            Ok(Account::new(
+                Email::from(&r.email.to_string()),
+                Hash::from("expected_hash"),
+                AccountRole::User,
+            ))
+        });
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let pow_policy = ProofOfWorkPolicy {
+            threshold: 1,
+            base_difficulty: 4,
+            ..ProofOfWorkPolicy::default()
+        };
+        let pow = ProofOfWork::new(&store, &pow_policy, b"secret", "registration");
+        let challenge = pow.issue(&client(), get_now()).unwrap();
+        let service = RegistrationService::new(&accounts, &policy).with_proof_of_work(pow);
+
+        assert!(service
+            .register(registration("MypassworD1234!"), &client())
+            .is_ok());
+        let error = service
+            .register(registration("MypassworD1234!"), &client())
+            .unwrap_err();
+        assert_eq!("proof_of_work_required", error.error);
+
+        let solution = serde_json::to_string(&proof_of_work::solve(&challenge)).unwrap();
+        let registration: Registration = serde_json::from_str(&format!(
+            r#"{{"email": "harun@digitalsekuriti.id", "password": "MypassworD1234!", "proof_of_work": {}}}"#,
+            solution
+        ))
+        .unwrap();
+        assert!(service.register(registration, &client()).is_ok());
     }
 }
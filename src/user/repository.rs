@@ -1,13 +1,20 @@
-use super::super::sharedkernel::error;
-use super::account::Account;
+use super::super::sharedkernel::{email::Email, error};
+use super::account::{Account, AccountStatus};
 use super::registration::Registration;
 use mockall::*;
 
 #[automock]
 pub trait AccountRepository {
+    /// Menyimpan account baru dengan status awal `status`, misal `PendingVerification`
     fn register(
         &self,
         registration: Registration,
+        status: AccountStatus,
     ) -> Result<Account, error::ApplicationError<'static>>;
     fn get_by_id(&self, id: &str) -> Result<Option<Account>, error::ApplicationError<'static>>;
+    fn get_by_email(
+        &self,
+        email: &Email,
+    ) -> Result<Option<Account>, error::ApplicationError<'static>>;
+    fn update(&self, account: &Account) -> Result<(), error::ApplicationError<'static>>;
 }
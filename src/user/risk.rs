@@ -0,0 +1,572 @@
+use super::super::sharedkernel::{
+    client::ClientInfo,
+    error,
+    function::to_hex,
+    geoip::{GeoIpDatabase, GeoLocation},
+    mailer::{Mailer, Message},
+};
+use super::account::Account;
+use super::session::Session;
+use mockall::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// Sinyal risiko yang ditemukan pada saat login
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskSignal {
+    NewDevice,
+    NewIp,
+    NewAsn,
+    ImpossibleTravel,
+    RecentFailures,
+}
+
+/// Tindakan yang diambil berdasarkan skor risiko
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskDecision {
+    Allow,
+    /// Login diizinkan, user menerima notifikasi email
+    Notify,
+    /// Login membutuhkan faktor autentikasi tambahan
+    StepUp,
+    Block,
+}
+
+/// Bobot setiap sinyal dan batas skor untuk setiap keputusan
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RiskPolicy {
+    pub new_device_score: u32,
+    pub new_ip_score: u32,
+    pub new_asn_score: u32,
+    pub impossible_travel_score: u32,
+    /// Skor untuk setiap kegagalan login sebelumnya, hingga `max_failure_score`
+    pub failure_score: u32,
+    pub max_failure_score: u32,
+    pub notify_threshold: u32,
+    pub step_up_threshold: u32,
+    pub block_threshold: u32,
+    /// Kecepatan perpindahan maksimal yang masih masuk akal (km/jam)
+    pub max_travel_speed: u32,
+    /// Jumlah login terakhir yang dianggap sebagai perangkat / jaringan yang dikenal
+    pub history_depth: usize,
+}
+
+impl Default for RiskPolicy {
+    fn default() -> Self {
+        RiskPolicy {
+            new_device_score: 30,
+            new_ip_score: 10,
+            new_asn_score: 20,
+            impossible_travel_score: 60,
+            failure_score: 10,
+            max_failure_score: 30,
+            notify_threshold: 20,
+            step_up_threshold: 40,
+            block_threshold: 90,
+            max_travel_speed: 1000,
+            history_depth: 20,
+        }
+    }
+}
+
+/// Hasil penilaian risiko satu percobaan login, disimpan sebagai riwayat login account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoginEvent {
+    _id: Uuid,
+    account_id: Uuid,
+    ip: IpAddr,
+    /// SHA-256 dari fingerprint perangkat
+    device: Option<String>,
+    location: Option<GeoLocation>,
+    score: u32,
+    signals: Vec<RiskSignal>,
+    decision: RiskDecision,
+    /// Waktu step up diselesaikan, hanya untuk login dengan keputusan `StepUp`
+    #[serde(default)]
+    verified_at: Option<u64>,
+    created_at: u64,
+}
+
+impl LoginEvent {
+    pub fn id(&self) -> &Uuid {
+        &self._id
+    }
+
+    pub fn account_id(&self) -> &Uuid {
+        &self.account_id
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn signals(&self) -> &[RiskSignal] {
+        &self.signals
+    }
+
+    pub fn decision(&self) -> RiskDecision {
+        self.decision
+    }
+
+    pub fn verified_at(&self) -> Option<u64> {
+        self.verified_at
+    }
+
+    /**
+    Login dianggap berasal dari perangkat / jaringan milik user jika tidak diblokir, dan untuk
+    keputusan `StepUp` hanya setelah faktor tambahan berhasil diverifikasi. Tanpa aturan ini,
+    penyerang yang mengetahui password cukup login dua kali dari perangkat yang sama.
+    */
+    fn is_trusted(&self) -> bool {
+        match self.decision {
+            RiskDecision::Block => false,
+            RiskDecision::StepUp => self.verified_at.is_some(),
+            _ => true,
+        }
+    }
+}
+
+#[automock]
+pub trait LoginEventRepository {
+    fn create(&self, event: &LoginEvent) -> Result<(), error::ApplicationError<'static>>;
+    /// Mengembalikan maksimal `limit` login terakhir milik account, yang terbaru lebih dulu
+    fn recent(
+        &self,
+        account_id: &Uuid,
+        limit: usize,
+    ) -> Result<Vec<LoginEvent>, error::ApplicationError<'static>>;
+    /// Mencatat waktu step up diselesaikan untuk login dengan id `event_id`
+    fn verify(&self, event_id: &Uuid, now: u64) -> Result<(), error::ApplicationError<'static>>;
+}
+
+// Fingerprint perangkat tidak disimpan apa adanya
+fn device_digest(device: &str) -> String {
+    to_hex(&Sha256::digest(device.as_bytes()))
+}
+
+/// Menilai risiko setiap login yang berhasil memverifikasi password
+pub struct RiskEngine<'a> {
+    events: &'a dyn LoginEventRepository,
+    policy: &'a RiskPolicy,
+    geoip: Option<&'a GeoIpDatabase>,
+    mailer: Option<&'a dyn Mailer>,
+}
+
+impl<'a> RiskEngine<'a> {
+    pub fn new(events: &'a dyn LoginEventRepository, policy: &'a RiskPolicy) -> RiskEngine<'a> {
+        RiskEngine {
+            events,
+            policy,
+            geoip: None,
+            mailer: None,
+        }
+    }
+
+    /// Mengaktifkan sinyal ASN dan impossible travel menggunakan database GeoIP
+    pub fn with_geoip(mut self, geoip: &'a GeoIpDatabase) -> RiskEngine<'a> {
+        self.geoip = Some(geoip);
+        self
+    }
+
+    /// Mengirim notifikasi email untuk login yang diizinkan dengan notifikasi atau diblokir
+    pub fn with_mailer(mut self, mailer: &'a dyn Mailer) -> RiskEngine<'a> {
+        self.mailer = Some(mailer);
+        self
+    }
+
+    // Mencari sinyal risiko dengan membandingkan login saat ini dengan riwayat login
+    fn signals(
+        &self,
+        history: &[LoginEvent],
+        event: &LoginEvent,
+        recent_failures: u32,
+    ) -> Vec<RiskSignal> {
+        let mut signals = Vec::new();
+
+        // Login pertama tidak memiliki pembanding
+        if !history.is_empty() {
+            if event.device.is_some() && history.iter().all(|known| known.device != event.device) {
+                signals.push(RiskSignal::NewDevice);
+            }
+            if history.iter().all(|known| known.ip != event.ip) {
+                signals.push(RiskSignal::NewIp);
+            }
+
+            let asn = event.location.as_ref().and_then(|location| location.asn);
+            let known_asn =
+                |known: &LoginEvent| known.location.as_ref().and_then(|location| location.asn);
+            if asn.is_some() && history.iter().all(|known| known_asn(known) != asn) {
+                signals.push(RiskSignal::NewAsn);
+            }
+        }
+
+        let previous = history.iter().find(|known| known.location.is_some());
+        if let (Some(previous), Some(location)) = (previous, &event.location) {
+            let distance = previous
+                .location
+                .as_ref()
+                .map_or(0.0, |known| known.distance_km(location));
+            let hours = event.created_at.saturating_sub(previous.created_at).max(1) as f64 / 3600.0;
+            if distance / hours > self.policy.max_travel_speed as f64 {
+                signals.push(RiskSignal::ImpossibleTravel);
+            }
+        }
+
+        if recent_failures > 0 {
+            signals.push(RiskSignal::RecentFailures);
+        }
+
+        signals
+    }
+
+    fn score(&self, signals: &[RiskSignal], recent_failures: u32) -> u32 {
+        signals
+            .iter()
+            .map(|signal| match signal {
+                RiskSignal::NewDevice => self.policy.new_device_score,
+                RiskSignal::NewIp => self.policy.new_ip_score,
+                RiskSignal::NewAsn => self.policy.new_asn_score,
+                RiskSignal::ImpossibleTravel => self.policy.impossible_travel_score,
+                RiskSignal::RecentFailures => recent_failures
+                    .saturating_mul(self.policy.failure_score)
+                    .min(self.policy.max_failure_score),
+            })
+            .sum()
+    }
+
+    fn decision(&self, score: u32) -> RiskDecision {
+        if score >= self.policy.block_threshold {
+            RiskDecision::Block
+        } else if score >= self.policy.step_up_threshold {
+            RiskDecision::StepUp
+        } else if score >= self.policy.notify_threshold {
+            RiskDecision::Notify
+        } else {
+            RiskDecision::Allow
+        }
+    }
+
+    fn notify(&self, account: &Account, event: &LoginEvent) {
+        let mailer = match self.mailer {
+            Some(mailer) => mailer,
+            None => return,
+        };
+        let location = event
+            .location
+            .as_ref()
+            .map(|location| format!(" ({})", location.country))
+            .unwrap_or_default();
+        let (subject, body) = match event.decision {
+            RiskDecision::Block => (
+                "We blocked a sign-in attempt to your account",
+                format!(
+                    "We blocked a sign-in attempt to your account {} from {}{} because it looked suspicious. If this was not you, please change your password immediately.",
+                    account.email(), event.ip, location
+                ),
+            ),
+            _ => (
+                "New sign-in to your account",
+                format!(
+                    "Your account {} was just signed in from {}{}. If this was not you, please change your password immediately.",
+                    account.email(), event.ip, location
+                ),
+            ),
+        };
+
+        // Notifikasi bersifat best-effort, tidak boleh menggagalkan login
+        let _ = mailer.send(&Message::new(account.email(), subject, &body));
+    }
+
+    /**
+    Menilai risiko login, menyimpan hasilnya ke riwayat login account dan mengirim notifikasi jika
+    diperlukan. Login yang diblokir, atau membutuhkan step up yang belum diselesaikan, tidak
+    dianggap sebagai perangkat / jaringan yang dikenal.
+
+    # Arguments
+    * `account` - Account yang passwordnya berhasil diverifikasi
+    * `client` - Informasi client yang melakukan login
+    * `recent_failures` - Jumlah kegagalan login account sebelum login ini
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn assess(
+        &self,
+        account: &Account,
+        client: &ClientInfo,
+        recent_failures: u32,
+        now: u64,
+    ) -> Result<LoginEvent, error::ApplicationError<'static>> {
+        let history: Vec<LoginEvent> = self
+            .events
+            .recent(account.id(), self.policy.history_depth)?
+            .into_iter()
+            .filter(LoginEvent::is_trusted)
+            .collect();
+
+        let mut event = LoginEvent {
+            _id: Uuid::new_v4(),
+            account_id: *account.id(),
+            ip: client.ip,
+            device: client.device.as_deref().map(device_digest),
+            location: self
+                .geoip
+                .and_then(|geoip| geoip.lookup(&client.ip))
+                .cloned(),
+            score: 0,
+            signals: Vec::new(),
+            decision: RiskDecision::Allow,
+            verified_at: None,
+            created_at: now,
+        };
+        event.signals = self.signals(&history, &event, recent_failures);
+        event.score = self.score(&event.signals, recent_failures);
+        event.decision = self.decision(event.score);
+
+        self.events.create(&event)?;
+        if matches!(event.decision, RiskDecision::Notify | RiskDecision::Block) {
+            self.notify(account, &event);
+        }
+
+        Ok(event)
+    }
+
+    /**
+    Mencatat bahwa step up untuk login yang menghasilkan session telah diselesaikan, sehingga
+    perangkat dan jaringan login tersebut dikenal pada login berikutnya. Dipanggil oleh use case
+    faktor tambahan setelah `Session::complete_step_up`.
+
+    # Arguments
+    * `session` - Session yang baru menyelesaikan step up
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn complete_step_up(
+        &self,
+        session: &Session,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        match session.login_event() {
+            Some(event_id) => self.events.verify(event_id, now),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{email::Email, mailer::MockMailer, password::Hash};
+    use super::super::account::AccountRole;
+    use super::super::session::AuthenticationScope;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const NOW: u64 = 1669969200;
+    const DATABASE: &str = "203.0.113.0/24,64500,ID,-6.2088,106.8456
+198.51.100.0/24,64501,ID,-6.2088,106.8456
+192.0.2.0/24,64502,NL,52.3676,4.9041
+";
+
+    fn account() -> Account {
+        Account::new(
+            Email::from("harun@digitalsekuriti.id"),
+            Hash::from("hash"),
+            AccountRole::User,
+        )
+    }
+
+    fn client(ip: &str, device: &str) -> ClientInfo {
+        ClientInfo::new(ip.parse().unwrap()).with_device(device)
+    }
+
+    /// Repository yang menyimpan riwayat login di memory
+    fn events() -> (MockLoginEventRepository, Arc<Mutex<Vec<LoginEvent>>>) {
+        let stored: Arc<Mutex<Vec<LoginEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut events = MockLoginEventRepository::new();
+        let created = stored.clone();
+        events.expect_create().returning(move |event| {
+            created.lock().unwrap().insert(0, event.clone());
+            Ok(())
+        });
+        let recent = stored.clone();
+        events.expect_recent().returning(move |_, limit| {
+            Ok(recent.lock().unwrap().iter().take(limit).cloned().collect())
+        });
+        let verified = stored.clone();
+        events.expect_verify().returning(move |event_id, now| {
+            for event in verified.lock().unwrap().iter_mut() {
+                if event.id() == event_id {
+                    event.verified_at = Some(now);
+                }
+            }
+            Ok(())
+        });
+        (events, stored)
+    }
+
+    #[test]
+    fn test_known_device_and_network() {
+        let (events, stored) = events();
+        let policy = RiskPolicy::default();
+        let geoip = GeoIpDatabase::parse(DATABASE.as_bytes()).unwrap();
+        let engine = RiskEngine::new(&events, &policy).with_geoip(&geoip);
+
+        let first = engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 0, NOW)
+            .unwrap();
+        assert_eq!(RiskDecision::Allow, first.decision());
+        assert!(first.signals().is_empty());
+
+        let second = engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 0, NOW + 3600)
+            .unwrap();
+        assert_eq!(0, second.score());
+        assert_eq!(2, stored.lock().unwrap().len());
+        assert!(!serde_json::to_string(&second).unwrap().contains("laptop"));
+    }
+
+    #[test]
+    fn test_new_device_requires_step_up() {
+        let (events, _) = events();
+        let policy = RiskPolicy::default();
+        let engine = RiskEngine::new(&events, &policy);
+
+        engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 0, NOW)
+            .unwrap();
+        let event = engine
+            .assess(&account(), &client("198.51.100.1", "phone"), 0, NOW + 3600)
+            .unwrap();
+
+        assert_eq!(
+            vec![RiskSignal::NewDevice, RiskSignal::NewIp],
+            event.signals()
+        );
+        assert_eq!(40, event.score());
+        assert_eq!(RiskDecision::StepUp, event.decision());
+    }
+
+    #[test]
+    fn test_unverified_step_up_is_not_trusted() {
+        let (events, _) = events();
+        let policy = RiskPolicy::default();
+        let engine = RiskEngine::new(&events, &policy);
+
+        engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 0, NOW)
+            .unwrap();
+        let attacker = client("198.51.100.1", "phone");
+        let first = engine.assess(&account(), &attacker, 0, NOW + 60).unwrap();
+        assert_eq!(RiskDecision::StepUp, first.decision());
+
+        // Login berikutnya dari perangkat yang sama tetap membutuhkan step up
+        let second = engine.assess(&account(), &attacker, 0, NOW + 120).unwrap();
+        assert_eq!(RiskDecision::StepUp, second.decision());
+
+        // Setelah step up diselesaikan, perangkat dikenal
+        let mut session = Session::new(account().id(), AuthenticationScope::StepUp, 60);
+        session.link_login_event(second.id());
+        engine.complete_step_up(&session, NOW + 130).unwrap();
+        let third = engine.assess(&account(), &attacker, 0, NOW + 180).unwrap();
+        assert_eq!(RiskDecision::Allow, third.decision());
+        assert_eq!(0, third.score());
+    }
+
+    #[test]
+    fn test_new_network_notifies() {
+        let (events, _) = events();
+        let policy = RiskPolicy::default();
+        let geoip = GeoIpDatabase::parse(DATABASE.as_bytes()).unwrap();
+        let sent = Arc::new(Mutex::new(None));
+        let captured = sent.clone();
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().times(1).returning(move |message| {
+            *captured.lock().unwrap() = Some(message.subject.clone());
+            Ok(())
+        });
+        let engine = RiskEngine::new(&events, &policy)
+            .with_geoip(&geoip)
+            .with_mailer(&mailer);
+
+        engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 0, NOW)
+            .unwrap();
+        let event = engine
+            .assess(&account(), &client("198.51.100.1", "laptop"), 0, NOW + 3600)
+            .unwrap();
+
+        assert_eq!(vec![RiskSignal::NewIp, RiskSignal::NewAsn], event.signals());
+        assert_eq!(RiskDecision::Notify, event.decision());
+        assert_eq!(
+            Some(String::from("New sign-in to your account")),
+            *sent.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_impossible_travel_is_blocked() {
+        let (events, _) = events();
+        let policy = RiskPolicy::default();
+        let geoip = GeoIpDatabase::parse(DATABASE.as_bytes()).unwrap();
+        let subjects = Arc::new(Mutex::new(Vec::new()));
+        let captured = subjects.clone();
+        let mut mailer = MockMailer::new();
+        mailer.expect_send().returning(move |message| {
+            captured.lock().unwrap().push(message.subject.clone());
+            Ok(())
+        });
+        let engine = RiskEngine::new(&events, &policy)
+            .with_geoip(&geoip)
+            .with_mailer(&mailer);
+
+        engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 0, NOW)
+            .unwrap();
+        // Jakarta - Amsterdam (sekitar 11.000 km) dalam satu jam
+        let event = engine
+            .assess(&account(), &client("192.0.2.1", "laptop"), 0, NOW + 3600)
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                RiskSignal::NewIp,
+                RiskSignal::NewAsn,
+                RiskSignal::ImpossibleTravel
+            ],
+            event.signals()
+        );
+        assert_eq!(RiskDecision::Block, event.decision());
+        assert!(subjects.lock().unwrap()[0].contains("blocked"));
+
+        // Login yang diblokir tidak membuat jaringan tersebut dikenal, namun perjalanan 2 hari masuk akal
+        let event = engine
+            .assess(
+                &account(),
+                &client("192.0.2.1", "laptop"),
+                0,
+                NOW + 2 * 86400,
+            )
+            .unwrap();
+        assert_eq!(vec![RiskSignal::NewIp, RiskSignal::NewAsn], event.signals());
+    }
+
+    #[test]
+    fn test_recent_failures() {
+        let (events, _) = events();
+        let policy = RiskPolicy::default();
+        let engine = RiskEngine::new(&events, &policy);
+
+        let event = engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 2, NOW)
+            .unwrap();
+        assert_eq!(vec![RiskSignal::RecentFailures], event.signals());
+        assert_eq!(20, event.score());
+
+        let event = engine
+            .assess(&account(), &client("203.0.113.1", "laptop"), 10, NOW)
+            .unwrap();
+        assert_eq!(30, event.score());
+    }
+}
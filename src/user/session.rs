@@ -0,0 +1,430 @@
+use super::super::sharedkernel::{error, function::get_now};
+use super::account::Account;
+use mockall::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Default masa berlaku session (dalam detik)
+pub const DEFAULT_SESSION_TTL: u64 = 86400;
+// Default batas usia autentikasi terakhir untuk operasi sensitif (dalam detik)
+pub const DEFAULT_REAUTHENTICATION_MAX_AGE: u64 = 300;
+
+/// Cakupan akses yang diberikan kepada session
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum AuthenticationScope {
+    #[serde(rename = "full")]
+    Full,
+    /// Password expired atau wajib diganti, hanya operasi ganti password yang diperbolehkan
+    #[serde(rename = "password_change")]
+    PasswordChange,
+    /// Faktor autentikasi tambahan (MFA) dibutuhkan sebelum akses penuh, misal karena MFA aktif
+    /// atau login dinilai berisiko. Account tanpa MFA menyelesaikannya dengan link dari email.
+    #[serde(rename = "step_up")]
+    StepUp,
+}
+
+/// Metode autentikasi yang digunakan pada session, mengikuti nilai `amr` dari RFC 8176
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum AuthenticationMethod {
+    #[serde(rename = "pwd")]
+    Password,
+    /// One-time password, misal TOTP atau recovery code
+    #[serde(rename = "otp")]
+    Otp,
+    #[serde(rename = "sms")]
+    Sms,
+    /// Kode dibacakan melalui panggilan telepon
+    #[serde(rename = "tel")]
+    Telephone,
+    /// Passkey / security key WebAuthn
+    #[serde(rename = "hwk")]
+    HardwareKey,
+    /// Link login yang dikirim melalui email (tidak terdapat di RFC 8176)
+    #[serde(rename = "email")]
+    Email,
+}
+
+/// Tingkat kepastian autentikasi (`acr`), mengikuti authenticator assurance level NIST SP 800-63B
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
+pub enum AssuranceLevel {
+    /// Satu faktor
+    #[default]
+    #[serde(rename = "aal1")]
+    SingleFactor,
+    /// Dua faktor atau lebih, termasuk passkey dengan user verification
+    #[serde(rename = "aal2")]
+    MultiFactor,
+}
+
+impl AssuranceLevel {
+    /**
+    Mengembalikan tingkat kepastian minimal untuk operasi sensitif pada account. Account dengan
+    MFA aktif wajib menggunakan dua faktor, agar password yang bocor saja tidak cukup.
+
+    # Arguments
+    * `account` - Account yang akan diubah
+    */
+    pub fn required_for(account: &Account) -> AssuranceLevel {
+        if account.is_mfa_enabled() {
+            AssuranceLevel::MultiFactor
+        } else {
+            AssuranceLevel::SingleFactor
+        }
+    }
+}
+
+/**
+Merepresentasikan session login sebuah account. Session juga berperan sebagai refresh token,
+sehingga mencabut session berarti user harus login kembali.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Session {
+    _id: Uuid,
+    account_id: Uuid,
+    scope: AuthenticationScope,
+    /// Waktu autentikasi terakhir yang berhasil (login, faktor kedua, atau autentikasi ulang)
+    #[serde(default)]
+    authenticated_at: u64,
+    #[serde(default)]
+    amr: Vec<AuthenticationMethod>,
+    #[serde(default)]
+    acr: AssuranceLevel,
+    /// Waktu autentikasi terakhir yang mencapai tingkat `MultiFactor`. Autentikasi ulang dengan
+    /// password saja tidak memperbaruinya.
+    #[serde(default)]
+    multi_factor_at: u64,
+    /// Id riwayat login (LoginEvent) yang menghasilkan session, jika risk engine diaktifkan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    login_event: Option<Uuid>,
+    created_at: u64,
+    expires_at: u64,
+}
+
+impl Session {
+    /**
+    Mengembalikan session baru
+
+    # Arguments
+    * `account_id` - Id dari account pemilik session
+    * `scope` - Cakupan akses session
+    * `ttl` - Masa berlaku session dalam detik
+    */
+    pub fn new(account_id: &Uuid, scope: AuthenticationScope, ttl: u64) -> Session {
+        let now = get_now();
+        Session {
+            _id: Uuid::new_v4(),
+            account_id: *account_id,
+            scope,
+            authenticated_at: now,
+            amr: Vec::new(),
+            acr: AssuranceLevel::SingleFactor,
+            multi_factor_at: 0,
+            login_event: None,
+            created_at: now,
+            expires_at: now + ttl,
+        }
+    }
+
+    pub fn id(&self) -> &Uuid {
+        &self._id
+    }
+
+    pub fn account_id(&self) -> &Uuid {
+        &self.account_id
+    }
+
+    pub fn scope(&self) -> AuthenticationScope {
+        self.scope
+    }
+
+    pub fn authenticated_at(&self) -> u64 {
+        self.authenticated_at
+    }
+
+    pub fn amr(&self) -> &[AuthenticationMethod] {
+        &self.amr
+    }
+
+    pub fn acr(&self) -> AssuranceLevel {
+        self.acr
+    }
+
+    pub fn multi_factor_at(&self) -> u64 {
+        self.multi_factor_at
+    }
+
+    pub fn login_event(&self) -> Option<&Uuid> {
+        self.login_event.as_ref()
+    }
+
+    /**
+    Menghubungkan session dengan hasil penilaian risiko login, agar penyelesaian step up dapat
+    dicatat pada riwayat login
+
+    # Arguments
+    * `event_id` - Id dari LoginEvent
+    */
+    pub fn link_login_event(&mut self, event_id: &Uuid) {
+        self.login_event = Some(*event_id);
+    }
+
+    /**
+    Mencatat autentikasi yang berhasil pada session, baik saat login, verifikasi faktor kedua,
+    maupun autentikasi ulang sebelum operasi sensitif
+
+    # Arguments
+    * `method` - Metode autentikasi yang digunakan
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn record_authentication(&mut self, method: AuthenticationMethod, now: u64) {
+        if !self.amr.contains(&method) {
+            self.amr.push(method);
+        }
+        self.authenticated_at = now;
+        // Passwordless login dengan passkey selalu mewajibkan user verification (PIN/biometrik)
+        self.acr = if self.amr.len() >= 2 || method == AuthenticationMethod::HardwareKey {
+            AssuranceLevel::MultiFactor
+        } else {
+            self.acr
+        };
+        // Password saja tidak membuktikan kepemilikan faktor kedua
+        if self.acr == AssuranceLevel::MultiFactor && method != AuthenticationMethod::Password {
+            self.multi_factor_at = now;
+        }
+    }
+
+    /**
+    Menolak operasi sensitif (misal menghapus account, mengganti email, atau menonaktifkan MFA)
+    jika autentikasi terakhir sudah terlalu lama, sehingga session yang dicuri atau ditinggalkan
+    tidak dapat langsung digunakan untuk operasi tersebut. Session dengan tingkat kepastian di
+    bawah `required` juga ditolak, misal login dari trusted device tanpa faktor kedua. Untuk
+    `MultiFactor`, autentikasi dua faktor terakhir juga harus berusia paling lama `max_age`.
+
+    # Arguments
+    * `max_age` - Usia maksimal autentikasi terakhir dalam detik
+    * `required` - Tingkat kepastian autentikasi minimal
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn ensure_recent_authentication(
+        &self,
+        max_age: u64,
+        required: AssuranceLevel,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.ensure_full_access()?;
+        if now.saturating_sub(self.authenticated_at) > max_age {
+            return Err(error::forbidden_error!(
+                "Please confirm your identity again before continuing.",
+                "reauthentication_required"
+            ));
+        }
+        let multi_factor_expired = required == AssuranceLevel::MultiFactor
+            && now.saturating_sub(self.multi_factor_at) > max_age;
+        if self.acr < required || multi_factor_expired {
+            return Err(error::forbidden_error!(
+                "Please confirm your identity with your second factor before continuing.",
+                "mfa_required"
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+    Memeriksa apakah session sudah tidak berlaku
+
+    # Arguments
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Menolak operasi selama password belum diganti atau verifikasi tambahan belum dilakukan
+    pub fn ensure_full_access(&self) -> Result<(), error::ApplicationError<'static>> {
+        match self.scope {
+            AuthenticationScope::Full => Ok(()),
+            AuthenticationScope::PasswordChange => Err(error::forbidden_error!(
+                "Sorry, but you need to change your password before continuing.",
+                "password_change_required"
+            )),
+            AuthenticationScope::StepUp => Err(error::forbidden_error!(
+                "Sorry, but we need to verify that it's really you before continuing.",
+                "step_up_required"
+            )),
+        }
+    }
+
+    /// Memberikan akses penuh kepada session, misal setelah password berhasil diganti
+    pub fn grant_full_access(&mut self) {
+        self.scope = AuthenticationScope::Full;
+    }
+
+    /**
+    Menyelesaikan verifikasi faktor tambahan. Jika password masih wajib diganti, session hanya
+    mendapatkan scope `PasswordChange`.
+
+    # Arguments
+    * `password_change_required` - Apakah password account wajib diganti
+    */
+    pub fn complete_step_up(&mut self, password_change_required: bool) {
+        self.scope = if password_change_required {
+            AuthenticationScope::PasswordChange
+        } else {
+            AuthenticationScope::Full
+        };
+    }
+}
+
+#[automock]
+pub trait SessionRepository {
+    fn create(&self, session: &Session) -> Result<(), error::ApplicationError<'static>>;
+    fn update(&self, session: &Session) -> Result<(), error::ApplicationError<'static>>;
+    /// Mencabut seluruh session milik account, kecuali session dengan id `except`
+    fn revoke_all(
+        &self,
+        account_id: &Uuid,
+        except: Option<Uuid>,
+    ) -> Result<(), error::ApplicationError<'static>>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let account_id = Uuid::new_v4();
+        let session = Session::new(&account_id, AuthenticationScope::PasswordChange, 60);
+
+        let serialized = serde_json::to_string(&session).unwrap();
+        assert!(serialized.contains("\"scope\":\"password_change\""));
+
+        let deserialized: Session = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(session, deserialized);
+
+        // Session lama tanpa informasi autentikasi tetap dapat dibaca
+        let legacy = format!(
+            "{{\"_id\":\"{}\",\"account_id\":\"{}\",\"scope\":\"full\",\"created_at\":1,\"expires_at\":2}}",
+            Uuid::new_v4(),
+            account_id
+        );
+        let legacy: Session = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(0, legacy.authenticated_at());
+        assert!(legacy.amr().is_empty());
+    }
+
+    #[test]
+    fn test_authentication_methods() {
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::StepUp, 60);
+        session.record_authentication(AuthenticationMethod::Password, 100);
+        assert_eq!(AssuranceLevel::SingleFactor, session.acr());
+
+        session.record_authentication(AuthenticationMethod::Otp, 110);
+        session.record_authentication(AuthenticationMethod::Otp, 120);
+        assert_eq!(
+            &[AuthenticationMethod::Password, AuthenticationMethod::Otp],
+            session.amr()
+        );
+        assert_eq!(AssuranceLevel::MultiFactor, session.acr());
+        assert_eq!(120, session.authenticated_at());
+
+        let serialized = serde_json::to_string(&session).unwrap();
+        assert!(serialized.contains("\"amr\":[\"pwd\",\"otp\"],\"acr\":\"aal2\""));
+
+        let mut passkey = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        passkey.record_authentication(AuthenticationMethod::HardwareKey, 100);
+        assert_eq!(AssuranceLevel::MultiFactor, passkey.acr());
+    }
+
+    #[test]
+    fn test_recent_authentication() {
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::StepUp, 60);
+        session.record_authentication(AuthenticationMethod::Password, 1000);
+        let single = AssuranceLevel::SingleFactor;
+        let error = session
+            .ensure_recent_authentication(300, single, 1000)
+            .unwrap_err();
+        assert_eq!("step_up_required", error.error);
+
+        session.complete_step_up(false);
+        assert!(session
+            .ensure_recent_authentication(300, single, 1300)
+            .is_ok());
+        let error = session
+            .ensure_recent_authentication(300, single, 1301)
+            .unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("reauthentication_required", error.error);
+
+        session.record_authentication(AuthenticationMethod::Password, 1400);
+        assert!(session
+            .ensure_recent_authentication(300, single, 1500)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_recent_authentication_requires_assurance_level() {
+        let multi = AssuranceLevel::MultiFactor;
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        session.record_authentication(AuthenticationMethod::Password, 1000);
+        let error = session
+            .ensure_recent_authentication(300, multi, 1000)
+            .unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("mfa_required", error.error);
+
+        session.record_authentication(AuthenticationMethod::Otp, 1100);
+        assert_eq!(1100, session.multi_factor_at());
+        assert!(session
+            .ensure_recent_authentication(300, multi, 1100)
+            .is_ok());
+        let error = session
+            .ensure_recent_authentication(300, multi, 1401)
+            .unwrap_err();
+        assert_eq!("reauthentication_required", error.error);
+
+        // Autentikasi ulang dengan password saja tidak memperbarui faktor kedua
+        session.record_authentication(AuthenticationMethod::Password, 1500);
+        assert_eq!(AssuranceLevel::MultiFactor, session.acr());
+        assert_eq!(1100, session.multi_factor_at());
+        let error = session
+            .ensure_recent_authentication(300, multi, 1500)
+            .unwrap_err();
+        assert_eq!("mfa_required", error.error);
+        assert!(session
+            .ensure_recent_authentication(300, AssuranceLevel::SingleFactor, 1500)
+            .is_ok());
+
+        session.record_authentication(AuthenticationMethod::Otp, 1600);
+        assert!(session
+            .ensure_recent_authentication(300, multi, 1600)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let session = Session::new(&Uuid::new_v4(), AuthenticationScope::Full, 60);
+        assert!(!session.is_expired(session.created_at + 59));
+        assert!(session.is_expired(session.created_at + 60));
+    }
+
+    #[test]
+    fn test_scope() {
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::PasswordChange, 60);
+        let error = session.ensure_full_access().unwrap_err();
+        assert_eq!(403, error.code);
+        assert_eq!("password_change_required", error.error);
+
+        session.grant_full_access();
+        assert!(session.ensure_full_access().is_ok());
+
+        let mut session = Session::new(&Uuid::new_v4(), AuthenticationScope::StepUp, 60);
+        let error = session.ensure_full_access().unwrap_err();
+        assert_eq!("step_up_required", error.error);
+
+        session.complete_step_up(true);
+        assert_eq!(AuthenticationScope::PasswordChange, session.scope());
+        session.complete_step_up(false);
+        assert_eq!(AuthenticationScope::Full, session.scope());
+    }
+}
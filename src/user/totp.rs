@@ -0,0 +1,688 @@
+use super::super::sharedkernel::{
+    cipher::SecretCipher,
+    error,
+    function::get_now,
+    password::Password,
+    password_policy::PasswordPolicy,
+    store::KeyValueStore,
+    totp::{self, TotpPolicy},
+};
+use super::account::{Account, AccountStatus};
+use super::lockout::LockoutGuard;
+use super::recovery::{self, RecoveryCodePolicy, RecoveryCodes};
+use super::repository::AccountRepository;
+use super::risk::RiskEngine;
+use super::session::{
+    AssuranceLevel, AuthenticationMethod, Session, SessionRepository,
+    DEFAULT_REAUTHENTICATION_MAX_AGE,
+};
+use serde::{Deserialize, Serialize};
+use validator::{ValidationError, ValidationErrors};
+use zeroize::Zeroize;
+
+/// Faktor TOTP milik account. Secret disimpan terenkripsi dan terikat dengan id account.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TotpFactor {
+    secret: String,
+    /// None selama enrollment belum dikonfirmasi dengan kode pertama
+    confirmed_at: Option<u64>,
+    /// Time step dari kode terakhir yang diterima, untuk mencegah replay
+    last_used_step: u64,
+}
+
+impl TotpFactor {
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
+
+/// Data enrollment yang ditampilkan kepada user, biasanya sebagai QR code
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    /// Secret dalam base32 untuk dimasukkan secara manual
+    pub secret: String,
+    pub uri: String,
+}
+
+/// Kode TOTP yang dikirim oleh user
+#[derive(Debug, Deserialize)]
+pub struct TotpCode {
+    code: String,
+}
+
+/// Permintaan menonaktifkan TOTP, membutuhkan password untuk autentikasi ulang
+#[derive(Debug, Deserialize)]
+pub struct TotpDisable {
+    password: Password,
+}
+
+/// Use case enrollment, verifikasi dan penonaktifan TOTP
+pub struct TotpService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    cipher: &'a SecretCipher,
+    password_policy: &'a PasswordPolicy,
+    store: &'a dyn KeyValueStore,
+    lockout: LockoutGuard<'a>,
+    issuer: &'a str,
+    policy: TotpPolicy,
+    recovery_policy: RecoveryCodePolicy,
+    reauthentication_max_age: u64,
+    risk: Option<RiskEngine<'a>>,
+}
+
+fn invalid_code() -> error::ApplicationError<'static> {
+    error::unauthorized_error!(
+        "Sorry, but the verification code is incorrect or has already been used.",
+        "invalid_mfa_code"
+    )
+}
+
+// Account dapat memiliki faktor lain (misal passkey), sehingga hanya TOTP yang diperiksa
+fn is_totp_enabled(account: &Account) -> bool {
+    account.totp().is_some_and(TotpFactor::is_confirmed)
+}
+
+impl<'a> TotpService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        cipher: &'a SecretCipher,
+        password_policy: &'a PasswordPolicy,
+        store: &'a dyn KeyValueStore,
+        lockout: LockoutGuard<'a>,
+        issuer: &'a str,
+    ) -> TotpService<'a> {
+        TotpService {
+            accounts,
+            sessions,
+            cipher,
+            password_policy,
+            store,
+            lockout,
+            issuer,
+            policy: TotpPolicy::default(),
+            recovery_policy: RecoveryCodePolicy::default(),
+            reauthentication_max_age: DEFAULT_REAUTHENTICATION_MAX_AGE,
+            risk: None,
+        }
+    }
+
+    /// Mengganti batas usia autentikasi terakhir (dalam detik) untuk menonaktifkan TOTP
+    pub fn with_reauthentication_max_age(mut self, max_age: u64) -> TotpService<'a> {
+        self.reauthentication_max_age = max_age;
+        self
+    }
+
+    /// Mengganti parameter TOTP (jumlah digit, durasi time step dan toleransi drift)
+    pub fn with_policy(mut self, policy: TotpPolicy) -> TotpService<'a> {
+        self.policy = policy;
+        self
+    }
+
+    /// Mengganti jumlah recovery code yang dibuat saat TOTP diaktifkan
+    pub fn with_recovery_code_policy(mut self, policy: RecoveryCodePolicy) -> TotpService<'a> {
+        self.recovery_policy = policy;
+        self
+    }
+
+    /// Mencatat penyelesaian step up pada riwayat login, agar perangkat user dikenal oleh risk engine
+    pub fn with_risk_engine(mut self, risk: RiskEngine<'a>) -> TotpService<'a> {
+        self.risk = Some(risk);
+        self
+    }
+
+    fn account(&self, session: &Session) -> Result<Account, error::ApplicationError<'static>> {
+        match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => Ok(account),
+            _ => Err(error::unauthorized_error!()),
+        }
+    }
+
+    /**
+    Memverifikasi kode terhadap faktor TOTP milik account dan mencatat time step yang digunakan.
+    Kode dengan time step yang sama atau lebih lama dari kode terakhir ditolak. Time step juga
+    ditandai secara atomic di store, karena request paralel dapat membaca `last_used_step` yang
+    sama sebelum account disimpan. Kode yang salah dihitung oleh lockout, dan verifikasi ditolak
+    selama faktor kedua account terkunci.
+    */
+    fn check_code(
+        &self,
+        account: &mut Account,
+        code: &TotpCode,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        self.lockout.ensure_factor_not_locked(account.id(), now)?;
+        let mut factor = account.totp().cloned().ok_or_else(invalid_code)?;
+        let mut secret = self
+            .cipher
+            .decrypt(&factor.secret, &account.id().to_string())?;
+        let step = totp::verify(&secret, &code.code, &self.policy, now);
+        secret.zeroize();
+
+        match step {
+            Some(step) if step > factor.last_used_step && self.consume_step(account, step)? => {
+                factor.last_used_step = step;
+                factor.confirmed_at = factor.confirmed_at.or(Some(now));
+                account.set_totp(Some(factor));
+                self.lockout.record_factor_success(account.id())
+            }
+            _ => {
+                self.lockout.record_factor_failure(account.id(), now)?;
+                Err(invalid_code())
+            }
+        }
+    }
+
+    // Kode sebuah time step diterima paling lama selama window drift
+    fn consume_step(
+        &self,
+        account: &Account,
+        step: u64,
+    ) -> Result<bool, error::ApplicationError<'static>> {
+        let ttl = (self.policy.drift * 2 + 1) * self.policy.step.max(1);
+        self.store
+            .set_if_absent(&format!("totp:used:{}:{}", account.id(), step), "1", ttl)
+    }
+
+    /**
+    Memulai enrollment TOTP. Secret baru menggantikan enrollment sebelumnya yang belum dikonfirmasi.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    */
+    pub fn enroll(
+        &self,
+        session: &Session,
+    ) -> Result<TotpEnrollment, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let mut account = self.account(session)?;
+        if is_totp_enabled(&account) {
+            return Err(error::bad_request_error!(
+                "An authenticator app is already enabled for your account.",
+                "mfa_already_enabled"
+            ));
+        }
+
+        let mut secret = totp::generate_secret();
+        let factor = TotpFactor {
+            secret: self.cipher.encrypt(&secret, &account.id().to_string())?,
+            confirmed_at: None,
+            last_used_step: 0,
+        };
+        let enrollment = TotpEnrollment {
+            secret: totp::encode_secret(&secret),
+            uri: totp::otpauth_uri(
+                self.issuer,
+                &account.email().to_string(),
+                &secret,
+                &self.policy,
+            ),
+        };
+        secret.zeroize();
+
+        account.set_totp(Some(factor));
+        self.accounts.update(&account)?;
+        Ok(enrollment)
+    }
+
+    /**
+    Mengaktifkan TOTP setelah user membuktikan authenticator sudah terpasang dengan kode pertama.
+    Recovery code dibuat jika account belum memilikinya, dan hanya ditampilkan satu kali.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `code` - Kode dari authenticator
+    */
+    pub fn confirm(
+        &self,
+        session: &Session,
+        code: &TotpCode,
+    ) -> Result<RecoveryCodes, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let mut account = self.account(session)?;
+        match account.totp() {
+            Some(factor) if !factor.is_confirmed() => {}
+            _ => {
+                return Err(error::bad_request_error!(
+                    "Please start two-factor authentication enrollment first.",
+                    "mfa_not_enrolled"
+                ))
+            }
+        }
+
+        self.check_code(&mut account, code, get_now())?;
+        let codes = match account.recovery_codes_remaining() {
+            0 => recovery::generate(&mut account, &self.recovery_policy)?,
+            _ => Vec::new(),
+        };
+        self.accounts.update(&account)?;
+        Ok(RecoveryCodes { codes })
+    }
+
+    /**
+    Memverifikasi kode TOTP sebagai faktor kedua saat login, lalu menaikkan scope session
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    * `code` - Kode dari authenticator
+    */
+    pub fn verify(
+        &self,
+        session: &mut Session,
+        code: &TotpCode,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let mut account = self.account(session)?;
+        if !is_totp_enabled(&account) {
+            return Err(invalid_code());
+        }
+
+        let now = get_now();
+        self.check_code(&mut account, code, now)?;
+        self.accounts.update(&account)?;
+
+        session.record_authentication(AuthenticationMethod::Otp, now);
+        session
+            .complete_step_up(account.requires_password_change(self.password_policy.max_age, now));
+        if let Some(risk) = &self.risk {
+            risk.complete_step_up(session, now)?;
+        }
+        self.sessions.update(session)
+    }
+
+    /**
+    Menonaktifkan TOTP setelah user memasukkan password kembali. Session harus baru saja
+    diautentikasi dengan dua faktor, jika tidak error `reauthentication_required` atau
+    `mfa_required` dikembalikan. Password yang salah
+    dihitung sebagai kegagalan login account. Recovery code ikut dihapus jika account tidak
+    memiliki faktor kedua lainnya.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `request` - Password saat ini
+    */
+    pub fn disable(
+        &self,
+        session: &Session,
+        request: &TotpDisable,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let now = get_now();
+        let mut account = self.account(session)?;
+        session.ensure_recent_authentication(
+            self.reauthentication_max_age,
+            AssuranceLevel::required_for(&account),
+            now,
+        )?;
+        self.lockout
+            .ensure_account_not_locked(account.email(), now)?;
+        if !account
+            .hash()
+            .verify_password(&request.password)
+            .unwrap_or(false)
+        {
+            self.lockout.record_account_failure(account.email(), now)?;
+            let mut errors = ValidationErrors::new();
+            errors.add("password", ValidationError::new("invalid_current_password"));
+            return Err(error::ApplicationError::from_validation_errors(errors));
+        }
+
+        account.set_totp(None);
+        // Recovery code hanya berguna selama masih ada faktor kedua yang aktif
+        if !account.is_mfa_enabled() {
+            account.set_recovery_codes(Vec::new());
+        }
+        self.accounts.update(&account)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::email::Email;
+    use super::super::super::sharedkernel::store::MemoryStore;
+    use super::super::fixture::{self, PASSWORD};
+    use super::super::lockout::LockoutPolicy;
+    use super::super::repository::MockAccountRepository;
+    use super::super::risk::{MockLoginEventRepository, RiskPolicy};
+    use super::super::session::{AuthenticationScope, MockSessionRepository};
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use uuid::Uuid;
+
+    const CIPHER_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    /// Repository satu account yang menyimpan perubahan terakhir
+    fn accounts() -> (MockAccountRepository, Arc<Mutex<Account>>, Uuid) {
+        let account = fixture::account();
+        let id = *account.id();
+        let (accounts, stored) = fixture::repository(account);
+        (accounts, stored, id)
+    }
+
+    fn code(code: &str) -> TotpCode {
+        TotpCode {
+            code: String::from(code),
+        }
+    }
+
+    /// Kode untuk time step tertentu. Time step diambil sekali per test, agar pergantian time step
+    /// di tengah test tidak mengubah kode yang diharapkan.
+    fn code_at(enrollment: &TotpEnrollment, step: u64) -> String {
+        let secret = base32::decode(
+            base32::Alphabet::RFC4648 { padding: false },
+            &enrollment.secret,
+        )
+        .unwrap();
+        totp::hotp(&secret, step, 6)
+    }
+
+    #[test]
+    fn test_enroll_confirm_and_verify() {
+        let (accounts, stored, account_id) = accounts();
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().times(1).returning(|_| Ok(()));
+        let cipher = SecretCipher::from_hex_key(CIPHER_KEY).unwrap();
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        );
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+
+        let enrollment = service.enroll(&session).unwrap();
+        let step = get_now() / 30;
+        assert!(enrollment
+            .uri
+            .starts_with("otpauth://totp/Brokoli:harun@digitalsekuriti.id?secret="));
+        assert!(enrollment.uri.contains(&enrollment.secret));
+        {
+            let account = stored.lock().unwrap();
+            let factor = account.totp().unwrap();
+            assert!(!factor.is_confirmed());
+            assert!(!factor.secret.contains(&enrollment.secret));
+            assert!(!account.is_mfa_enabled());
+        }
+
+        let error = service.confirm(&session, &code("000000x")).unwrap_err();
+        assert_eq!("invalid_mfa_code", error.error);
+        let recovery = service
+            .confirm(&session, &code(&code_at(&enrollment, step - 1)))
+            .unwrap();
+        assert!(stored.lock().unwrap().is_mfa_enabled());
+        assert_eq!(10, recovery.codes.len());
+        assert_eq!(10, stored.lock().unwrap().recovery_codes_remaining());
+
+        let error = service.enroll(&session).unwrap_err();
+        assert_eq!("mfa_already_enabled", error.error);
+
+        // Kode yang sudah digunakan saat konfirmasi tidak dapat digunakan kembali
+        let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        let error = service
+            .verify(&mut login, &code(&code_at(&enrollment, step - 1)))
+            .unwrap_err();
+        assert_eq!("invalid_mfa_code", error.error);
+        assert_eq!(AuthenticationScope::StepUp, login.scope());
+
+        service
+            .verify(&mut login, &code(&code_at(&enrollment, step)))
+            .unwrap();
+        assert_eq!(AuthenticationScope::Full, login.scope());
+        assert!(service
+            .verify(&mut login, &code(&code_at(&enrollment, step)))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parallel_verify_accepts_code_once() {
+        let (accounts, stored, account_id) = accounts();
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let cipher = SecretCipher::from_hex_key(CIPHER_KEY).unwrap();
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        );
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let enrollment = service.enroll(&session).unwrap();
+        let step = get_now() / 30;
+        service
+            .confirm(&session, &code(&code_at(&enrollment, step - 1)))
+            .unwrap();
+
+        // Seluruh request paralel membaca account sebelum salah satunya tersimpan
+        let snapshot = stored.lock().unwrap().clone();
+        let mut accounts = MockAccountRepository::new();
+        accounts
+            .expect_get_by_id()
+            .returning(move |_| Ok(Some(snapshot.clone())));
+        accounts.expect_update().returning(|_| Ok(()));
+
+        // Request paralel dengan kode yang sama hanya diterima satu kali
+        let valid = code_at(&enrollment, step);
+        let accepted = std::sync::atomic::AtomicU32::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let service = TotpService::new(
+                        &accounts,
+                        &sessions,
+                        &cipher,
+                        &policy,
+                        &store,
+                        LockoutGuard::new(&store, &lockout_policy),
+                        "Brokoli",
+                    );
+                    let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+                    if service.verify(&mut login, &code(&valid)).is_ok() {
+                        accepted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert_eq!(1, accepted.into_inner());
+    }
+
+    #[test]
+    fn test_verify_completes_risk_step_up() {
+        let (accounts, _, account_id) = accounts();
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let cipher = SecretCipher::from_hex_key(CIPHER_KEY).unwrap();
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        );
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let enrollment = service.enroll(&session).unwrap();
+        let step = get_now() / 30;
+        service
+            .confirm(&session, &code(&code_at(&enrollment, step)))
+            .unwrap();
+
+        let event_id = Uuid::new_v4();
+        let mut events = MockLoginEventRepository::new();
+        events
+            .expect_verify()
+            .withf(move |id, _| *id == event_id)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let risk_policy = RiskPolicy::default();
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        )
+        .with_risk_engine(RiskEngine::new(&events, &risk_policy));
+
+        // Kode salah tidak menandai login sebagai terpercaya
+        let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        login.link_login_event(&event_id);
+        assert!(service.verify(&mut login, &code("000000")).is_err());
+        service
+            .verify(&mut login, &code(&code_at(&enrollment, step + 1)))
+            .unwrap();
+        assert_eq!(AuthenticationScope::Full, login.scope());
+    }
+
+    #[test]
+    fn test_lock_after_wrong_codes() {
+        let (accounts, _, account_id) = accounts();
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_update().returning(|_| Ok(()));
+        let cipher = SecretCipher::from_hex_key(CIPHER_KEY).unwrap();
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy {
+            account_threshold: 3,
+            ..LockoutPolicy::default()
+        };
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        );
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let enrollment = service.enroll(&session).unwrap();
+        let step = get_now() / 30;
+        service
+            .confirm(&session, &code(&code_at(&enrollment, step)))
+            .unwrap();
+
+        let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        for _ in 0..3 {
+            let error = service.verify(&mut login, &code("000000")).unwrap_err();
+            assert_eq!("invalid_mfa_code", error.error);
+        }
+
+        // Kode yang benar juga ditolak selama faktor kedua terkunci
+        let error = service
+            .verify(&mut login, &code(&code_at(&enrollment, step + 1)))
+            .unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!("mfa_locked", error.error);
+        assert_eq!(AuthenticationScope::StepUp, login.scope());
+    }
+
+    #[test]
+    fn test_confirm_without_enrollment() {
+        let (accounts, _, account_id) = accounts();
+        let sessions = MockSessionRepository::new();
+        let cipher = SecretCipher::from_hex_key(CIPHER_KEY).unwrap();
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        );
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+
+        let error = service.confirm(&session, &code("123456")).unwrap_err();
+        assert_eq!("mfa_not_enrolled", error.error);
+
+        let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        assert!(service.verify(&mut login, &code("123456")).is_err());
+
+        // Enrollment membutuhkan akses penuh
+        let error = service.enroll(&login).unwrap_err();
+        assert_eq!("step_up_required", error.error);
+    }
+
+    #[test]
+    fn test_disable_requires_password() {
+        let (accounts, stored, account_id) = accounts();
+        let sessions = MockSessionRepository::new();
+        let cipher = SecretCipher::from_hex_key(CIPHER_KEY).unwrap();
+        let policy = PasswordPolicy::default();
+        let store = MemoryStore::new();
+        let lockout_policy = LockoutPolicy::default();
+        let service = TotpService::new(
+            &accounts,
+            &sessions,
+            &cipher,
+            &policy,
+            &store,
+            LockoutGuard::new(&store, &lockout_policy),
+            "Brokoli",
+        )
+        .with_recovery_code_policy(RecoveryCodePolicy {
+            count: 2,
+            ..RecoveryCodePolicy::default()
+        });
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+
+        let enrollment = service.enroll(&session).unwrap();
+        let step = get_now() / 30;
+        service
+            .confirm(&session, &code(&code_at(&enrollment, step)))
+            .unwrap();
+        assert_eq!(2, stored.lock().unwrap().recovery_codes_remaining());
+
+        let disable = |password: &str| TotpDisable {
+            password: Password::from(password),
+        };
+        let error = service.disable(&session, &disable(PASSWORD)).unwrap_err();
+        assert_eq!("mfa_required", error.error);
+
+        let mut stale = Session::new(&account_id, AuthenticationScope::Full, 60);
+        stale.record_authentication(AuthenticationMethod::Password, 0);
+        stale.record_authentication(AuthenticationMethod::Otp, 0);
+        let error = service.disable(&stale, &disable(PASSWORD)).unwrap_err();
+        assert_eq!("reauthentication_required", error.error);
+
+        let mut session = session.clone();
+        session.record_authentication(AuthenticationMethod::Password, get_now());
+        session.record_authentication(AuthenticationMethod::Otp, get_now());
+        let error = service
+            .disable(&session, &disable("WrongPassw0rd!"))
+            .unwrap_err();
+        assert!(error.fields.unwrap()["password"][0].contains("invalid_current_password"));
+        assert!(stored.lock().unwrap().is_mfa_enabled());
+        let email = Email::from("harun@digitalsekuriti.id");
+        assert_eq!(
+            1,
+            LockoutGuard::new(&store, &lockout_policy)
+                .failures(&email)
+                .unwrap()
+        );
+
+        service.disable(&session, &disable(PASSWORD)).unwrap();
+        assert!(stored.lock().unwrap().totp().is_none());
+        assert_eq!(0, stored.lock().unwrap().recovery_codes_remaining());
+    }
+}
@@ -0,0 +1,349 @@
+use super::super::sharedkernel::{error, function::get_now, token::Token};
+use super::account::{Account, AccountStatus};
+use super::repository::AccountRepository;
+use super::session::{AssuranceLevel, Session};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Panjang maksimal nama perangkat yang disimpan
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Konfigurasi fitur "remember this browser"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedDevicePolicy {
+    /// Masa berlaku trusted device (dalam detik)
+    pub ttl: u64,
+    /// Jumlah maksimal trusted device per account, perangkat terlama dihapus jika terlampaui
+    pub max_devices: usize,
+}
+
+impl Default for TrustedDevicePolicy {
+    fn default() -> Self {
+        TrustedDevicePolicy {
+            ttl: 30 * 86400,
+            max_devices: 10,
+        }
+    }
+}
+
+/// Browser yang diizinkan melewati verifikasi faktor kedua saat login
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrustedDevice {
+    id: Uuid,
+    /// SHA-256 digest dari token yang disimpan di browser
+    #[serde(skip_serializing)]
+    digest: String,
+    name: String,
+    created_at: u64,
+    expires_at: u64,
+    last_used_at: Option<u64>,
+}
+
+impl TrustedDevice {
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    pub fn last_used_at(&self) -> Option<u64> {
+        self.last_used_at
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /**
+    Memeriksa token yang dikirim browser dan mencatat penggunaannya
+
+    # Arguments
+    * `digest` - Digest dari token yang dikirim browser
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn verify(&mut self, digest: &str, now: u64) -> bool {
+        if self.is_expired(now) || self.digest != digest {
+            return false;
+        }
+        self.last_used_at = Some(now);
+        true
+    }
+}
+
+/// Permintaan untuk mengingat browser yang sedang digunakan
+#[derive(Debug, Deserialize)]
+pub struct TrustDevice {
+    /// Nama perangkat yang ditampilkan di halaman keamanan account, misal "Firefox di Linux"
+    #[serde(default)]
+    pub name: String,
+}
+
+/// Token yang disimpan browser (misal dalam cookie) dan dikirim kembali saat login
+#[derive(Debug, Serialize)]
+pub struct TrustedDeviceToken {
+    pub id: Uuid,
+    pub token: String,
+    pub expires_at: u64,
+}
+
+/**
+Use case "remember this browser" : browser yang sudah menyelesaikan MFA dapat melewati verifikasi
+faktor kedua selama masa berlaku tertentu. Seluruh trusted device dihapus saat password diganti.
+*/
+pub struct TrustedDeviceService<'a> {
+    accounts: &'a dyn AccountRepository,
+    policy: TrustedDevicePolicy,
+}
+
+impl<'a> TrustedDeviceService<'a> {
+    pub fn new(accounts: &'a dyn AccountRepository) -> TrustedDeviceService<'a> {
+        TrustedDeviceService {
+            accounts,
+            policy: TrustedDevicePolicy::default(),
+        }
+    }
+
+    /// Mengganti masa berlaku dan jumlah maksimal trusted device
+    pub fn with_policy(mut self, policy: TrustedDevicePolicy) -> TrustedDeviceService<'a> {
+        self.policy = policy;
+        self
+    }
+
+    fn account(&self, session: &Session) -> Result<Account, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        match self.accounts.get_by_id(&session.account_id().to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => Ok(account),
+            _ => Err(error::unauthorized_error!()),
+        }
+    }
+
+    /**
+    Mengingat browser yang sedang digunakan. Hanya dapat dilakukan dari session yang sudah
+    menyelesaikan MFA, token hanya dikembalikan sekali dan disimpan dalam bentuk digest.
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `request` - Nama perangkat
+    */
+    pub fn trust(
+        &self,
+        session: &Session,
+        request: &TrustDevice,
+    ) -> Result<TrustedDeviceToken, error::ApplicationError<'static>> {
+        let mut account = self.account(session)?;
+        if !account.is_mfa_enabled() {
+            return Err(error::bad_request_error!(
+                "Please enable two-factor authentication first.",
+                "mfa_not_enabled"
+            ));
+        }
+        if session.acr() != AssuranceLevel::MultiFactor {
+            return Err(error::forbidden_error!(
+                "Please verify your identity with your second factor first.",
+                "step_up_required"
+            ));
+        }
+
+        let now = get_now();
+        let token = Token::generate();
+        let name: String = request.name.trim().chars().take(MAX_NAME_LENGTH).collect();
+        let device = TrustedDevice {
+            id: Uuid::new_v4(),
+            digest: token.digest(),
+            name: if name.is_empty() {
+                String::from("Unknown device")
+            } else {
+                name
+            },
+            created_at: now,
+            expires_at: now + self.policy.ttl,
+            last_used_at: None,
+        };
+        let response = TrustedDeviceToken {
+            id: device.id,
+            token: String::from(token.expose_secret()),
+            expires_at: device.expires_at,
+        };
+
+        account.add_trusted_device(device, self.policy.max_devices, now);
+        self.accounts.update(&account)?;
+        Ok(response)
+    }
+
+    /**
+    Mengembalikan trusted device milik user yang masih berlaku
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    */
+    pub fn list(
+        &self,
+        session: &Session,
+    ) -> Result<Vec<TrustedDevice>, error::ApplicationError<'static>> {
+        let now = get_now();
+        Ok(self
+            .account(session)?
+            .trusted_devices()
+            .iter()
+            .filter(|device| !device.is_expired(now))
+            .cloned()
+            .collect())
+    }
+
+    /**
+    Mencabut satu trusted device, browser tersebut harus melakukan MFA kembali saat login
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `id` - Id trusted device
+    */
+    pub fn revoke(
+        &self,
+        session: &Session,
+        id: &Uuid,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let mut account = self.account(session)?;
+        if !account.remove_trusted_device(id) {
+            return Err(error::not_found_error!());
+        }
+        self.accounts.update(&account)
+    }
+
+    /**
+    Mencabut seluruh trusted device milik user
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    */
+    pub fn revoke_all(&self, session: &Session) -> Result<(), error::ApplicationError<'static>> {
+        let mut account = self.account(session)?;
+        account.clear_trusted_devices();
+        self.accounts.update(&account)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::password::{Hash, Password};
+    use super::super::fixture::{self, KEY};
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::{AuthenticationMethod, AuthenticationScope};
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn repository(mfa: bool) -> (MockAccountRepository, Arc<Mutex<Account>>, Uuid) {
+        let mut account = fixture::account();
+        if mfa {
+            account.set_phone(Some(
+                serde_json::from_value(
+                    serde_json::json!({"number": "+6281234567890", "verified_at": 1}),
+                )
+                .unwrap(),
+            ));
+        }
+        let account_id = *account.id();
+        let (accounts, stored) = fixture::repository(account);
+        (accounts, stored, account_id)
+    }
+
+    fn mfa_session(account_id: &Uuid) -> Session {
+        let mut session = Session::new(account_id, AuthenticationScope::StepUp, 60);
+        session.record_authentication(AuthenticationMethod::Password, get_now());
+        session.record_authentication(AuthenticationMethod::Otp, get_now());
+        session.complete_step_up(false);
+        session
+    }
+
+    fn trust(name: &str) -> TrustDevice {
+        TrustDevice {
+            name: String::from(name),
+        }
+    }
+
+    #[test]
+    fn test_trust_requires_mfa() {
+        let (accounts, _, account_id) = repository(false);
+        let service = TrustedDeviceService::new(&accounts);
+        let error = service
+            .trust(&mfa_session(&account_id), &trust("Firefox"))
+            .unwrap_err();
+        assert_eq!("mfa_not_enabled", error.error);
+
+        let (accounts, _, account_id) = repository(true);
+        let service = TrustedDeviceService::new(&accounts);
+        let mut session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        session.record_authentication(AuthenticationMethod::Password, get_now());
+        let error = service.trust(&session, &trust("Firefox")).unwrap_err();
+        assert_eq!("step_up_required", error.error);
+    }
+
+    #[test]
+    fn test_trust_list_and_revoke() {
+        let (accounts, stored, account_id) = repository(true);
+        let service = TrustedDeviceService::new(&accounts).with_policy(TrustedDevicePolicy {
+            max_devices: 2,
+            ..TrustedDevicePolicy::default()
+        });
+        let session = mfa_session(&account_id);
+
+        let first = service.trust(&session, &trust("  Firefox  ")).unwrap();
+        let second = service.trust(&session, &trust("")).unwrap();
+        let third = service.trust(&session, &trust("Chrome")).unwrap();
+
+        // Perangkat terlama dihapus jika jumlah maksimal terlampaui
+        let devices = service.list(&session).unwrap();
+        let ids: Vec<Uuid> = devices.iter().map(|device| *device.id()).collect();
+        assert_eq!(vec![second.id, third.id], ids);
+        assert_eq!("Unknown device", devices[0].name());
+        assert!(!serde_json::to_string(&devices)
+            .unwrap()
+            .contains(&third.token));
+
+        // Token hanya disimpan dalam bentuk digest
+        let digest = Token::from(&third.token).digest();
+        assert!(stored
+            .lock()
+            .unwrap()
+            .use_trusted_device(&digest, get_now()));
+        assert!(!stored
+            .lock()
+            .unwrap()
+            .use_trusted_device(&Token::from(&first.token).digest(), get_now()));
+
+        let error = service.revoke(&session, &first.id).unwrap_err();
+        assert_eq!("not_found", error.error);
+        service.revoke(&session, &second.id).unwrap();
+        assert_eq!(1, service.list(&session).unwrap().len());
+
+        service.revoke_all(&session).unwrap();
+        assert!(service.list(&session).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expiry_and_password_change() {
+        let (accounts, stored, account_id) = repository(true);
+        let service = TrustedDeviceService::new(&accounts);
+        let session = mfa_session(&account_id);
+        let trusted = service.trust(&session, &trust("Firefox")).unwrap();
+        let digest = Token::from(&trusted.token).digest();
+
+        assert!(!stored
+            .lock()
+            .unwrap()
+            .use_trusted_device(&digest, trusted.expires_at));
+
+        let hash = Hash::from_password(KEY, &Password::from("NewPassworD5678!")).unwrap();
+        stored.lock().unwrap().change_hash(hash, 0);
+        assert!(stored.lock().unwrap().trusted_devices().is_empty());
+        assert!(!stored
+            .lock()
+            .unwrap()
+            .use_trusted_device(&digest, get_now()));
+    }
+}
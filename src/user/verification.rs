@@ -0,0 +1,390 @@
+use super::super::sharedkernel::{
+    email::Email,
+    error,
+    function::get_now,
+    mailer::{Mailer, Message},
+    rate_limit::{RateLimit, RateLimitAlgorithm, RateLimitKey},
+    store::KeyValueStore,
+    token::Token,
+};
+use super::account::{Account, AccountStatus};
+use super::repository::AccountRepository;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Kebijakan token verifikasi email dan pengiriman ulang
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EmailVerificationPolicy {
+    /// Masa berlaku token verifikasi dalam detik
+    pub token_ttl: u64,
+    /// Jeda minimal antar pengiriman ulang dalam detik
+    pub resend_interval: u64,
+    /// Jumlah pengiriman ulang maksimal dalam `resend_window`
+    pub max_resends: u32,
+    /// Periode perhitungan pengiriman ulang dalam detik
+    pub resend_window: u64,
+}
+
+impl Default for EmailVerificationPolicy {
+    fn default() -> Self {
+        EmailVerificationPolicy {
+            token_ttl: 86400,
+            resend_interval: 60,
+            max_resends: 5,
+            resend_window: 86400,
+        }
+    }
+}
+
+/// Konfirmasi verifikasi email menggunakan token yang dikirim melalui email
+#[derive(Debug, Deserialize)]
+pub struct EmailVerificationConfirmation {
+    token: Token,
+}
+
+/// Permintaan pengiriman ulang email verifikasi
+#[derive(Debug, Deserialize)]
+pub struct EmailVerificationResend {
+    email: Email,
+}
+
+/// Token verifikasi yang belum digunakan, disimpan dengan key digest dari token
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingVerification {
+    account_id: Uuid,
+    email: Email,
+}
+
+/// Use case verifikasi email untuk account yang baru mendaftar
+pub struct EmailVerificationService<'a> {
+    accounts: &'a dyn AccountRepository,
+    store: &'a dyn KeyValueStore,
+    mailer: &'a dyn Mailer,
+    verify_url: String,
+    policy: EmailVerificationPolicy,
+}
+
+impl<'a> EmailVerificationService<'a> {
+    /**
+    Mengembalikan service verifikasi email
+
+    # Arguments
+    * `verify_url` - URL halaman verifikasi email, token ditambahkan sebagai query `token`
+    */
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        store: &'a dyn KeyValueStore,
+        mailer: &'a dyn Mailer,
+        verify_url: &str,
+    ) -> EmailVerificationService<'a> {
+        EmailVerificationService {
+            accounts,
+            store,
+            mailer,
+            verify_url: String::from(verify_url),
+            policy: EmailVerificationPolicy::default(),
+        }
+    }
+
+    /// Mengganti masa berlaku token dan batas pengiriman ulang
+    pub fn with_policy(mut self, policy: EmailVerificationPolicy) -> EmailVerificationService<'a> {
+        self.policy = policy;
+        self
+    }
+
+    fn token_key(digest: &str) -> String {
+        format!("email_verification:token:{}", digest)
+    }
+
+    fn account_key(account_id: &Uuid) -> String {
+        format!("email_verification:account:{}", account_id)
+    }
+
+    fn resend_key(email: &Email, limit: &str) -> String {
+        format!(
+            "email_verification:resend:{}:{}",
+            limit,
+            email.to_string().to_lowercase()
+        )
+    }
+
+    /**
+    Membuat token verifikasi baru dan mengirimkannya ke email account. Token sebelumnya tidak
+    dapat digunakan lagi.
+    */
+    fn send(&self, account: &Account) -> Result<(), error::ApplicationError<'static>> {
+        let account_key = Self::account_key(account.id());
+        if let Some(previous) = self.store.get(&account_key)? {
+            self.store.delete(&Self::token_key(&previous))?;
+        }
+
+        let token = Token::generate();
+        let digest = token.digest();
+        let pending = PendingVerification {
+            account_id: *account.id(),
+            email: account.email().clone(),
+        };
+        let value = serde_json::to_string(&pending).map_err(|_| error::internal_server_error!())?;
+        self.store
+            .set(&Self::token_key(&digest), &value, self.policy.token_ttl)?;
+        self.store
+            .set(&account_key, &digest, self.policy.token_ttl)?;
+
+        let separator = if self.verify_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        self.mailer.send(&Message::new(
+            account.email(),
+            "Verify your email address",
+            &format!(
+                "Thanks for signing up. Open the following link within {} hours to verify your email address and activate your account:\n\n{}{}token={}\n\nIf you did not create an account, you can safely ignore this email.",
+                self.policy.token_ttl / 3600,
+                self.verify_url,
+                separator,
+                token.expose_secret()
+            ),
+        ))
+    }
+
+    /**
+    Mengirimkan email verifikasi pertama untuk account yang baru didaftarkan dengan status
+    `PendingVerification`
+
+    # Arguments
+    * `account` - Account yang baru didaftarkan
+    */
+    pub fn start(&self, account: &Account) -> Result<(), error::ApplicationError<'static>> {
+        // Account sudah terdaftar, user dapat meminta pengiriman ulang jika email gagal dikirim
+        let _ = self.send(account);
+        Ok(())
+    }
+
+    /**
+    Memverifikasi email menggunakan token. Token hanya dapat digunakan sekali dan hanya berlaku
+    untuk email yang sama dengan saat token dikirim.
+
+    # Arguments
+    * `confirmation` - Token dari link verifikasi
+    */
+    pub fn confirm(
+        &self,
+        confirmation: &EmailVerificationConfirmation,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let invalid_token = || {
+            error::bad_request_error!(
+                "Sorry, but your verification link is invalid or has expired.",
+                "invalid_verification_token"
+            )
+        };
+
+        // Token diambil dan dihapus secara atomic, sehingga hanya dapat digunakan oleh satu request
+        let key = Self::token_key(&confirmation.token.digest());
+        let pending: PendingVerification = match self.store.take(&key)? {
+            Some(value) => serde_json::from_str(&value).map_err(|_| invalid_token())?,
+            None => return Err(invalid_token()),
+        };
+        self.store.delete(&Self::account_key(&pending.account_id))?;
+
+        let mut account = match self.accounts.get_by_id(&pending.account_id.to_string())? {
+            Some(account)
+                if *account.status() != AccountStatus::Deleted
+                    && account.email() == &pending.email =>
+            {
+                account
+            }
+            _ => return Err(invalid_token()),
+        };
+
+        account.verify_email(get_now());
+        self.accounts.update(&account)
+    }
+
+    /**
+    Mengirim ulang email verifikasi. Pengiriman dibatasi per email, dan response selalu sama baik
+    email terdaftar maupun tidak agar tidak dapat digunakan untuk enumerasi account.
+
+    # Arguments
+    * `request` - Email dari user
+    */
+    pub fn resend(
+        &self,
+        request: &EmailVerificationResend,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let now = get_now();
+        // Jeda antar pengiriman menggunakan token bucket berkapasitas satu, sedangkan jumlah
+        // pengiriman dalam satu periode menggunakan sliding window
+        let interval = RateLimit {
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            key: RateLimitKey::Route,
+            limit: 1,
+            window: self.policy.resend_interval,
+        };
+        let window = RateLimit {
+            algorithm: RateLimitAlgorithm::SlidingWindow,
+            key: RateLimitKey::Route,
+            limit: self.policy.max_resends,
+            window: self.policy.resend_window,
+        };
+        let allowed = (self.policy.resend_interval == 0
+            || interval
+                .check(
+                    self.store,
+                    &Self::resend_key(&request.email, "interval"),
+                    now,
+                )?
+                .allowed)
+            && window
+                .check(self.store, &Self::resend_key(&request.email, "window"), now)?
+                .allowed;
+        if !allowed {
+            return Err(error::too_many_request_error!(
+                "Sorry, you have requested too many verification emails. Please try again later.",
+                "verification_resend_throttled"
+            ));
+        }
+
+        match self.accounts.get_by_email(&request.email)? {
+            Some(account) if *account.status() == AccountStatus::PendingVerification => {
+                // Kegagalan pengiriman tidak dikembalikan agar response tetap identik
+                let _ = self.send(&account);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{
+        mailer::capture::CapturingMailer, password::Hash, store::MemoryStore,
+    };
+    use super::super::account::AccountRole;
+    use super::super::fixture;
+    use super::super::repository::MockAccountRepository;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const VERIFY_URL: &str = "https://brokoli.id/verify-email";
+
+    fn repository() -> (MockAccountRepository, Arc<Mutex<Account>>) {
+        fixture::repository(Account::new(
+            Email::from(fixture::EMAIL),
+            Hash::from("hash"),
+            AccountRole::User,
+        ))
+    }
+
+    /// Account yang didaftarkan dengan status `PendingVerification`
+    fn pending_account(stored: &Arc<Mutex<Account>>) -> Account {
+        let mut account = stored.lock().unwrap();
+        account.require_email_verification();
+        account.clone()
+    }
+
+    fn token(message: &Message) -> String {
+        let (_, token) = message.body.split_once("token=").unwrap();
+        token.split_whitespace().next().unwrap().to_string()
+    }
+
+    fn confirmation(token: &str) -> EmailVerificationConfirmation {
+        serde_json::from_str(&format!(r#"{{"token": "{}"}}"#, token)).unwrap()
+    }
+
+    fn resend(email: &str) -> EmailVerificationResend {
+        serde_json::from_str(&format!(r#"{{"email": "{}"}}"#, email)).unwrap()
+    }
+
+    #[test]
+    fn test_start_and_confirm() {
+        let (accounts, stored) = repository();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service = EmailVerificationService::new(&accounts, &store, &mailer, VERIFY_URL);
+
+        let account = pending_account(&stored);
+        service.start(&account).unwrap();
+        assert_eq!(
+            AccountStatus::PendingVerification,
+            *stored.lock().unwrap().status()
+        );
+        let token = token(&mailer.sent()[0]);
+        assert!(mailer.sent()[0]
+            .body
+            .contains(&format!("{}?token=", VERIFY_URL)));
+
+        let error = service.confirm(&confirmation("invalid")).unwrap_err();
+        assert_eq!("invalid_verification_token", error.error);
+
+        service.confirm(&confirmation(&token)).unwrap();
+        assert_eq!(AccountStatus::Active, *stored.lock().unwrap().status());
+        assert!(stored.lock().unwrap().email_verified_at().is_some());
+
+        // Token hanya dapat digunakan sekali
+        let error = service.confirm(&confirmation(&token)).unwrap_err();
+        assert_eq!("invalid_verification_token", error.error);
+    }
+
+    #[test]
+    fn test_resend() {
+        let (accounts, stored) = repository();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service = EmailVerificationService::new(&accounts, &store, &mailer, VERIFY_URL)
+            .with_policy(EmailVerificationPolicy {
+                resend_interval: 0,
+                max_resends: 2,
+                ..EmailVerificationPolicy::default()
+            });
+
+        let account = pending_account(&stored);
+        service.start(&account).unwrap();
+        let first = token(&mailer.sent()[0]);
+
+        service.resend(&resend("harun@digitalsekuriti.id")).unwrap();
+        assert_eq!(2, mailer.sent().len());
+        let second = token(&mailer.sent()[1]);
+
+        // Hanya token terakhir yang berlaku
+        let error = service.confirm(&confirmation(&first)).unwrap_err();
+        assert_eq!("invalid_verification_token", error.error);
+
+        service.resend(&resend("harun@digitalsekuriti.id")).unwrap();
+        let error = service
+            .resend(&resend("harun@digitalsekuriti.id"))
+            .unwrap_err();
+        assert_eq!(429, error.code);
+        assert_eq!("verification_resend_throttled", error.error);
+        let third = token(&mailer.sent()[2]);
+        assert_ne!(second, third);
+        service.confirm(&confirmation(&third)).unwrap();
+
+        // Email tidak terdaftar atau sudah terverifikasi mendapatkan response yang sama
+        service
+            .resend(&resend("unknown@digitalsekuriti.id"))
+            .unwrap();
+        service
+            .resend(&resend("HARUN@digitalsekuriti.id"))
+            .unwrap_err();
+        assert_eq!(3, mailer.sent().len());
+    }
+
+    #[test]
+    fn test_resend_interval() {
+        let (accounts, stored) = repository();
+        let store = MemoryStore::new();
+        let mailer = CapturingMailer::new();
+        let service = EmailVerificationService::new(&accounts, &store, &mailer, VERIFY_URL);
+
+        let account = pending_account(&stored);
+        service.start(&account).unwrap();
+        service.resend(&resend("harun@digitalsekuriti.id")).unwrap();
+        let error = service
+            .resend(&resend("harun@digitalsekuriti.id"))
+            .unwrap_err();
+        assert_eq!("verification_resend_throttled", error.error);
+    }
+}
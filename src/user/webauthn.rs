@@ -0,0 +1,798 @@
+use super::super::sharedkernel::{
+    error,
+    function::{from_base64url, get_now, to_base64url},
+    password_policy::PasswordPolicy,
+    store::KeyValueStore,
+    webauthn::{self, ClientData, PublicKey, COSE_ALG_ES256},
+};
+use super::account::{Account, AccountStatus};
+use super::authentication::Authentication;
+use super::repository::AccountRepository;
+use super::risk::RiskEngine;
+use super::session::{
+    AuthenticationMethod, AuthenticationScope, Session, SessionRepository, DEFAULT_SESSION_TTL,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Konfigurasi relying party WebAuthn
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WebAuthnConfig {
+    /// Domain aplikasi, passkey hanya dapat digunakan pada domain ini dan subdomain-nya
+    pub rp_id: String,
+    pub rp_name: String,
+    /// Origin yang diharapkan pada client data, misal https://brokoli.id
+    pub origin: String,
+    /// Masa berlaku challenge dalam detik
+    pub challenge_ttl: u64,
+    /// Mewajibkan PIN/biometrik saat passkey digunakan sebagai faktor kedua. Login tanpa password
+    /// selalu mewajibkan user verification.
+    pub user_verification: bool,
+}
+
+impl Default for WebAuthnConfig {
+    fn default() -> Self {
+        WebAuthnConfig {
+            rp_id: String::from("localhost"),
+            rp_name: String::from("Brokoli"),
+            origin: String::from("http://localhost"),
+            challenge_ttl: 300,
+            user_verification: false,
+        }
+    }
+}
+
+/// Credential WebAuthn (passkey) yang terdaftar pada account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WebAuthnCredential {
+    /// Credential id dalam format base64url
+    id: String,
+    #[serde(skip_serializing)]
+    public_key: String,
+    sign_count: u32,
+    name: String,
+    created_at: u64,
+    last_used_at: Option<u64>,
+}
+
+impl WebAuthnCredential {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sign_count(&self) -> u32 {
+        self.sign_count
+    }
+
+    pub fn last_used_at(&self) -> Option<u64> {
+        self.last_used_at
+    }
+
+    /**
+    Mencatat penggunaan credential. Signature counter harus selalu naik, kecuali authenticator
+    tidak menggunakan counter (selalu 0). Counter yang tidak naik menandakan authenticator telah
+    digandakan.
+
+    # Arguments
+    * `sign_count` - Signature counter dari authenticator data
+    * `now` - Waktu saat ini dalam unix timestamp
+    */
+    pub fn record_use(
+        &mut self,
+        sign_count: u32,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        if (sign_count != 0 || self.sign_count != 0) && sign_count <= self.sign_count {
+            return Err(error::unauthorized_error!(
+                "Sorry, but this passkey can not be used. Please contact support.",
+                "passkey_counter_regression"
+            ));
+        }
+        self.sign_count = sign_count;
+        self.last_used_at = Some(now);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    pub id: String,
+}
+
+impl CredentialDescriptor {
+    fn from(credential: &WebAuthnCredential) -> CredentialDescriptor {
+        CredentialDescriptor {
+            kind: "public-key",
+            id: credential.id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    /// User handle (id account) dalam format base64url
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialParameter {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    alg: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    resident_key: &'static str,
+    user_verification: &'static str,
+}
+
+/// Parameter untuk `navigator.credentials.create()`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameter>,
+    /// Timeout dalam milidetik
+    pub timeout: u64,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: &'static str,
+}
+
+/// Parameter untuk `navigator.credentials.get()`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    /// Timeout dalam milidetik
+    pub timeout: u64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: &'static str,
+}
+
+/// Hasil `navigator.credentials.create()`, seluruh data biner dalam format base64url
+#[derive(Debug, Deserialize)]
+pub struct RegistrationResponse {
+    client_data_json: String,
+    attestation_object: String,
+    /// Nama credential yang ditampilkan kepada user, misal "MacBook" atau "YubiKey"
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Hasil `navigator.credentials.get()`, seluruh data biner dalam format base64url
+#[derive(Debug, Deserialize)]
+pub struct AssertionResponse {
+    credential_id: String,
+    client_data_json: String,
+    authenticator_data: String,
+    signature: String,
+    /// Wajib untuk login tanpa password (discoverable credential)
+    #[serde(default)]
+    user_handle: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+enum Ceremony {
+    #[serde(rename = "registration")]
+    Registration,
+    #[serde(rename = "authentication")]
+    Authentication,
+}
+
+/// Challenge yang menunggu response dari authenticator
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingChallenge {
+    ceremony: Ceremony,
+    /// None untuk login tanpa password, account belum diketahui saat challenge dibuat
+    account_id: Option<Uuid>,
+}
+
+fn invalid_passkey() -> error::ApplicationError<'static> {
+    error::unauthorized_error!(
+        "Sorry, but we could not verify your passkey. Please try again.",
+        "invalid_passkey"
+    )
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, error::ApplicationError<'static>> {
+    from_base64url(text).ok_or_else(invalid_passkey)
+}
+
+fn user_verification(required: bool) -> &'static str {
+    if required {
+        "required"
+    } else {
+        "preferred"
+    }
+}
+
+/**
+Use case registrasi dan autentikasi WebAuthn. Passkey dapat digunakan sebagai faktor kedua setelah
+login dengan password, maupun untuk login tanpa password.
+*/
+pub struct WebAuthnService<'a> {
+    accounts: &'a dyn AccountRepository,
+    sessions: &'a dyn SessionRepository,
+    store: &'a dyn KeyValueStore,
+    config: &'a WebAuthnConfig,
+    password_policy: &'a PasswordPolicy,
+    session_ttl: u64,
+    risk: Option<RiskEngine<'a>>,
+}
+
+impl<'a> WebAuthnService<'a> {
+    pub fn new(
+        accounts: &'a dyn AccountRepository,
+        sessions: &'a dyn SessionRepository,
+        store: &'a dyn KeyValueStore,
+        config: &'a WebAuthnConfig,
+        password_policy: &'a PasswordPolicy,
+    ) -> WebAuthnService<'a> {
+        WebAuthnService {
+            accounts,
+            sessions,
+            store,
+            config,
+            password_policy,
+            session_ttl: DEFAULT_SESSION_TTL,
+            risk: None,
+        }
+    }
+
+    /// Mengganti masa berlaku session (dalam detik) yang dibuat saat login tanpa password
+    pub fn with_session_ttl(mut self, ttl: u64) -> WebAuthnService<'a> {
+        self.session_ttl = ttl;
+        self
+    }
+
+    /// Mencatat penyelesaian step up pada riwayat login, agar perangkat user dikenal oleh risk engine
+    pub fn with_risk_engine(mut self, risk: RiskEngine<'a>) -> WebAuthnService<'a> {
+        self.risk = Some(risk);
+        self
+    }
+
+    fn account(&self, account_id: &Uuid) -> Result<Account, error::ApplicationError<'static>> {
+        match self.accounts.get_by_id(&account_id.to_string())? {
+            Some(account) if *account.status() == AccountStatus::Active => Ok(account),
+            _ => Err(invalid_passkey()),
+        }
+    }
+
+    fn issue_challenge(
+        &self,
+        ceremony: Ceremony,
+        account_id: Option<Uuid>,
+    ) -> Result<String, error::ApplicationError<'static>> {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let challenge = to_base64url(&bytes);
+        let pending = PendingChallenge {
+            ceremony,
+            account_id,
+        };
+        let value = serde_json::to_string(&pending).map_err(|_| error::internal_server_error!())?;
+        self.store.set(
+            &format!("webauthn:challenge:{}", challenge),
+            &value,
+            self.config.challenge_ttl,
+        )?;
+        Ok(challenge)
+    }
+
+    /**
+    Memeriksa client data lalu mengambil dan menghapus challenge-nya, sehingga setiap challenge
+    hanya dapat digunakan satu kali
+
+    # Arguments
+    * `client_data_json` - clientDataJSON dari browser
+    * `ceremony` - Jenis ceremony yang diharapkan
+    */
+    fn take_challenge(
+        &self,
+        client_data_json: &[u8],
+        ceremony: Ceremony,
+    ) -> Result<PendingChallenge, error::ApplicationError<'static>> {
+        let client_data = ClientData::parse(client_data_json)?;
+        let expected_type = match ceremony {
+            Ceremony::Registration => "webauthn.create",
+            Ceremony::Authentication => "webauthn.get",
+        };
+        if client_data.ceremony != expected_type || client_data.origin != self.config.origin {
+            return Err(invalid_passkey());
+        }
+
+        // Challenge diambil dan dihapus secara atomic, sehingga hanya dapat digunakan oleh satu request
+        let key = format!("webauthn:challenge:{}", client_data.challenge);
+        let value = self.store.take(&key)?.ok_or_else(invalid_passkey)?;
+        let pending: PendingChallenge =
+            serde_json::from_str(&value).map_err(|_| invalid_passkey())?;
+        if pending.ceremony != ceremony {
+            return Err(invalid_passkey());
+        }
+        Ok(pending)
+    }
+
+    /**
+    Memulai registrasi passkey untuk user yang sedang login
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    */
+    pub fn start_registration(
+        &self,
+        session: &Session,
+    ) -> Result<CreationOptions, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let account = self.account(session.account_id())?;
+        let challenge = self.issue_challenge(Ceremony::Registration, Some(*account.id()))?;
+
+        Ok(CreationOptions {
+            challenge,
+            rp: RelyingParty {
+                id: self.config.rp_id.clone(),
+                name: self.config.rp_name.clone(),
+            },
+            user: UserEntity {
+                id: to_base64url(account.id().as_bytes()),
+                name: account.email().to_string(),
+                display_name: account.email().to_string(),
+            },
+            pub_key_cred_params: vec![CredentialParameter {
+                kind: "public-key",
+                alg: COSE_ALG_ES256,
+            }],
+            timeout: self.config.challenge_ttl * 1000,
+            exclude_credentials: account
+                .webauthn_credentials()
+                .iter()
+                .map(CredentialDescriptor::from)
+                .collect(),
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: "preferred",
+                user_verification: user_verification(self.config.user_verification),
+            },
+            attestation: "direct",
+        })
+    }
+
+    /**
+    Menyelesaikan registrasi passkey dan menyimpan credential pada account
+
+    # Arguments
+    * `session` - Session user yang sedang login
+    * `response` - Hasil `navigator.credentials.create()`
+    */
+    pub fn finish_registration(
+        &self,
+        session: &Session,
+        response: &RegistrationResponse,
+    ) -> Result<WebAuthnCredential, error::ApplicationError<'static>> {
+        session.ensure_full_access()?;
+        let client_data_json = decode(&response.client_data_json)?;
+        let pending = self.take_challenge(&client_data_json, Ceremony::Registration)?;
+        if pending.account_id.as_ref() != Some(session.account_id()) {
+            return Err(invalid_passkey());
+        }
+
+        let auth_data = webauthn::verify_attestation(
+            &decode(&response.attestation_object)?,
+            &client_data_json,
+        )?;
+        auth_data.ensure_valid(&self.config.rp_id, self.config.user_verification)?;
+        let attested = auth_data.credential.ok_or_else(invalid_passkey)?;
+
+        let mut account = self.account(session.account_id())?;
+        let id = to_base64url(&attested.credential_id);
+        if account.webauthn_credential_mut(&id).is_some() {
+            return Err(error::bad_request_error!(
+                "This passkey is already registered to your account.",
+                "passkey_already_registered"
+            ));
+        }
+
+        let credential = WebAuthnCredential {
+            id,
+            public_key: attested.public_key.encode(),
+            sign_count: auth_data.sign_count,
+            name: response
+                .name
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .unwrap_or("Passkey")
+                .to_string(),
+            created_at: get_now(),
+            last_used_at: None,
+        };
+        account.add_webauthn_credential(credential.clone());
+        self.accounts.update(&account)?;
+        Ok(credential)
+    }
+
+    /// Memulai login tanpa password, authenticator memilih sendiri passkey yang tersimpan
+    pub fn start_login(&self) -> Result<RequestOptions, error::ApplicationError<'static>> {
+        Ok(RequestOptions {
+            challenge: self.issue_challenge(Ceremony::Authentication, None)?,
+            rp_id: self.config.rp_id.clone(),
+            timeout: self.config.challenge_ttl * 1000,
+            allow_credentials: Vec::new(),
+            user_verification: user_verification(true),
+        })
+    }
+
+    /**
+    Memulai verifikasi passkey sebagai faktor kedua
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    */
+    pub fn start_step_up(
+        &self,
+        session: &Session,
+    ) -> Result<RequestOptions, error::ApplicationError<'static>> {
+        let account = self.account(session.account_id())?;
+        Ok(RequestOptions {
+            challenge: self.issue_challenge(Ceremony::Authentication, Some(*account.id()))?,
+            rp_id: self.config.rp_id.clone(),
+            timeout: self.config.challenge_ttl * 1000,
+            allow_credentials: account
+                .webauthn_credentials()
+                .iter()
+                .map(CredentialDescriptor::from)
+                .collect(),
+            user_verification: user_verification(self.config.user_verification),
+        })
+    }
+
+    // Memverifikasi assertion dengan credential milik account dan mencatat signature counter
+    fn verify_assertion(
+        &self,
+        account: &mut Account,
+        response: &AssertionResponse,
+        client_data_json: &[u8],
+        user_verification: bool,
+        now: u64,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let credential = account
+            .webauthn_credential_mut(&response.credential_id)
+            .ok_or_else(invalid_passkey)?;
+        let auth_data = webauthn::verify_assertion(
+            &PublicKey::decode(&credential.public_key)?,
+            &decode(&response.authenticator_data)?,
+            client_data_json,
+            &decode(&response.signature)?,
+        )?;
+        auth_data.ensure_valid(&self.config.rp_id, user_verification)?;
+        credential.record_use(auth_data.sign_count, now)
+    }
+
+    /**
+    Login tanpa password menggunakan passkey. Passkey dengan user verification sudah memenuhi dua
+    faktor, sehingga session langsung mendapatkan akses penuh (kecuali password wajib diganti).
+
+    # Arguments
+    * `response` - Hasil `navigator.credentials.get()`
+    */
+    pub fn login(
+        &self,
+        response: &AssertionResponse,
+    ) -> Result<Authentication, error::ApplicationError<'static>> {
+        let client_data_json = decode(&response.client_data_json)?;
+        let pending = self.take_challenge(&client_data_json, Ceremony::Authentication)?;
+        if pending.account_id.is_some() {
+            return Err(invalid_passkey());
+        }
+
+        let user_handle = decode(response.user_handle.as_deref().unwrap_or_default())?;
+        let account_id = Uuid::from_slice(&user_handle).map_err(|_| invalid_passkey())?;
+        let mut account = self.account(&account_id)?;
+        let now = get_now();
+        self.verify_assertion(&mut account, response, &client_data_json, true, now)?;
+        self.accounts.update(&account)?;
+
+        let scope = if account.requires_password_change(self.password_policy.max_age, now) {
+            AuthenticationScope::PasswordChange
+        } else {
+            AuthenticationScope::Full
+        };
+        let mut session = Session::new(account.id(), scope, self.session_ttl);
+        session.record_authentication(AuthenticationMethod::HardwareKey, now);
+        self.sessions.create(&session)?;
+
+        Ok(Authentication {
+            account,
+            session,
+            risk: None,
+        })
+    }
+
+    /**
+    Memverifikasi passkey sebagai faktor kedua, lalu menaikkan scope session
+
+    # Arguments
+    * `session` - Session dengan scope `StepUp` hasil login
+    * `response` - Hasil `navigator.credentials.get()`
+    */
+    pub fn step_up(
+        &self,
+        session: &mut Session,
+        response: &AssertionResponse,
+    ) -> Result<(), error::ApplicationError<'static>> {
+        let client_data_json = decode(&response.client_data_json)?;
+        let pending = self.take_challenge(&client_data_json, Ceremony::Authentication)?;
+        if pending.account_id.as_ref() != Some(session.account_id()) {
+            return Err(invalid_passkey());
+        }
+
+        let mut account = self.account(session.account_id())?;
+        let now = get_now();
+        self.verify_assertion(
+            &mut account,
+            response,
+            &client_data_json,
+            self.config.user_verification,
+            now,
+        )?;
+        self.accounts.update(&account)?;
+
+        session.record_authentication(AuthenticationMethod::HardwareKey, now);
+        session
+            .complete_step_up(account.requires_password_change(self.password_policy.max_age, now));
+        if let Some(risk) = &self.risk {
+            risk.complete_step_up(session, now)?;
+        }
+        self.sessions.update(session)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::sharedkernel::{
+        store::MemoryStore, webauthn::software::SoftwareAuthenticator,
+    };
+    use super::super::fixture;
+    use super::super::repository::MockAccountRepository;
+    use super::super::session::MockSessionRepository;
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn config() -> WebAuthnConfig {
+        WebAuthnConfig {
+            rp_id: String::from("brokoli.id"),
+            origin: String::from("https://brokoli.id"),
+            ..WebAuthnConfig::default()
+        }
+    }
+
+    /// Repository satu account yang menyimpan perubahan terakhir
+    fn accounts() -> (MockAccountRepository, Arc<Mutex<Account>>, Uuid) {
+        let account = fixture::account();
+        let id = *account.id();
+        let (accounts, stored) = fixture::repository(account);
+        (accounts, stored, id)
+    }
+
+    fn sessions() -> MockSessionRepository {
+        let mut sessions = MockSessionRepository::new();
+        sessions.expect_create().returning(|_| Ok(()));
+        sessions.expect_update().returning(|_| Ok(()));
+        sessions
+    }
+
+    fn register(
+        service: &WebAuthnService,
+        session: &Session,
+        authenticator: &SoftwareAuthenticator,
+        format: &str,
+    ) -> Result<WebAuthnCredential, error::ApplicationError<'static>> {
+        let options = service.start_registration(session).unwrap();
+        let (client_data, object) = authenticator.attest(
+            &options.rp.id,
+            &options.challenge,
+            "https://brokoli.id",
+            format,
+        );
+        service.finish_registration(
+            session,
+            &RegistrationResponse {
+                client_data_json: to_base64url(&client_data),
+                attestation_object: to_base64url(&object),
+                name: Some(String::from(" YubiKey ")),
+            },
+        )
+    }
+
+    fn assertion(
+        authenticator: &mut SoftwareAuthenticator,
+        options: &RequestOptions,
+        account_id: &Uuid,
+    ) -> AssertionResponse {
+        let (client_data, auth_data, signature) =
+            authenticator.assert(&options.rp_id, &options.challenge, "https://brokoli.id");
+        AssertionResponse {
+            credential_id: to_base64url(&authenticator.credential_id),
+            client_data_json: to_base64url(&client_data),
+            authenticator_data: to_base64url(&auth_data),
+            signature: to_base64url(&signature),
+            user_handle: Some(to_base64url(account_id.as_bytes())),
+        }
+    }
+
+    #[test]
+    fn test_register_and_login_without_password() {
+        let (accounts, stored, account_id) = accounts();
+        let sessions = sessions();
+        let store = MemoryStore::new();
+        let config = config();
+        let policy = PasswordPolicy::default();
+        let service = WebAuthnService::new(&accounts, &sessions, &store, &config, &policy);
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let mut authenticator = SoftwareAuthenticator::new();
+
+        let credential = register(&service, &session, &authenticator, "packed").unwrap();
+        assert_eq!("YubiKey", credential.name());
+        assert!(stored.lock().unwrap().is_mfa_enabled());
+
+        // Credential yang sama tidak dapat didaftarkan dua kali
+        let error = register(&service, &session, &authenticator, "none").unwrap_err();
+        assert_eq!("passkey_already_registered", error.error);
+        let options = service.start_registration(&session).unwrap();
+        assert_eq!(1, options.exclude_credentials.len());
+
+        let options = service.start_login().unwrap();
+        assert!(options.allow_credentials.is_empty());
+        let response = assertion(&mut authenticator, &options, &account_id);
+        let authentication = service.login(&response).unwrap();
+        assert_eq!(&account_id, authentication.account.id());
+        assert_eq!(AuthenticationScope::Full, authentication.session.scope());
+        let account = stored.lock().unwrap();
+        let credential = &account.webauthn_credentials()[0];
+        assert_eq!(1, credential.sign_count());
+        assert!(credential.last_used_at().is_some());
+        drop(account);
+
+        // Challenge hanya dapat digunakan satu kali
+        let error = service.login(&response).unwrap_err();
+        assert_eq!("invalid_passkey", error.error);
+
+        // Login tanpa password selalu membutuhkan user verification
+        authenticator.user_verified = false;
+        let options = service.start_login().unwrap();
+        let response = assertion(&mut authenticator, &options, &account_id);
+        assert!(service.login(&response).is_err());
+    }
+
+    #[test]
+    fn test_step_up_with_passkey() {
+        let (accounts, _, account_id) = accounts();
+        let sessions = sessions();
+        let store = MemoryStore::new();
+        let config = config();
+        let policy = PasswordPolicy::default();
+        let service = WebAuthnService::new(&accounts, &sessions, &store, &config, &policy);
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let mut authenticator = SoftwareAuthenticator::new();
+        register(&service, &session, &authenticator, "none").unwrap();
+
+        let mut login = Session::new(&account_id, AuthenticationScope::StepUp, 60);
+        let error = service.start_registration(&login).unwrap_err();
+        assert_eq!("step_up_required", error.error);
+
+        // Challenge login tanpa password tidak dapat digunakan untuk step up
+        let options = service.start_login().unwrap();
+        let response = assertion(&mut authenticator, &options, &account_id);
+        assert!(service.step_up(&mut login, &response).is_err());
+
+        let options = service.start_step_up(&login).unwrap();
+        assert_eq!(1, options.allow_credentials.len());
+        let response = assertion(&mut authenticator, &options, &account_id);
+        service.step_up(&mut login, &response).unwrap();
+        assert_eq!(AuthenticationScope::Full, login.scope());
+    }
+
+    #[test]
+    fn test_reject_invalid_ceremonies() {
+        let (accounts, _, account_id) = accounts();
+        let sessions = sessions();
+        let store = MemoryStore::new();
+        let config = config();
+        let policy = PasswordPolicy::default();
+        let service = WebAuthnService::new(&accounts, &sessions, &store, &config, &policy);
+        let session = Session::new(&account_id, AuthenticationScope::Full, 60);
+        let mut authenticator = SoftwareAuthenticator::new();
+
+        // Origin lain (phishing) ditolak
+        let options = service.start_registration(&session).unwrap();
+        let (client_data, object) = authenticator.attest(
+            "brokoli.id",
+            &options.challenge,
+            "https://brokoli.evil",
+            "none",
+        );
+        let response = RegistrationResponse {
+            client_data_json: to_base64url(&client_data),
+            attestation_object: to_base64url(&object),
+            name: None,
+        };
+        let error = service
+            .finish_registration(&session, &response)
+            .unwrap_err();
+        assert_eq!("invalid_passkey", error.error);
+
+        // RP id lain ditolak
+        let options = service.start_registration(&session).unwrap();
+        let (client_data, object) =
+            authenticator.attest("evil.id", &options.challenge, "https://brokoli.id", "none");
+        let response = RegistrationResponse {
+            client_data_json: to_base64url(&client_data),
+            attestation_object: to_base64url(&object),
+            name: None,
+        };
+        let error = service
+            .finish_registration(&session, &response)
+            .unwrap_err();
+        assert_eq!("invalid_webauthn_response", error.error);
+
+        // Authenticator yang digandakan terdeteksi dari signature counter
+        register(&service, &session, &authenticator, "none").unwrap();
+        let options = service.start_login().unwrap();
+        service
+            .login(&assertion(&mut authenticator, &options, &account_id))
+            .unwrap();
+        authenticator.sign_count = 0;
+        let options = service.start_login().unwrap();
+        let error = service
+            .login(&assertion(&mut authenticator, &options, &account_id))
+            .unwrap_err();
+        assert_eq!("passkey_counter_regression", error.error);
+
+        // Credential milik authenticator lain ditolak
+        let mut other = SoftwareAuthenticator::new();
+        let options = service.start_login().unwrap();
+        let error = service
+            .login(&assertion(&mut other, &options, &account_id))
+            .unwrap_err();
+        assert_eq!("invalid_passkey", error.error);
+    }
+
+    #[test]
+    fn test_record_use_without_counter() {
+        let mut credential = WebAuthnCredential {
+            id: String::from("abc"),
+            public_key: String::new(),
+            sign_count: 0,
+            name: String::from("Passkey"),
+            created_at: 0,
+            last_used_at: None,
+        };
+        credential.record_use(0, 10).unwrap();
+        credential.record_use(0, 20).unwrap();
+        assert_eq!(Some(20), credential.last_used_at());
+        credential.record_use(5, 30).unwrap();
+        assert!(credential.record_use(5, 40).is_err());
+        assert!(credential.record_use(0, 40).is_err());
+    }
+}